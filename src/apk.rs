@@ -0,0 +1,446 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_TABLE_TYPE: u16 = 0x0002;
+const RES_TABLE_PACKAGE_TYPE: u16 = 0x0200;
+const RES_TABLE_TYPE_TYPE: u16 = 0x0201;
+
+const UTF8_FLAG: u32 = 0x100;
+const NO_ENTRY: u32 = 0xffff_ffff;
+const FLAG_SPARSE: u8 = 0x01;
+const FLAG_OFFSET16: u8 = 0x02;
+const FLAG_COMPACT: u16 = 0x08;
+
+/// Reads the names of the strings in a built APK's resources.arsc, or in the
+/// resources.pb of each module of an app bundle (AAB).
+pub fn string_names(path: &Path) -> Result<HashSet<String>> {
+    let mut archive = ZipArchive::new(File::open(path)?)
+        .with_context(|| format!("{} isn't an APK or app bundle", path.display()))?;
+    let tables: Vec<String> = archive
+        .file_names()
+        .filter_map(|name| name.ok())
+        .filter(|name| *name == "resources.arsc" || name.ends_with("/resources.pb"))
+        .map(|name| name.to_string())
+        .collect();
+    if tables.is_empty() {
+        return Err(anyhow!(
+            "No resources.arsc or resources.pb in {}",
+            path.display()
+        ));
+    }
+
+    let mut names = HashSet::new();
+    for table in tables {
+        let mut data = Vec::new();
+        archive.by_name(&table)?.read_to_end(&mut data)?;
+        let parsed = if table.ends_with(".arsc") {
+            arsc_string_names(&data)
+        } else {
+            pb_string_names(&data)
+        };
+        names.extend(parsed.with_context(|| format!("Failed to read {}", table))?);
+    }
+
+    Ok(names)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| anyhow!("Truncated resource table"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| anyhow!("Truncated resource table"))
+}
+
+/// The chunks following a chunk header of `header_size` bytes, as their type
+/// and their bytes.
+fn chunks(data: &[u8], header_size: usize) -> Result<Vec<(u16, &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut offset = header_size;
+    while offset + 8 <= data.len() {
+        let size = u32_at(data, offset + 4)? as usize;
+        let chunk = data
+            .get(offset..offset + size)
+            .filter(|_| size >= 8)
+            .ok_or_else(|| anyhow!("Truncated resource table"))?;
+        chunks.push((u16_at(data, offset)?, chunk));
+        offset += size;
+    }
+
+    Ok(chunks)
+}
+
+/// Reads the strings in the string pool chunk at the start of `data`.
+fn string_pool(data: &[u8]) -> Result<Vec<String>> {
+    if u16_at(data, 0)? != RES_STRING_POOL_TYPE {
+        return Err(anyhow!("Expected a string pool"));
+    }
+    let header_size = u16_at(data, 2)? as usize;
+    let count = u32_at(data, 8)? as usize;
+    let utf8 = u32_at(data, 16)? & UTF8_FLAG != 0;
+    let strings_start = u32_at(data, 20)? as usize;
+
+    let mut strings = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut offset = strings_start + u32_at(data, header_size + i * 4)? as usize;
+        let string = if utf8 {
+            // The length in characters, then in bytes; each is one byte, or
+            // two if the high bit is set.
+            let mut length = || -> Result<usize> {
+                let first = *data
+                    .get(offset)
+                    .ok_or_else(|| anyhow!("Truncated string"))?;
+                offset += 1;
+                if first & 0x80 == 0 {
+                    return Ok(first as usize);
+                }
+                let second = *data
+                    .get(offset)
+                    .ok_or_else(|| anyhow!("Truncated string"))?;
+                offset += 1;
+                Ok(((first as usize & 0x7f) << 8) | second as usize)
+            };
+            length()?;
+            let length = length()?;
+            let bytes = data
+                .get(offset..offset + length)
+                .ok_or_else(|| anyhow!("Truncated string"))?;
+            String::from_utf8_lossy(bytes).into_owned()
+        } else {
+            let mut length = u16_at(data, offset)? as usize;
+            offset += 2;
+            if length & 0x8000 != 0 {
+                length = ((length & 0x7fff) << 16) | u16_at(data, offset)? as usize;
+                offset += 2;
+            }
+            let units = (0..length)
+                .map(|i| u16_at(data, offset + i * 2))
+                .collect::<Result<Vec<u16>>>()?;
+            String::from_utf16_lossy(&units)
+        };
+        strings.push(string);
+    }
+
+    Ok(strings)
+}
+
+/// Reads the names of the strings in a compiled resource table.
+fn arsc_string_names(data: &[u8]) -> Result<HashSet<String>> {
+    if u16_at(data, 0)? != RES_TABLE_TYPE {
+        return Err(anyhow!("Not a resource table"));
+    }
+
+    let mut names = HashSet::new();
+    for (chunk_type, package) in chunks(data, u16_at(data, 2)? as usize)? {
+        if chunk_type != RES_TABLE_PACKAGE_TYPE {
+            continue;
+        }
+        // After the chunk header come the package id and its name, 128 UTF-16
+        // units, then the offsets of the type and key string pools.
+        let type_strings = u32_at(package, 8 + 4 + 256)? as usize;
+        let key_strings = u32_at(package, 8 + 4 + 256 + 8)? as usize;
+        let types = string_pool(package.get(type_strings..).unwrap_or_default())?;
+        let keys = string_pool(package.get(key_strings..).unwrap_or_default())?;
+        let string_type_id = match types.iter().position(|name| name == "string") {
+            Some(position) => position + 1,
+            None => continue,
+        };
+
+        for (chunk_type, chunk) in chunks(package, u16_at(package, 2)? as usize)? {
+            if chunk_type == RES_TABLE_TYPE_TYPE && chunk.get(8) == Some(&(string_type_id as u8)) {
+                for key in type_entry_keys(chunk)? {
+                    let name = keys
+                        .get(key as usize)
+                        .ok_or_else(|| anyhow!("Bad key string index {}", key))?;
+                    names.insert(name.clone());
+                }
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// The key string index of each entry in a type chunk, which holds one
+/// configuration's values of one resource type.
+fn type_entry_keys(chunk: &[u8]) -> Result<Vec<u32>> {
+    let header_size = u16_at(chunk, 2)? as usize;
+    let flags = *chunk.get(9).ok_or_else(|| anyhow!("Truncated type"))?;
+    let entry_count = u32_at(chunk, 12)? as usize;
+    let entries_start = u32_at(chunk, 16)? as usize;
+
+    let mut keys = Vec::new();
+    for i in 0..entry_count {
+        let offset = if flags & FLAG_SPARSE != 0 {
+            u16_at(chunk, header_size + i * 4 + 2)? as u32 * 4
+        } else if flags & FLAG_OFFSET16 != 0 {
+            match u16_at(chunk, header_size + i * 2)? {
+                0xffff => continue,
+                offset => offset as u32 * 4,
+            }
+        } else {
+            match u32_at(chunk, header_size + i * 4)? {
+                NO_ENTRY => continue,
+                offset => offset,
+            }
+        };
+        let entry = entries_start + offset as usize;
+        let key = if u16_at(chunk, entry + 2)? & FLAG_COMPACT != 0 {
+            u16_at(chunk, entry)? as u32
+        } else {
+            u32_at(chunk, entry + 4)?
+        };
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+/// The length-delimited fields of a protobuf message, as their number and
+/// bytes. Other fields are skipped.
+fn pb_fields(mut data: &[u8]) -> Result<Vec<(u64, &[u8])>> {
+    fn varint(data: &mut &[u8]) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = data
+                .split_first()
+                .ok_or_else(|| anyhow!("Truncated protobuf"))?;
+            *data = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Bad protobuf varint"))
+    }
+
+    let mut fields = Vec::new();
+    while !data.is_empty() {
+        let key = varint(&mut data)?;
+        let skip = match key & 7 {
+            0 => {
+                varint(&mut data)?;
+                0
+            }
+            1 => 8,
+            2 => {
+                let length = varint(&mut data)? as usize;
+                let value = data
+                    .get(..length)
+                    .ok_or_else(|| anyhow!("Truncated protobuf"))?;
+                fields.push((key >> 3, value));
+                length
+            }
+            5 => 4,
+            wire_type => return Err(anyhow!("Unsupported protobuf wire type {}", wire_type)),
+        };
+        data = data
+            .get(skip..)
+            .ok_or_else(|| anyhow!("Truncated protobuf"))?;
+    }
+
+    Ok(fields)
+}
+
+/// Reads the names of the strings in an app bundle module's resource table,
+/// an aapt2 ResourceTable message: packages (field 2) hold types (3), which
+/// have a name (2) and entries (3), each with a name (2).
+fn pb_string_names(data: &[u8]) -> Result<HashSet<String>> {
+    let name_of = |message: &[u8]| -> Result<Option<String>> {
+        Ok(pb_fields(message)?
+            .into_iter()
+            .find(|(field, _)| *field == 2)
+            .map(|(_, name)| String::from_utf8_lossy(name).into_owned()))
+    };
+
+    let mut names = HashSet::new();
+    for (_, package) in pb_fields(data)?.into_iter().filter(|(f, _)| *f == 2) {
+        for (_, resource_type) in pb_fields(package)?.into_iter().filter(|(f, _)| *f == 3) {
+            if name_of(resource_type)?.as_deref() != Some("string") {
+                continue;
+            }
+            for (_, entry) in pb_fields(resource_type)?
+                .into_iter()
+                .filter(|(f, _)| *f == 3)
+            {
+                names.extend(name_of(entry)?);
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Hand-built resource tables and archives, for tests here and in the
+/// modules reading them.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    fn string_pool_chunk(strings: &[&str]) -> Vec<u8> {
+        let header_size = 28;
+        let strings_start = header_size + strings.len() * 4;
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for string in strings {
+            offsets.extend((data.len() as u32).to_le_bytes());
+            data.extend([string.len() as u8, string.len() as u8]);
+            data.extend(string.as_bytes());
+            data.push(0);
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+
+        let mut chunk = Vec::new();
+        chunk.extend(RES_STRING_POOL_TYPE.to_le_bytes());
+        chunk.extend((header_size as u16).to_le_bytes());
+        chunk.extend(((strings_start + data.len()) as u32).to_le_bytes());
+        chunk.extend((strings.len() as u32).to_le_bytes());
+        chunk.extend(0u32.to_le_bytes());
+        chunk.extend(UTF8_FLAG.to_le_bytes());
+        chunk.extend((strings_start as u32).to_le_bytes());
+        chunk.extend(0u32.to_le_bytes());
+        chunk.extend(offsets);
+        chunk.extend(data);
+        chunk
+    }
+
+    /// A type chunk of `type_id` holding an entry for each of `keys`, with
+    /// NO_ENTRY in between.
+    fn type_chunk(type_id: u8, keys: &[u32]) -> Vec<u8> {
+        let header_size = 20 + 4;
+        let entry_count = keys.len() * 2;
+        let entries_start = header_size + entry_count * 4;
+
+        let mut chunk = Vec::new();
+        chunk.extend(RES_TABLE_TYPE_TYPE.to_le_bytes());
+        chunk.extend((header_size as u16).to_le_bytes());
+        chunk.extend(((entries_start + keys.len() * 16) as u32).to_le_bytes());
+        chunk.extend([type_id, 0, 0, 0]);
+        chunk.extend((entry_count as u32).to_le_bytes());
+        chunk.extend((entries_start as u32).to_le_bytes());
+        // An empty configuration, just its size.
+        chunk.extend(4u32.to_le_bytes());
+        for i in 0..keys.len() {
+            chunk.extend(((i * 16) as u32).to_le_bytes());
+            chunk.extend(NO_ENTRY.to_le_bytes());
+        }
+        for key in keys {
+            chunk.extend(8u16.to_le_bytes());
+            chunk.extend(0u16.to_le_bytes());
+            chunk.extend(key.to_le_bytes());
+            chunk.extend([8, 0, 0, 3, 0, 0, 0, 0]);
+        }
+        chunk
+    }
+
+    /// A resources.arsc with an entry for each of `strings`, and a drawable.
+    pub fn resources_arsc(strings: &[&str]) -> Vec<u8> {
+        let package_header_size = 8 + 4 + 256 + 16 + 4;
+        let types = string_pool_chunk(&["drawable", "string"]);
+        let mut key_names = vec!["icon"];
+        key_names.extend(strings);
+        let keys = string_pool_chunk(&key_names);
+        let string_keys: Vec<u32> = (1..=strings.len() as u32).collect();
+
+        let mut body = Vec::new();
+        body.extend(&types);
+        body.extend(&keys);
+        body.extend(type_chunk(1, &[0]));
+        body.extend(type_chunk(2, &string_keys));
+
+        let mut package = Vec::new();
+        package.extend(RES_TABLE_PACKAGE_TYPE.to_le_bytes());
+        package.extend((package_header_size as u16).to_le_bytes());
+        package.extend(((package_header_size + body.len()) as u32).to_le_bytes());
+        package.extend(0x7fu32.to_le_bytes());
+        package.extend([0; 256]);
+        package.extend((package_header_size as u32).to_le_bytes());
+        package.extend(0u32.to_le_bytes());
+        package.extend(((package_header_size + types.len()) as u32).to_le_bytes());
+        package.extend(0u32.to_le_bytes());
+        package.extend(0u32.to_le_bytes());
+        package.extend(body);
+
+        let mut table = Vec::new();
+        table.extend(RES_TABLE_TYPE.to_le_bytes());
+        table.extend(12u16.to_le_bytes());
+        table.extend(((12 + package.len()) as u32).to_le_bytes());
+        table.extend(1u32.to_le_bytes());
+        table.extend(package);
+        table
+    }
+
+    /// Writes a zip holding `files`, like an APK or app bundle.
+    pub fn write_zip(path: &Path, files: &[(&str, &[u8])]) -> Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default())?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{resources_arsc, write_zip};
+    use super::*;
+    use tempdir::TempDir;
+
+    fn pb_field(field: u8, value: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![field << 3 | 2, value.len() as u8];
+        bytes.extend(value);
+        bytes
+    }
+
+    #[test]
+    fn test_string_names() -> Result<()> {
+        let tmp_dir = TempDir::new("apk")?;
+        let sorted = |names: HashSet<String>| {
+            let mut names: Vec<String> = names.into_iter().collect();
+            names.sort();
+            names
+        };
+
+        let apk = tmp_dir.path().join("app.apk");
+        write_zip(
+            &apk,
+            &[
+                ("AndroidManifest.xml", b""),
+                ("resources.arsc", &resources_arsc(&["app_name", "title"])),
+            ],
+        )?;
+        assert_eq!(sorted(string_names(&apk)?), vec!["app_name", "title"]);
+
+        let mut string_type = pb_field(2, b"string");
+        string_type.extend(pb_field(3, &pb_field(2, b"welcome")));
+        let mut drawable_type = pb_field(2, b"drawable");
+        drawable_type.extend(pb_field(3, &pb_field(2, b"icon")));
+        let mut package = pb_field(2, b"com.example");
+        package.extend(pb_field(3, &drawable_type));
+        package.extend(pb_field(3, &string_type));
+        let aab = tmp_dir.path().join("app.aab");
+        write_zip(&aab, &[("base/resources.pb", &pb_field(2, &package))])?;
+        assert_eq!(sorted(string_names(&aab)?), vec!["welcome"]);
+
+        let empty = tmp_dir.path().join("empty.apk");
+        write_zip(&empty, &[("classes.dex", b"")])?;
+        assert!(string_names(&empty).is_err());
+
+        Ok(())
+    }
+}
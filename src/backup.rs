@@ -0,0 +1,125 @@
+use crate::index::PathRoots;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Serialize)]
+struct Entry {
+    file: PathBuf,
+    backup: PathBuf,
+    removed: Vec<String>,
+}
+
+/// Copies files into a backup directory before they're edited, and records
+/// what was changed in each in a manifest alongside the copies.
+pub struct Backup {
+    dir: PathBuf,
+    roots: PathRoots,
+    entries: Vec<Entry>,
+}
+
+impl Backup {
+    /// Copies go under `dir`, in a directory for each of `roots`.
+    pub fn new(dir: PathBuf, roots: PathRoots) -> Result<Backup> {
+        fs::create_dir_all(&dir)?;
+        Ok(Backup {
+            dir,
+            roots,
+            entries: Vec::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Backs up `path` unless it's already been backed up, so the copy is
+    /// always of the file before any edits. `removed` names what is about to
+    /// be removed from it.
+    pub fn save(&mut self, path: &Path, removed: Vec<String>) -> Result<()> {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.file == path) {
+            entry.removed.extend(removed);
+            return self.write_manifest();
+        }
+
+        // Mirror the file's path below its root under the root's name, so
+        // files with the same name in different directories don't collide.
+        let (root, relative) = self
+            .roots
+            .relative(path)
+            .ok_or_else(|| anyhow!("Can't back up {:?}, which isn't under the roots", path))?;
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(anyhow!(
+                "Can't back up {:?}, which isn't a plain path",
+                path
+            ));
+        }
+        let backup = self.dir.join(root).join(relative);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(path, &backup)?;
+
+        self.entries.push(Entry {
+            file: path.to_path_buf(),
+            backup,
+            removed,
+        });
+        self.write_manifest()
+    }
+
+    /// Rewritten after each file is saved, so the manifest is complete even if
+    /// a later edit fails.
+    fn write_manifest(&self) -> Result<()> {
+        fs::write(
+            self.dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.entries)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_backup() -> Result<()> {
+        let tmp_dir = TempDir::new("backup")?;
+        let res = tmp_dir.path().join("res");
+        let values = res.join("values");
+        fs::create_dir_all(&values)?;
+        let file = values.join("strings.xml");
+        fs::write(&file, "original")?;
+        let roots =
+            Indexer::new(tmp_dir.path().join("java"), res.clone(), None, None)?.path_roots();
+
+        let mut backup = Backup::new(tmp_dir.path().join("backup"), roots)?;
+        backup.save(&file, vec!["a".to_string()])?;
+        fs::write(&file, "edited")?;
+        backup.save(&file, vec!["b".to_string()])?;
+
+        let copy = backup.dir().join("res/values/strings.xml");
+        assert_eq!(fs::read_to_string(copy)?, "original");
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(backup.dir().join(MANIFEST_FILE))?)?;
+        assert_eq!(manifest[0]["file"], file.to_str().unwrap());
+        assert_eq!(manifest[0]["removed"], serde_json::json!(["a", "b"]));
+
+        // Paths that would land outside of their root's directory are refused.
+        let outside = tmp_dir.path().join("outside.xml");
+        fs::write(&outside, "outside")?;
+        assert!(backup.save(&outside, vec![]).is_err());
+        assert!(backup.save(&res.join("../outside.xml"), vec![]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use crate::diagnostics::Diagnostics;
+use crate::index::{Indexer, ResourceIndex};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// The phases of indexing, in the order they run.
+const PHASES: [&str; 5] = ["walk", "xml parse", "source grep", "invert", "serialize"];
+
+/// How long each phase took in each iteration, in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Report {
+    pub phases: Vec<(String, Vec<f64>)>,
+}
+
+impl Report {
+    fn samples(&self, phase: &str) -> Option<&[f64]> {
+        self.phases
+            .iter()
+            .find(|(name, _)| name == phase)
+            .map(|(_, samples)| samples.as_slice())
+    }
+
+    pub fn read(path: &Path) -> Result<Report> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+/// Times `f` in milliseconds.
+fn time<T>(f: impl FnOnce() -> Result<T>) -> Result<(T, f64)> {
+    let start = Instant::now();
+    let result = f()?;
+    Ok((result, start.elapsed().as_secs_f64() * 1000.0))
+}
+
+/// Indexes `iterations` times, timing each phase separately. The index is
+/// serialized to a scratch file, leaving the cached index alone.
+pub fn run(indexer: &Indexer, iterations: usize) -> Result<Report> {
+    if iterations == 0 {
+        return Err(anyhow!("Expected at least one iteration"));
+    }
+    fs::create_dir_all(indexer.cache_dir())?;
+    let scratch = indexer.cache_dir().join("bench.tmp");
+
+    let mut report = Report {
+        phases: PHASES
+            .iter()
+            .map(|phase| (phase.to_string(), Vec::with_capacity(iterations)))
+            .collect(),
+    };
+    for _ in 0..iterations {
+        let (_, walk) = time(|| indexer.walk_only())?;
+        let diagnostics = Diagnostics::default();
+        let (mut files, xml) = time(|| {
+            let mut files =
+                indexer.index_xml_files(indexer.res_root(), "*.xml", "resources", &diagnostics)?;
+            if indexer.manifest_root() != indexer.res_root() {
+                files.extend(indexer.index_xml_files(
+                    indexer.manifest_root(),
+                    "AndroidManifest.xml",
+                    "manifests",
+                    &diagnostics,
+                )?);
+            }
+            files.extend(indexer.index_drawable_files(&diagnostics)?);
+            Ok(files)
+        })?;
+        let (sources, grep) = time(|| indexer.index_source_files(&diagnostics))?;
+        files.extend(sources);
+        let (index, invert) = time(|| Ok(ResourceIndex::new(files)))?;
+        let (_, serialize) = time(|| indexer.write_index(&index, &scratch))?;
+
+        for ((_, samples), sample) in report
+            .phases
+            .iter_mut()
+            .zip([walk, xml, grep, invert, serialize])
+        {
+            samples.push(sample);
+        }
+    }
+    fs::remove_file(&scratch)?;
+
+    Ok(report)
+}
+
+fn median(samples: &[f64]) -> f64 {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+/// Prints the min, median and max of each phase, and with a `baseline`, its
+/// median and how much the median changed since.
+pub fn print(report: &Report, baseline: Option<&Report>, out: &mut dyn Write) -> Result<()> {
+    write!(
+        out,
+        "{:<12} {:>10} {:>10} {:>10}",
+        "phase", "min", "median", "max"
+    )?;
+    if baseline.is_some() {
+        write!(out, " {:>10} {:>8}", "baseline", "change")?;
+    }
+    writeln!(out)?;
+
+    for (phase, samples) in &report.phases {
+        let min = samples.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = samples.iter().copied().fold(0.0, f64::max);
+        let current = median(samples);
+        write!(
+            out,
+            "{:<12} {:>8.1}ms {:>8.1}ms {:>8.1}ms",
+            phase, min, current, max
+        )?;
+        if let Some(baseline) = baseline {
+            match baseline
+                .samples(phase)
+                .filter(|samples| !samples.is_empty())
+            {
+                Some(samples) => {
+                    let before = median(samples);
+                    let change = if before > 0.0 {
+                        format!("{:+.1}%", (current - before) / before * 100.0)
+                    } else {
+                        "-".to_string()
+                    };
+                    write!(out, " {:>8.1}ms {:>8}", before, change)?;
+                }
+                None => write!(out, " {:>10} {:>8}", "-", "-")?,
+            }
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_run_and_print() -> Result<()> {
+        let tmp_dir = TempDir::new("bench")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="title">T</string></resources>"#,
+        )?;
+        fs::write(src_dir.join("Main.java"), "R.string.title;")?;
+        let indexer = Indexer::new(src_dir, res_dir, None, Some(tmp_dir.path().join("cache")))?;
+
+        let report = run(&indexer, 2)?;
+        let phases: Vec<&str> = report
+            .phases
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(phases, PHASES);
+        assert!(report.phases.iter().all(|(_, samples)| samples.len() == 2));
+        assert!(!indexer.cache_dir().join("bench.tmp").exists());
+
+        let baseline = Report {
+            phases: vec![
+                ("walk".to_string(), vec![0.0]),
+                ("invert".to_string(), vec![1.0, 3.0]),
+            ],
+        };
+        let mut out = Vec::new();
+        print(&report, Some(&baseline), &mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].ends_with("baseline   change"));
+        assert!(
+            lines[4].starts_with("invert") && lines[4].contains("2.0ms") && lines[4].ends_with('%')
+        );
+        assert!(lines[5].starts_with("serialize") && lines[5].ends_with('-'));
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+use crate::output::outln;
+use anyhow::{anyhow, Result};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub const CACHE_FILE_PREFIX: &str = "res_cache";
+
+/// The extensions of index files, one for each backend. Other files named
+/// like them, such as their stamps, aren't indexes.
+const INDEX_EXTENSIONS: &[&str] = &["bin", "sqlite"];
+
+/// The extension of the directories of per-directory fragments.
+pub const FRAGMENTS_EXTENSION: &str = "d";
+
+/// FNV-1a, used to name cache files. Unlike `DefaultHasher` its output is
+/// stable across Rust releases, so cache file names are too.
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Writes the file at `path` with `write`, into a temporary file alongside
+/// it that's then renamed over it. Another run with the old file open, or
+/// mapped, keeps reading it whole rather than having it truncated under it.
+pub fn replace(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = File::create(&tmp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write(&mut out)?;
+            out.flush()?;
+            Ok(fs::rename(&tmp_path, path)?)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// A cached index: a file, or a directory of fragments.
+pub struct CacheFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// The total size of the files in `dir`, and when the newest of them, or
+/// `dir` itself, was modified.
+fn dir_usage(dir: &Path, modified: SystemTime) -> Result<(u64, SystemTime)> {
+    let mut usage = (0, modified);
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        usage.0 += metadata.len();
+        usage.1 = usage.1.max(metadata.modified()?);
+    }
+
+    Ok(usage)
+}
+
+/// Lists the indexes in the cache dir, including directories of fragments,
+/// least recently modified first.
+pub fn cache_files(cache_dir: &Path) -> Result<Vec<CacheFile>> {
+    let mut files = Vec::new();
+    if !cache_dir.exists() {
+        return Ok(files);
+    }
+
+    for entry in fs::read_dir(cache_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_cached = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(CACHE_FILE_PREFIX));
+        if !is_cached {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let (size, modified) = if metadata.is_dir() && extension == Some(FRAGMENTS_EXTENSION) {
+            dir_usage(&path, metadata.modified()?)?
+        } else if metadata.is_file()
+            && extension.is_some_and(|extension| INDEX_EXTENSIONS.contains(&extension))
+        {
+            (metadata.len(), metadata.modified()?)
+        } else {
+            continue;
+        };
+        files.push(CacheFile {
+            path,
+            size,
+            modified,
+        });
+    }
+    files.sort_by_key(|file| file.modified);
+
+    Ok(files)
+}
+
+/// Where the stamp of the working tree the index at `path` was built from
+/// is kept.
+pub fn stamp_file(path: &Path) -> PathBuf {
+    path.with_extension("stamp.json")
+}
+
+/// Removes a cached index along with its stamp.
+fn remove(file: &CacheFile) -> Result<()> {
+    if file.path.is_dir() {
+        fs::remove_dir_all(&file.path)?;
+    } else {
+        fs::remove_file(&file.path)?;
+    }
+    match fs::remove_file(stamp_file(&file.path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Parses sizes like 1048576, 500K, 20M or 2G.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((i, 'K')) | Some((i, 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M')) | Some((i, 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G')) | Some((i, 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+
+    digits
+        .trim()
+        .parse::<u64>()
+        .map(|n| n * multiplier)
+        .map_err(|_| anyhow!("Invalid size {}", size))
+}
+
+pub fn format_size(size: u64) -> String {
+    if size >= 1 << 30 {
+        format!("{:.1}G", size as f64 / (1 << 30) as f64)
+    } else if size >= 1 << 20 {
+        format!("{:.1}M", size as f64 / (1 << 20) as f64)
+    } else if size >= 1 << 10 {
+        format!("{:.1}K", size as f64 / (1 << 10) as f64)
+    } else {
+        format!("{}B", size)
+    }
+}
+
+pub fn format_age(modified: SystemTime) -> String {
+    let secs = modified.elapsed().map_or(0, |age| age.as_secs());
+    match secs {
+        s if s >= 86400 => format!("{}d ago", s / 86400),
+        s if s >= 3600 => format!("{}h ago", s / 3600),
+        s if s >= 60 => format!("{}m ago", s / 60),
+        s => format!("{}s ago", s),
+    }
+}
+
+pub fn ls(cache_dir: &Path, current: &Path) -> Result<()> {
+    let files = cache_files(cache_dir)?;
+    for file in &files {
+        outln!(
+            "{:>8}  {:>8}  {}{}",
+            format_size(file.size),
+            format_age(file.modified),
+            file.path.display(),
+            if file.path == current || file.path == current.with_extension(FRAGMENTS_EXTENSION) {
+                " (current)"
+            } else {
+                ""
+            }
+        );
+    }
+    let total: u64 = files.iter().map(|file| file.size).sum();
+    outln!(
+        "{} cached indexes, {} total",
+        files.len(),
+        format_size(total)
+    );
+
+    Ok(())
+}
+
+/// Removes every cached index, including per-directory fragments.
+pub fn clean(cache_dir: &Path) -> Result<()> {
+    let files = cache_files(cache_dir)?;
+    for file in &files {
+        remove(file)?;
+    }
+    outln!("Removed {} cached indexes", files.len());
+
+    Ok(())
+}
+
+/// Removes the least recently built indexes until the cache fits in
+/// `max_size` bytes, counting directories of fragments by what's in them.
+pub fn gc(cache_dir: &Path, max_size: u64) -> Result<()> {
+    let files = cache_files(cache_dir)?;
+    let mut total: u64 = files.iter().map(|file| file.size).sum();
+
+    let mut removed = 0;
+    for file in &files {
+        if total <= max_size {
+            break;
+        }
+        remove(file)?;
+        total -= file.size;
+        removed += 1;
+    }
+    outln!(
+        "Removed {} cached indexes, {} remaining",
+        removed,
+        format_size(total)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_replace() -> Result<()> {
+        let tmp_dir = TempDir::new("cache")?;
+        let path = tmp_dir.path().join("res_cache.bin");
+        fs::write(&path, "old")?;
+        let mut reader = File::open(&path)?;
+
+        replace(&path, |out| Ok(out.write_all(b"new")?))?;
+        // A reader of the old file still sees all of it.
+        let mut old = String::new();
+        reader.read_to_string(&mut old)?;
+        assert_eq!(old, "old");
+        assert_eq!(fs::read_to_string(&path)?, "new");
+
+        assert!(replace(&path, |_| Err(anyhow!("failed"))).is_err());
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    /// A cache dir with an old index and its stamp, a newer one, the newest
+    /// directory of fragments, and a file that isn't an index.
+    fn cache_dir() -> Result<TempDir> {
+        let tmp_dir = TempDir::new("cache")?;
+        let write = |name: &str, size: usize, hours_ago: u64| -> Result<()> {
+            let path = tmp_dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, vec![0; size])?;
+            let modified = SystemTime::now() - std::time::Duration::from_secs(hours_ago * 3600);
+            File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(modified)?;
+            Ok(())
+        };
+        write("res_cache-a.bin", 100, 3)?;
+        write("res_cache-a.stamp.json", 10, 3)?;
+        write("res_cache-b.sqlite", 100, 2)?;
+        write("res_cache-c.d/manifest.json", 20, 1)?;
+        write("res_cache-c.d/0123.bin", 30, 1)?;
+        write("notes.txt", 1000, 4)?;
+
+        Ok(tmp_dir)
+    }
+
+    fn names(dir: &Path) -> Result<Vec<String>> {
+        let mut names: Vec<String> = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        Ok(names)
+    }
+
+    #[test]
+    fn test_gc() -> Result<()> {
+        let tmp_dir = cache_dir()?;
+        let files = cache_files(tmp_dir.path())?;
+        let sizes: Vec<u64> = files.iter().map(|file| file.size).collect();
+        assert_eq!(sizes, vec![100, 100, 50]);
+
+        // Over the limit by the oldest index, which goes with its stamp.
+        gc(tmp_dir.path(), 200)?;
+        assert_eq!(
+            names(tmp_dir.path())?,
+            vec!["notes.txt", "res_cache-b.sqlite", "res_cache-c.d"]
+        );
+        gc(tmp_dir.path(), 150)?;
+        assert_eq!(names(tmp_dir.path())?.len(), 3);
+
+        // Fragments count, so they go too once they don't fit.
+        gc(tmp_dir.path(), 49)?;
+        assert_eq!(names(tmp_dir.path())?, vec!["notes.txt"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean() -> Result<()> {
+        let tmp_dir = cache_dir()?;
+        clean(tmp_dir.path())?;
+        assert_eq!(names(tmp_dir.path())?, vec!["notes.txt"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_size() -> Result<()> {
+        assert_eq!(parse_size("1024")?, 1024);
+        assert_eq!(parse_size("500K")?, 500 * 1024);
+        assert_eq!(parse_size("20M")?, 20 * 1024 * 1024);
+        assert_eq!(parse_size("2g")?, 2 * 1024 * 1024 * 1024);
+        assert!(parse_size("lots").is_err());
+
+        Ok(())
+    }
+}
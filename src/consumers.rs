@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Reads the string names used by a downstream app from `path`. That's
+/// either an R.txt from its build, with lines like
+/// `int string app_name 0x7f0a0001`, of which only strings count, or a
+/// plain list with one name per line. Blank lines and lines starting with #
+/// are ignored.
+pub fn read_string_names(path: &Path) -> Result<HashSet<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read external consumers from {}", path.display()))?;
+    Ok(parse_string_names(&content))
+}
+
+fn parse_string_names(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [name] => Some(name.to_string()),
+                [_, "string", name, ..] => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_read_string_names() -> Result<()> {
+        let tmp_dir = TempDir::new("consumers")?;
+        let r_txt = tmp_dir.path().join("R.txt");
+        fs::write(
+            &r_txt,
+            "int drawable icon 0x7f020000\nint string app_name 0x7f0a0000\nint[] styleable Button { 0x7f010000 }\nint string title 0x7f0a0001\n",
+        )?;
+        let mut names: Vec<String> = read_string_names(&r_txt)?.into_iter().collect();
+        names.sort();
+        assert_eq!(names, vec!["app_name", "title"]);
+
+        let list = tmp_dir.path().join("symbols.txt");
+        fs::write(
+            &list,
+            "# Used by the wear app\nwear_title\n\n  watch_face\n",
+        )?;
+        let mut names: Vec<String> = read_string_names(&list)?.into_iter().collect();
+        names.sort();
+        assert_eq!(names, vec!["watch_face", "wear_title"]);
+
+        assert!(read_string_names(&tmp_dir.path().join("missing.txt")).is_err());
+
+        Ok(())
+    }
+}
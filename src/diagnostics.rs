@@ -0,0 +1,249 @@
+use crate::progress::ProgressMode;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// What went wrong with a file while indexing.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    /// An xml file that couldn't be parsed.
+    XmlParse,
+    /// A file that couldn't be read.
+    Unreadable,
+    /// A directory entry the walk couldn't get to.
+    Walk,
+    /// A file that isn't valid UTF-8, and was read as ISO-8859-1 instead.
+    Latin1,
+    /// A file that looks binary, and was skipped.
+    Binary,
+    /// A file over --max-filesize, and was skipped.
+    TooLarge,
+    /// A file reached by another path too, and indexed once under that.
+    Duplicate,
+}
+
+impl Kind {
+    const ALL: [Kind; 7] = [
+        Kind::XmlParse,
+        Kind::Unreadable,
+        Kind::Walk,
+        Kind::Latin1,
+        Kind::Binary,
+        Kind::TooLarge,
+        Kind::Duplicate,
+    ];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::XmlParse => "failed to parse as xml",
+            Kind::Unreadable => "couldn't be read",
+            Kind::Walk => "couldn't be walked",
+            Kind::Latin1 => "read as ISO-8859-1",
+            Kind::Binary => "binary",
+            Kind::TooLarge => "over the size limit",
+            Kind::Duplicate => "duplicate",
+        }
+    }
+
+    /// Whether the file was left out of the index because of a problem with
+    /// it, rather than because it was skipped.
+    pub fn is_error(self) -> bool {
+        matches!(self, Kind::XmlParse | Kind::Unreadable | Kind::Walk)
+    }
+
+    /// Skipped files are only listed when verbose; there can be lots of
+    /// them, and skipping them is what was asked for.
+    fn is_skip(self) -> bool {
+        matches!(self, Kind::Binary | Kind::TooLarge)
+    }
+}
+
+/// A problem with one file while indexing.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub message: String,
+}
+
+/// Collects diagnostics from walker threads, so they can be reported together
+/// once indexing is over rather than interleaved as they happen.
+#[derive(Default)]
+pub struct Diagnostics(Mutex<Vec<Diagnostic>>);
+
+impl Diagnostics {
+    pub fn push(&self, kind: Kind, path: Option<&Path>, message: impl ToString) {
+        self.0.lock().unwrap().push(Diagnostic {
+            kind,
+            path: path.map(|path| path.display().to_string()),
+            message: message.to_string(),
+        });
+    }
+
+    /// Returns the diagnostics grouped by kind, and sorted by path within it.
+    pub fn into_sorted(self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.0.into_inner().unwrap();
+        diagnostics.sort();
+        diagnostics
+    }
+}
+
+/// Writes how many files had each kind of problem, then the files, leaving
+/// out skipped ones unless `verbose`.
+fn write_summary(diagnostics: &[Diagnostic], verbose: bool, out: &mut dyn Write) -> io::Result<()> {
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+    let counts: Vec<String> = Kind::ALL
+        .iter()
+        .map(|&kind| (kind, diagnostics.iter().filter(|d| d.kind == kind).count()))
+        .filter(|(_, count)| *count > 0)
+        .map(|(kind, count)| format!("{} {}", count, kind.describe()))
+        .collect();
+    writeln!(
+        out,
+        "Warning: {} problem{} while indexing: {}",
+        diagnostics.len(),
+        if diagnostics.len() == 1 { "" } else { "s" },
+        counts.join(", ")
+    )?;
+
+    for diagnostic in diagnostics {
+        if diagnostic.kind.is_skip() && !verbose {
+            continue;
+        }
+        write!(
+            out,
+            "  {} ({})",
+            diagnostic.path.as_deref().unwrap_or("-"),
+            diagnostic.kind.describe()
+        )?;
+        if !diagnostic.message.is_empty() {
+            write!(out, ": {}", diagnostic.message)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DiagnosticsEvent<'a> {
+    event: &'a str,
+    diagnostics: &'a [Diagnostic],
+}
+
+/// Reports the diagnostics on stderr: as one json event alongside the
+/// progress events with `--progress json`, and as a summary otherwise.
+pub fn report(diagnostics: &[Diagnostic], verbose: bool, progress: ProgressMode) {
+    let mut stderr = io::stderr().lock();
+    if progress == ProgressMode::Json {
+        let event = DiagnosticsEvent {
+            event: "diagnostics",
+            diagnostics,
+        };
+        if let Ok(json) = serde_json::to_string(&event) {
+            let _ = writeln!(stderr, "{}", json);
+        }
+    } else {
+        let _ = write_summary(diagnostics, verbose, &mut stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use anyhow::Result;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_diagnostics() -> Result<()> {
+        let tmp_dir = TempDir::new("diagnostics")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="title">T</string></resources>"#,
+        )?;
+        fs::write(res_dir.join("values/broken.xml"), "<resources><string")?;
+        fs::write(src_dir.join("Blob.java"), b"R.string.title;\0\x01")?;
+        fs::write(src_dir.join("Old.java"), b"// Caf\xe9\nR.string.title;")?;
+
+        let mut indexer = Indexer::new(src_dir.clone(), res_dir.clone(), None, None)?;
+        let index = indexer.index()?;
+        let found: Vec<(Kind, Option<String>)> = index
+            .diagnostics()
+            .iter()
+            .map(|d| (d.kind, d.path.clone()))
+            .collect();
+        let path = |path: &Path| Some(path.display().to_string());
+        assert_eq!(
+            found,
+            vec![
+                (Kind::XmlParse, path(&res_dir.join("values/broken.xml"))),
+                (Kind::Latin1, path(&src_dir.join("Old.java"))),
+                (Kind::Binary, path(&src_dir.join("Blob.java"))),
+            ]
+        );
+        assert!(!index.diagnostics()[0].message.is_empty());
+
+        let summary = |verbose| -> Result<String> {
+            let mut out = Vec::new();
+            write_summary(index.diagnostics(), verbose, &mut out)?;
+            Ok(String::from_utf8(out)?)
+        };
+        let quiet = summary(false)?;
+        assert!(quiet.starts_with(
+            "Warning: 3 problems while indexing: 1 failed to parse as xml, 1 read as ISO-8859-1, 1 binary\n"
+        ));
+        assert_eq!(quiet.lines().count(), 3);
+        assert_eq!(summary(true)?.lines().count(), 4);
+
+        // Binary and ISO-8859-1 files aren't errors, but the broken xml is.
+        let error = indexer.strict(true).index().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "1 files couldn't be indexed, failing because of --strict"
+        );
+        fs::remove_file(res_dir.join("values/broken.xml"))?;
+        assert!(indexer.index().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicates_are_listed() -> Result<()> {
+        let diagnostic = |kind, path: &str, message: &str| Diagnostic {
+            kind,
+            path: Some(path.to_string()),
+            message: message.to_string(),
+        };
+        let diagnostics = vec![
+            diagnostic(Kind::Binary, "src/Blob.java", ""),
+            diagnostic(
+                Kind::Duplicate,
+                "res/../res/values/strings.xml",
+                "the same file as res/values/strings.xml",
+            ),
+        ];
+
+        let mut out = Vec::new();
+        write_summary(&diagnostics, false, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "Warning: 2 problems while indexing: 1 binary, 1 duplicate
+  res/../res/values/strings.xml (duplicate): the same file as res/values/strings.xml
+"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use crate::index::ResourceIndex;
+use crate::output::{outln, Format};
+use crate::symbols::Symbol;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// How strings changed between two indexes. Each list is sorted by name.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Diff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Unused in the new index but not in the old, whether they were used
+    /// before or have just been added.
+    pub newly_unused: Vec<String>,
+    /// Unused in the old index but used in the new.
+    pub newly_used: Vec<String>,
+}
+
+/// The names of `symbols`, sorted.
+fn names(index: &ResourceIndex, symbols: impl IntoIterator<Item = Symbol>) -> BTreeSet<String> {
+    symbols
+        .into_iter()
+        .map(|symbol| index.name(symbol).to_string())
+        .collect()
+}
+
+/// Compares the strings defined and unused in `old` and `new`.
+pub fn diff(old: &ResourceIndex, new: &ResourceIndex) -> Diff {
+    let old_defined = names(old, old.defined_strings());
+    let new_defined = names(new, new.defined_strings());
+    let old_unused = names(old, old.unused_strings());
+    let new_unused = names(new, new.unused_strings());
+
+    Diff {
+        added: new_defined.difference(&old_defined).cloned().collect(),
+        removed: old_defined.difference(&new_defined).cloned().collect(),
+        newly_unused: new_unused.difference(&old_unused).cloned().collect(),
+        newly_used: old_unused
+            .iter()
+            .filter(|name| new_defined.contains(*name) && !new_unused.contains(*name))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Prints the diff between `old` and `new`. `is_reported` filters the
+/// strings listed, e.g. by the keep list.
+pub fn print(
+    old: &ResourceIndex,
+    new: &ResourceIndex,
+    is_reported: impl Fn(&str) -> bool,
+    format: Format,
+) -> Result<Diff> {
+    let mut diff = diff(old, new);
+    for names in [
+        &mut diff.added,
+        &mut diff.removed,
+        &mut diff.newly_unused,
+        &mut diff.newly_used,
+    ] {
+        names.retain(|name| is_reported(name));
+    }
+
+    match format {
+        Format::Text => {
+            for (heading, names) in [
+                ("Added", &diff.added),
+                ("Removed", &diff.removed),
+                ("Newly unused", &diff.newly_unused),
+                ("Newly used", &diff.newly_used),
+            ] {
+                outln!("{} ({}):", heading, names.len());
+                for name in names {
+                    outln!("  {}", name);
+                }
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let tmp_dir = TempDir::new("diff")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(&src_dir)?;
+        let strings = res_dir.join("values/strings.xml");
+        let source = src_dir.join("Main.java");
+        let index = || Indexer::new(src_dir.clone(), res_dir.clone(), None, None)?.index();
+
+        fs::write(
+            &strings,
+            r#"<resources><string name="kept">K</string><string name="gone">G</string><string name="was_used">W</string><string name="was_unused">U</string></resources>"#,
+        )?;
+        fs::write(&source, "R.string.kept; R.string.was_used;")?;
+        let old = index()?;
+
+        fs::write(
+            &strings,
+            r#"<resources><string name="kept">K</string><string name="was_used">W</string><string name="was_unused">U</string><string name="new">N</string></resources>"#,
+        )?;
+        fs::write(&source, "R.string.kept; R.string.was_unused;")?;
+        let new = index()?;
+
+        assert_eq!(
+            diff(&old, &new),
+            Diff {
+                added: vec!["new".to_string()],
+                removed: vec!["gone".to_string()],
+                newly_unused: vec!["new".to_string(), "was_used".to_string()],
+                newly_used: vec!["was_unused".to_string()],
+            }
+        );
+
+        Ok(())
+    }
+}
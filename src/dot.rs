@@ -0,0 +1,144 @@
+use crate::index::ResourceIndex;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Quotes `id` for use as a DOT identifier.
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Shortens `path` to be relative to the first of `roots` it's under.
+fn label<'a>(path: &'a str, roots: &[&Path]) -> &'a str {
+    roots
+        .iter()
+        .find_map(|root| Path::new(path).strip_prefix(root).ok())
+        .and_then(Path::to_str)
+        .unwrap_or(path)
+}
+
+/// Writes a Graphviz graph of which files reference which strings, limited to
+/// strings whose names `matches` accepts. Files are ellipses labelled with
+/// their path relative to the first of `roots` holding them; strings are
+/// boxes, dashed if unused. Returns the number of strings in the graph.
+pub fn export(
+    index: &ResourceIndex,
+    roots: &[&Path],
+    matches: impl Fn(&str) -> bool,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let unused = index.unused_strings();
+    let strings: BTreeMap<&str, bool> = index
+        .defined_strings()
+        .into_iter()
+        .map(|symbol| (index.name(symbol), unused.contains(&symbol)))
+        .filter(|(name, _)| matches(name))
+        .collect();
+
+    let mut edges = BTreeSet::new();
+    for file in index.files() {
+        for symbol in file.string_usages() {
+            let name = index.name(*symbol);
+            if strings.contains_key(name) {
+                edges.insert((file.path(), name));
+            }
+        }
+    }
+    let files: BTreeSet<&str> = edges.iter().map(|(path, _)| *path).collect();
+
+    writeln!(out, "digraph strings {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    for path in files {
+        writeln!(
+            out,
+            "  {} [label={}];",
+            quote(path),
+            quote(label(path, roots))
+        )?;
+    }
+    for (name, unused) in &strings {
+        let style = if *unused { ", style=dashed" } else { "" };
+        writeln!(
+            out,
+            "  {} [label={}, shape=box{}];",
+            quote(&format!("string/{}", name)),
+            quote(name),
+            style
+        )?;
+    }
+    for (path, name) in &edges {
+        writeln!(
+            out,
+            "  {} -> {};",
+            quote(path),
+            quote(&format!("string/{}", name))
+        )?;
+    }
+    writeln!(out, "}}")?;
+
+    Ok(strings.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export() -> Result<()> {
+        let tmp_dir = TempDir::new("dot")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("layout"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="feature_title">T</string><string name="feature_old">O</string><string name="other">X</string></resources>"#,
+        )?;
+        fs::write(
+            res_dir.join("layout/main.xml"),
+            r#"<TextView xmlns:android="http://schemas.android.com/apk/res/android" android:text="@string/feature_title" />"#,
+        )?;
+        fs::write(
+            src_dir.join("Main.java"),
+            "R.string.feature_title; R.string.other;",
+        )?;
+        let index = Indexer::new(src_dir.clone(), res_dir.clone(), None, None)?.index()?;
+
+        let mut out = Vec::new();
+        let count = export(
+            &index,
+            &[&src_dir, &res_dir],
+            |name| name.starts_with("feature_"),
+            &mut out,
+        )?;
+        assert_eq!(count, 2);
+        let main = src_dir.join("Main.java");
+        let layout = res_dir.join("layout/main.xml");
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!(
+                r#"digraph strings {{
+  rankdir=LR;
+  "{}" [label="layout/main.xml"];
+  "{}" [label="Main.java"];
+  "string/feature_old" [label="feature_old", shape=box, style=dashed];
+  "string/feature_title" [label="feature_title", shape=box];
+  "{}" -> "string/feature_title";
+  "{}" -> "string/feature_title";
+}}
+"#,
+                layout.display(),
+                main.display(),
+                layout.display(),
+                main.display()
+            )
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+use crate::index::ResourceIndex;
+use crate::output::outln;
+use crate::xeditor;
+use anyhow::{anyhow, Result};
+use ignore::WalkBuilder;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use xml::common::{Position, TextPosition};
+use xml::escape::escape_str_pcdata;
+use xml::reader::{EventReader, XmlEvent};
+
+const ANDROID_NS: &str = "http://schemas.android.com/apk/res/android";
+
+/// An attribute in a resource file holding literal text rather than a
+/// reference to a string resource.
+pub struct HardcodedText {
+    pub path: PathBuf,
+    pub position: TextPosition,
+    pub attribute: String,
+    pub value: String,
+}
+
+fn is_literal(value: &str) -> bool {
+    !value.is_empty() && !value.starts_with('@') && !value.starts_with('?')
+}
+
+/// Finds android: attributes with one of the given local names whose values
+/// are literal text.
+pub fn find_hardcoded_text(path: &Path, attributes: &[&str]) -> Result<Vec<HardcodedText>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut parser = EventReader::new(file);
+    let mut found = Vec::new();
+
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement {
+                attributes: attrs, ..
+            }) => {
+                let position = parser.position();
+                for attr in attrs {
+                    if attr.name.namespace.as_deref() == Some(ANDROID_NS)
+                        && attributes.contains(&attr.name.local_name.as_str())
+                        && is_literal(&attr.value)
+                    {
+                        let attribute = match &attr.name.prefix {
+                            Some(prefix) => format!("{}:{}", prefix, attr.name.local_name),
+                            None => attr.name.local_name.clone(),
+                        };
+                        found.push(HardcodedText {
+                            path: path.to_path_buf(),
+                            position,
+                            attribute,
+                            value: attr.value,
+                        });
+                    }
+                }
+            }
+            Ok(XmlEvent::EndDocument) => break,
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("In {:?}", path))),
+            _ => {}
+        }
+    }
+
+    Ok(found)
+}
+
+/// Lists the layout files under the given res root.
+pub fn layout_files(res_root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(res_root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "xml")
+                && path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .and_then(|dir| dir.to_str())
+                    .is_some_and(|dir| dir.starts_with("layout"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Derives a resource name from a piece of text, e.g. "Sign in!" -> sign_in.
+fn derive_name(text: &str) -> String {
+    let mut name = String::new();
+    for word in text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if name.len() + word.len() > 40 {
+            break;
+        }
+        if !name.is_empty() {
+            name.push('_');
+        }
+        name.push_str(&word.to_ascii_lowercase());
+    }
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert_str(0, "text_");
+    }
+
+    name
+}
+
+/// Chooses names for extracted text, reusing a name when the same text
+/// appears more than once and avoiding names that are already defined or
+/// referenced.
+struct Namer<'a> {
+    taken: HashSet<String>,
+    index: &'a ResourceIndex,
+    names_for_text: HashMap<String, String>,
+}
+
+impl<'a> Namer<'a> {
+    fn name_for(&mut self, text: &str) -> (String, bool) {
+        if let Some(name) = self.names_for_text.get(text) {
+            return (name.clone(), false);
+        }
+
+        let base = derive_name(text);
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while self.taken.contains(&name) || self.index.symbol(&name).is_some() {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+
+        self.taken.insert(name.clone());
+        self.names_for_text.insert(text.to_string(), name.clone());
+        (name, true)
+    }
+}
+
+/// Moves literal android:text values in layouts into string resources in
+/// res/values/strings.xml, rewriting the layouts to reference them. Returns
+/// the number of attributes rewritten.
+pub fn extract_hardcoded(index: &ResourceIndex, res_root: &Path, dry_run: bool) -> Result<usize> {
+    let mut namer = Namer {
+        taken: HashSet::new(),
+        index,
+        names_for_text: HashMap::new(),
+    };
+
+    let mut edits_for_file: BTreeMap<PathBuf, Vec<xeditor::AttributeEdit>> = BTreeMap::new();
+    let mut definitions = Vec::new();
+    for layout in layout_files(res_root) {
+        for hardcoded in find_hardcoded_text(&layout, &["text"])? {
+            let (name, is_new) = namer.name_for(&hardcoded.value);
+            outln!(
+                "{}:{} {}=\"{}\" -> @string/{}",
+                hardcoded.path.display(),
+                hardcoded.position.row + 1,
+                hardcoded.attribute,
+                hardcoded.value,
+                name
+            );
+            if is_new {
+                definitions.push(format!(
+                    "<string name=\"{}\">{}</string>",
+                    name,
+                    escape_str_pcdata(&hardcoded.value)
+                ));
+            }
+            edits_for_file
+                .entry(hardcoded.path)
+                .or_default()
+                .push(xeditor::AttributeEdit {
+                    position: hardcoded.position,
+                    name: hardcoded.attribute,
+                    value: format!("@string/{}", name),
+                });
+        }
+    }
+
+    if dry_run {
+        return Ok(edits_for_file.values().map(Vec::len).sum());
+    }
+
+    if !definitions.is_empty() {
+        let strings = res_root.join("values").join("strings.xml");
+        if !strings.exists() {
+            xeditor::append_elements(&strings, &[])?;
+        }
+        let resources = xeditor::ElementMatcher::for_path("/resources")?;
+        let fragment = definitions.join("\n");
+        let report = xeditor::insert_element(
+            &strings,
+            &resources,
+            &fragment,
+            xeditor::InsertPosition::Last,
+        )?;
+        if !report.changed() {
+            return Err(anyhow!("No <resources> element in {:?}", strings));
+        }
+    }
+
+    let mut edited = 0;
+    for (path, edits) in edits_for_file {
+        edited += xeditor::edit_attributes(&path, &edits)?.edits.len();
+    }
+
+    Ok(edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_derive_name() {
+        assert_eq!(derive_name("Sign in!"), "sign_in");
+        assert_eq!(derive_name("3 items"), "text_3_items");
+        assert_eq!(derive_name("¡¿?"), "text_");
+        assert_eq!(
+            derive_name("A very long label that goes on and on and on forever"),
+            "a_very_long_label_that_goes_on_and_on_and"
+        );
+    }
+
+    #[test]
+    fn test_extract_hardcoded() -> Result<()> {
+        let tmp_dir = TempDir::new("extract")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("layout"))?;
+        let layout = res_dir.join("layout/main.xml");
+        fs::write(
+            &layout,
+            r#"<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android">
+    <TextView android:text="Hello" />
+    <Button android:text="Sign in" />
+    <TextView android:text="Sign in" />
+    <TextView android:text="@string/hello" />
+</LinearLayout>
+"#,
+        )?;
+
+        // Without a strings.xml, it's created.
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+        assert_eq!(extract_hardcoded(&index, &res_dir, true)?, 3);
+        assert!(!res_dir.join("values").exists());
+        assert_eq!(extract_hardcoded(&index, &res_dir, false)?, 3);
+        let strings = fs::read_to_string(res_dir.join("values/strings.xml"))?;
+        // hello is taken by the reference to it.
+        assert!(strings.contains(r#"<string name="hello_2">Hello</string>"#));
+        assert!(strings.contains(r#"<string name="sign_in">Sign in</string>"#));
+        assert_eq!(strings.matches("<string ").count(), 2);
+        assert_eq!(
+            fs::read_to_string(&layout)?,
+            r#"<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android">
+    <TextView android:text="@string/hello_2" />
+    <Button android:text="@string/sign_in" />
+    <TextView android:text="@string/sign_in" />
+    <TextView android:text="@string/hello" />
+</LinearLayout>
+"#
+        );
+
+        // With one, new strings are appended, named around those defined.
+        fs::write(
+            &layout,
+            r#"<TextView xmlns:android="http://schemas.android.com/apk/res/android"
+    android:text="Hello &amp; bye" android:hint="Hello" />
+"#,
+        )?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources>
+    <string name="hello_bye">Bye</string>
+</resources>
+"#,
+        )?;
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+        assert_eq!(extract_hardcoded(&index, &res_dir, false)?, 1);
+        let strings = fs::read_to_string(res_dir.join("values/strings.xml"))?;
+        assert!(strings.contains(r#"<string name="hello_bye">Bye</string>"#));
+        assert!(strings.contains(r#"<string name="hello_bye_2">Hello &amp; bye</string>"#));
+        assert!(fs::read_to_string(&layout)?
+            .contains(r#"android:text="@string/hello_bye_2" android:hint="Hello""#));
+
+        Ok(())
+    }
+}
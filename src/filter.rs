@@ -0,0 +1,128 @@
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Options narrowing which strings ls-unused and rm-unused consider.
+#[derive(Debug, Default, StructOpt)]
+pub struct Filter {
+    /// Only considers strings whose names match this regex, e.g. ^legacy_
+    #[structopt(long)]
+    name_regex: Option<Regex>,
+
+    /// Only considers definitions in files matching this glob, e.g. 'feature_x/**/strings.xml'
+    #[structopt(long, parse(try_from_str = parse_glob))]
+    in_file: Option<GlobMatcher>,
+
+    /// Only considers definitions in this file (repeatable), even if the
+    /// string is also defined elsewhere
+    #[structopt(long = "file", parse(from_os_str = canonical_path))]
+    files: Vec<PathBuf>,
+
+    /// Only considers string definitions whose values match this regex, e.g. 'https?://'
+    #[structopt(long)]
+    value_regex: Option<Regex>,
+}
+
+fn canonical_path(path: &OsStr) -> PathBuf {
+    let path = Path::new(path);
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn parse_glob(glob: &str) -> Result<GlobMatcher, globset::Error> {
+    Ok(Glob::new(glob)?.compile_matcher())
+}
+
+impl Filter {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name_regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(name))
+    }
+
+    /// Whether matching needs definition values, which aren't available from
+    /// every view of the index.
+    pub fn needs_values(&self) -> bool {
+        self.value_regex.is_some()
+    }
+
+    /// Whether a definition with the given value in the given file should be
+    /// considered.
+    pub fn matches_definition(&self, path: &str, value: &str) -> bool {
+        self.matches_file(path)
+            && self
+                .value_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(value))
+    }
+
+    /// Whether a definition in the given file should be considered. Relative
+    /// globs may match any trailing portion of the path.
+    pub fn matches_file(&self, path: &str) -> bool {
+        if !self.files.is_empty() && !self.files.contains(&canonical_path(path.as_ref())) {
+            return false;
+        }
+        let glob = match &self.in_file {
+            Some(glob) => glob,
+            None => return true,
+        };
+        let path = Path::new(path);
+        path.ancestors()
+            .filter_map(|ancestor| path.strip_prefix(ancestor).ok())
+            .any(|suffix| glob.is_match(suffix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() -> anyhow::Result<()> {
+        let filter = Filter {
+            name_regex: Some(Regex::new("^legacy_")?),
+            ..Default::default()
+        };
+        assert!(filter.matches("legacy_title"));
+        assert!(!filter.matches("title_legacy_"));
+        assert!(Filter::default().matches("title"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_definition() -> anyhow::Result<()> {
+        let filter = Filter {
+            value_regex: Some(Regex::new("https?://")?),
+            ..Default::default()
+        };
+        assert!(filter.needs_values());
+        assert!(filter.matches_definition("values/strings.xml", "See http://example.com"));
+        assert!(!filter.matches_definition("values/strings.xml", "See the docs"));
+        assert!(!Filter::default().needs_values());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_file() -> anyhow::Result<()> {
+        let filter = Filter {
+            in_file: Some(parse_glob("feature_x/**/strings.xml")?),
+            ..Default::default()
+        };
+        assert!(filter.matches_file("/src/feature_x/res/values/strings.xml"));
+        assert!(filter.matches_file("feature_x/res/values-fr/strings.xml"));
+        assert!(!filter.matches_file("/src/feature_y/res/values/strings.xml"));
+        assert!(Filter::default().matches_file("/src/feature_y/res/values/strings.xml"));
+
+        let filter = Filter {
+            files: vec![PathBuf::from("/src/feature_x/res/values/strings.xml")],
+            ..Default::default()
+        };
+        assert!(filter.matches_file("/src/feature_x/res/values/strings.xml"));
+        assert!(!filter.matches_file("/src/feature_x/res/values-fr/strings.xml"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,280 @@
+use crate::cache;
+use crate::index::{self, Indexer, ResourceFile, ResourceIndex};
+use crate::paths;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// The fingerprint of each top-level directory when its fragment was
+/// written.
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    fingerprints: BTreeMap<PathBuf, u64>,
+}
+
+/// What `update` did.
+pub struct Update {
+    pub reindexed: usize,
+    pub kept: usize,
+    pub removed: usize,
+}
+
+/// The directory the fragments are kept in, beside where the whole index
+/// would be cached.
+fn fragments_dir(indexer: &Indexer) -> PathBuf {
+    indexer
+        .cache_file()
+        .with_extension(cache::FRAGMENTS_EXTENSION)
+}
+
+fn manifest_file(indexer: &Indexer) -> PathBuf {
+    fragments_dir(indexer).join("manifest.json")
+}
+
+/// The fragment for the top-level directory `dir`, named by the hash of its
+/// path.
+fn fragment_file(indexer: &Indexer, dir: &Path) -> PathBuf {
+    let mut hash = cache::Fnv::default();
+    hash.write(dir.to_string_lossy().as_bytes());
+    let extension = indexer.cache_file();
+    let extension = extension.extension().unwrap_or_default().to_string_lossy();
+    fragments_dir(indexer).join(format!("{:016x}.{}", hash.finish(), extension))
+}
+
+fn read_manifest(indexer: &Indexer) -> Result<Option<Manifest>> {
+    let path = manifest_file(indexer);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let manifest = serde_json::from_reader(BufReader::new(File::open(&path)?))
+        .with_context(|| format!("Unable to read {}", path.display()))?;
+
+    Ok(Some(manifest))
+}
+
+/// Brings the fragments up to date, indexing just the top-level directories
+/// whose files changed since their fragments were written, all in one walk.
+/// The fragments of the others are left alone, and those of directories
+/// that are gone are removed.
+pub fn update(indexer: &Indexer) -> Result<Update> {
+    let fingerprints = indexer.fingerprints()?;
+    let old = read_manifest(indexer)?.unwrap_or_default();
+    let changed: Vec<&PathBuf> = fingerprints
+        .iter()
+        .filter(|(dir, fingerprint)| {
+            old.fingerprints.get(*dir) != Some(fingerprint) || !fragment_file(indexer, dir).exists()
+        })
+        .map(|(dir, _)| dir)
+        .collect();
+    let gone: Vec<&PathBuf> = old
+        .fingerprints
+        .keys()
+        .filter(|dir| !fingerprints.contains_key(*dir))
+        .collect();
+
+    fs::create_dir_all(fragments_dir(indexer))?;
+    if !changed.is_empty() {
+        let index = indexer
+            .clone()
+            .subtrees(changed.iter().map(|dir| dir.to_path_buf()).collect())
+            .index()?;
+
+        let mut fragments: BTreeMap<&Path, Vec<ResourceFile<String>>> = changed
+            .iter()
+            .map(|dir| (dir.as_path(), Vec::new()))
+            .collect();
+        for file in index.named_files() {
+            // Imported files belong to no directory, and are left out.
+            let dir = indexer.top_level_dir(&paths::from_stored(file.path()));
+            if let Some(files) = dir.and_then(|dir| fragments.get_mut(dir.as_path())) {
+                files.push(file);
+            }
+        }
+        for (dir, files) in fragments {
+            indexer.write_index(&ResourceIndex::new(files), &fragment_file(indexer, dir))?;
+        }
+    }
+    for dir in &gone {
+        let _ = fs::remove_file(fragment_file(indexer, dir));
+    }
+
+    let counts = (
+        changed.len(),
+        fingerprints.len() - changed.len(),
+        gone.len(),
+    );
+    write_manifest(indexer, &Manifest { fingerprints })?;
+    indexer.write_stamp()?;
+
+    let (reindexed, kept, removed) = counts;
+    Ok(Update {
+        reindexed,
+        kept,
+        removed,
+    })
+}
+
+/// Patches files indexed again into the fragments of their directories, and
+/// removes the files at `removed` from them. Directories new since the
+/// fragments were written get fragments of their own, with no fingerprint so
+/// that they're indexed in full by the next update.
+pub fn patch(
+    indexer: &Indexer,
+    updated: Vec<ResourceFile<String>>,
+    removed: &[String],
+) -> Result<()> {
+    let mut manifest = match read_manifest(indexer)? {
+        Some(manifest) => manifest,
+        None => {
+            update(indexer)?;
+            return Ok(());
+        }
+    };
+
+    type Patch<'a> = (Vec<ResourceFile<String>>, Vec<&'a str>);
+    let mut patches: BTreeMap<PathBuf, Patch> = BTreeMap::new();
+    for file in updated {
+        if let Some(dir) = indexer.top_level_dir(&paths::from_stored(file.path())) {
+            patches.entry(dir).or_default().0.push(file);
+        }
+    }
+    for path in removed {
+        if let Some(dir) = indexer.top_level_dir(&paths::from_stored(path)) {
+            patches.entry(dir).or_default().1.push(path);
+        }
+    }
+    for (dir, (updated, removed)) in patches {
+        let fragment = fragment_file(indexer, &dir);
+        let mut files = match fragment.exists() {
+            true => indexer.read_index(&fragment)?.named_files(),
+            false => Vec::new(),
+        };
+        files.retain(|file| !removed.contains(&file.path()));
+        index::merge_files(&mut files, updated);
+        indexer.write_index(&ResourceIndex::new(files), &fragment)?;
+        manifest.fingerprints.entry(dir).or_insert(0);
+    }
+
+    write_manifest(indexer, &manifest)
+}
+
+fn write_manifest(indexer: &Indexer, manifest: &Manifest) -> Result<()> {
+    cache::replace(&manifest_file(indexer), |out| {
+        Ok(serde_json::to_writer_pretty(out, manifest)?)
+    })
+}
+
+/// Loads the whole index from the fragments, with any imported files,
+/// writing the fragments first if there are none.
+pub fn load(indexer: &Indexer) -> Result<ResourceIndex> {
+    let manifest = match read_manifest(indexer)? {
+        Some(manifest) => manifest,
+        None => {
+            update(indexer)?;
+            read_manifest(indexer)?.unwrap_or_default()
+        }
+    };
+
+    let mut files = Vec::new();
+    for key in manifest.fingerprints.keys() {
+        files.extend(
+            indexer
+                .read_index(&fragment_file(indexer, key))?
+                .named_files(),
+        );
+    }
+    index::merge_files(&mut files, indexer.imported_files()?);
+
+    Ok(ResourceIndex::new(files))
+}
+
+/// The directory of the fragments, with the number of files they hold and
+/// the space they take, or None if there are none yet.
+pub fn summary(indexer: &Indexer) -> Result<Option<(PathBuf, usize, u64)>> {
+    let manifest = match read_manifest(indexer)? {
+        Some(manifest) => manifest,
+        None => return Ok(None),
+    };
+
+    let mut files = indexer.imported_files()?.len();
+    let mut size = fs::metadata(manifest_file(indexer))?.len();
+    for key in manifest.fingerprints.keys() {
+        let fragment = fragment_file(indexer, key);
+        size += fs::metadata(&fragment)?.len();
+        files += indexer.read_index(&fragment)?.files().len();
+    }
+
+    Ok(Some((fragments_dir(indexer), files, size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_update_and_load() -> Result<()> {
+        let tmp_dir = TempDir::new("fragments")?;
+        let root = tmp_dir.path().join("repo");
+        let create = |path: &str, content: &str| -> Result<()> {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            Ok(fs::write(path, content)?)
+        };
+        create(
+            "app/res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="feed">F</string><string name="old">O</string></resources>"#,
+        )?;
+        create("app/src/Main.java", "R.string.title;")?;
+        create("feed/src/Feed.java", "R.string.feed;")?;
+        let mut indexer = Indexer::new(
+            root.clone(),
+            root.clone(),
+            None,
+            Some(tmp_dir.path().join("cache")),
+        )?;
+        indexer.per_directory_cache(true);
+        let unused = || -> Result<Vec<String>> {
+            let index = indexer.deserialize()?;
+            let mut names: Vec<String> = index
+                .unused_strings()
+                .into_iter()
+                .map(|symbol| index.name(symbol).to_string())
+                .collect();
+            names.sort();
+            Ok(names)
+        };
+        let counts = |update: Update| (update.reindexed, update.kept, update.removed);
+
+        // Loading indexes everything the first time.
+        assert_eq!(unused()?, vec!["old"]);
+        assert!(!indexer.cache_file().exists());
+        assert_eq!(counts(update(&indexer)?), (0, 2, 0));
+
+        let app_fragment = fragment_file(&indexer, &root.join("app"));
+        let written = fs::metadata(&app_fragment)?.modified()?;
+        create("feed/src/Feed.java", "// Not any more")?;
+        assert_eq!(counts(update(&indexer)?), (1, 1, 0));
+        assert_eq!(fs::metadata(&app_fragment)?.modified()?, written);
+        assert_eq!(unused()?, vec!["feed", "old"]);
+
+        fs::remove_dir_all(root.join("feed"))?;
+        assert_eq!(counts(update(&indexer)?), (0, 1, 1));
+        assert_eq!(unused()?, vec!["feed", "old"]);
+
+        // Files updated one at a time are patched into their fragments, new
+        // directories included.
+        create("lib/src/Lib.java", "R.string.old;")?;
+        assert_eq!(
+            indexer.update_files(&[root.join("lib/src/Lib.java")])?,
+            (1, 0)
+        );
+        assert_eq!(unused()?, vec!["feed"]);
+        assert_eq!(counts(update(&indexer)?), (1, 1, 0));
+
+        Ok(())
+    }
+}
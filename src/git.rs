@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs git in `dir` and returns its trimmed stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow!("Unable to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the commit checked out in the repository containing `dir`.
+pub fn head_commit(dir: &Path) -> Result<String> {
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Creates and checks out a new branch in the repository containing `dir`.
+pub fn create_branch(dir: &Path, branch: &str) -> Result<()> {
+    git(dir, &["checkout", "-b", branch])?;
+    Ok(())
+}
+
+/// Commits the changes to `files` with `message`, leaving anything else that
+/// was staged alone.
+pub fn commit(dir: &Path, files: &[PathBuf], message: &str) -> Result<()> {
+    let files: Vec<String> = files.iter().map(|f| f.to_string_lossy().into()).collect();
+    let mut args = vec!["commit", "-q", "-m", message, "--"];
+    args.extend(files.iter().map(String::as_str));
+    git(dir, &args)?;
+    Ok(())
+}
+
+/// Returns the files tracked by git under `dir`, relative to `dir`.
+pub fn tracked_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let output = git(dir, &["ls-files", "-z", "--", "."])?;
+    Ok(output
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Returns the files under `dir` that differ from the checked out commit,
+/// staged or not, and the untracked files that aren't ignored, relative to
+/// `dir`.
+pub fn dirty_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let changed = git(
+        dir,
+        &["diff", "--name-only", "--relative", "-z", "HEAD", "--", "."],
+    )?;
+    let untracked = git(
+        dir,
+        &[
+            "ls-files",
+            "-z",
+            "--others",
+            "--exclude-standard",
+            "--",
+            ".",
+        ],
+    )?;
+    let mut files: Vec<PathBuf> = changed
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
+
+/// Who last changed a line, and when.
+#[derive(Debug, Clone, Serialize)]
+pub struct Blame {
+    pub commit: String,
+    pub author: String,
+    /// Seconds since the unix epoch.
+    pub time: i64,
+}
+
+impl Blame {
+    /// The commit date as YYYY-MM-DD (UTC).
+    pub fn date(&self) -> String {
+        // Days to civil date, from Howard Hinnant's date algorithms.
+        let z = self.time.div_euclid(86400) + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// Parses ages like 90d, 2w, 6m or 1y into seconds. Months are 30 days and
+/// years are 365.
+pub fn parse_age(age: &str) -> Result<i64> {
+    let age = age.trim();
+    let (digits, multiplier) = match age.char_indices().last() {
+        Some((i, 'd')) => (&age[..i], 1),
+        Some((i, 'w')) => (&age[..i], 7),
+        Some((i, 'm')) => (&age[..i], 30),
+        Some((i, 'y')) => (&age[..i], 365),
+        _ => return Err(anyhow!("Invalid age {}, expected e.g. 180d", age)),
+    };
+
+    digits
+        .trim()
+        .parse::<i64>()
+        .map(|n| n * multiplier * 86400)
+        .map_err(|_| anyhow!("Invalid age {}, expected e.g. 180d", age))
+}
+
+/// Blames every line of `path`, returning a map from 1 based line numbers.
+pub fn blame(path: &Path) -> Result<HashMap<u64, Blame>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a file", path))?
+        .to_string_lossy();
+    let output = git(dir, &["blame", "--porcelain", "--", &file])?;
+
+    // Commit details are only given the first time each commit appears.
+    let mut commits: HashMap<&str, Blame> = HashMap::new();
+    let mut lines: Vec<(u64, &str)> = Vec::new();
+    let mut current: Option<(&str, u64)> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            lines.extend(current.take().map(|(commit, line)| (line, commit)));
+            continue;
+        }
+        let mut fields = line.splitn(2, ' ');
+        let key = fields.next().unwrap_or_default();
+        let value = fields.next().unwrap_or_default();
+        if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            let final_line = value.split(' ').nth(1).and_then(|n| n.parse().ok());
+            current = final_line.map(|final_line| (key, final_line));
+            commits.entry(key).or_insert_with(|| Blame {
+                commit: key.to_string(),
+                author: String::new(),
+                time: 0,
+            });
+        } else if let Some((commit, _)) = current {
+            let blame = commits.get_mut(commit).unwrap();
+            match key {
+                "author" => blame.author = value.to_string(),
+                "author-time" => blame.time = value.parse().unwrap_or_default(),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(lines
+        .into_iter()
+        .map(|(line, commit)| (line, commits[commit].clone()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blame_date() {
+        let blame = |time| Blame {
+            commit: String::new(),
+            author: String::new(),
+            time,
+        };
+        assert_eq!(blame(0).date(), "1970-01-01");
+        assert_eq!(blame(951_782_400).date(), "2000-02-29");
+        assert_eq!(blame(1_704_067_199).date(), "2023-12-31");
+    }
+
+    #[test]
+    fn test_parse_age() -> Result<()> {
+        assert_eq!(parse_age("180d")?, 180 * 86400);
+        assert_eq!(parse_age("2w")?, 14 * 86400);
+        assert_eq!(parse_age("6m")?, 180 * 86400);
+        assert_eq!(parse_age("1y")?, 365 * 86400);
+        assert!(parse_age("180").is_err());
+        assert!(parse_age("xd").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::owners::Owners;
+use anyhow::{anyhow, Error, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How to bucket reported strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupBy {
+    /// The first N underscore separated segments of the name.
+    Prefix(usize),
+    /// The owners of the first file defining the string, from CODEOWNERS or
+    /// OWNERS files.
+    Owner,
+}
+
+impl FromStr for GroupBy {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = match value.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (value, None),
+        };
+        match kind {
+            "prefix" => {
+                let segments = match arg {
+                    Some(arg) => arg
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| anyhow!("Invalid prefix length {}", arg))?,
+                    None => 1,
+                };
+                Ok(GroupBy::Prefix(segments))
+            }
+            "owner" if arg.is_none() => Ok(GroupBy::Owner),
+            _ => Err(anyhow!("Unrecognized grouping {}", value)),
+        }
+    }
+}
+
+/// A string name with the files that define it.
+pub type Item = (String, Vec<String>);
+
+/// The bucket for strings in files nobody owns.
+const UNOWNED: &str = "(unowned)";
+
+fn prefix(name: &str, segments: usize) -> String {
+    name.split('_').take(segments).collect::<Vec<_>>().join("_")
+}
+
+impl GroupBy {
+    /// Buckets strings, given with the files defining them. Owners are looked
+    /// up from the repository containing `res_root`.
+    pub fn group(&self, res_root: &Path, items: Vec<Item>) -> Result<BTreeMap<String, Vec<Item>>> {
+        let owners = match self {
+            GroupBy::Owner => Some(Owners::find(res_root)?),
+            GroupBy::Prefix(_) => None,
+        };
+
+        let mut groups: BTreeMap<String, Vec<Item>> = BTreeMap::new();
+        for item in items {
+            let key = match (self, &owners) {
+                (GroupBy::Prefix(segments), _) => prefix(&item.0, *segments),
+                (GroupBy::Owner, owners) => item
+                    .1
+                    .first()
+                    .and_then(|path| owners.as_ref()?.owner_of(path))
+                    .unwrap_or_else(|| UNOWNED.to_string()),
+            };
+            groups.entry(key).or_default().push(item);
+        }
+
+        Ok(groups)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_prefix() -> Result<()> {
+        assert_eq!("prefix:2".parse::<GroupBy>()?, GroupBy::Prefix(2));
+        assert_eq!("prefix".parse::<GroupBy>()?, GroupBy::Prefix(1));
+        assert_eq!("owner".parse::<GroupBy>()?, GroupBy::Owner);
+        assert!("prefix:0".parse::<GroupBy>().is_err());
+        assert!("owner:1".parse::<GroupBy>().is_err());
+
+        let groups = GroupBy::Prefix(2).group(
+            Path::new("res"),
+            vec![
+                ("feature_x_title".to_string(), Vec::new()),
+                ("feature_x_body".to_string(), Vec::new()),
+                ("single".to_string(), Vec::new()),
+            ],
+        )?;
+        let counts: Vec<(&str, usize)> = groups
+            .iter()
+            .map(|(key, items)| (key.as_str(), items.len()))
+            .collect();
+        assert_eq!(counts, vec![("feature_x", 2), ("single", 1)]);
+
+        Ok(())
+    }
+}
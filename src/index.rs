@@ -1,427 +1,3483 @@
-use anyhow::{anyhow, Result};
-use dirs;
+use crate::cache;
+use crate::diagnostics::{self, Diagnostic, Diagnostics, Kind};
+use crate::fragments;
+use crate::git;
+use crate::index_json;
+use crate::lexer;
+use crate::mapped::{self, MappedIndex, UnsupportedVersion};
+use crate::paths;
+use crate::progress::{Progress, ProgressMode};
+use crate::remote::RemoteCache;
+use crate::sqlite::{self, SqliteIndex};
+use crate::stamp::{StalePolicy, Stamp};
+use crate::symbols::{Symbol, SymbolTable};
+use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 extern crate grep;
 use grep::matcher::Matcher;
-use grep::regex::RegexMatcher;
+use grep::regex::{RegexMatcher, RegexMatcherBuilder};
 use grep::searcher::sinks::UTF8;
-use grep::searcher::Searcher;
+use grep::searcher::{Encoding, MmapChoice, SearcherBuilder};
 use ignore::overrides::OverrideBuilder;
-use ignore::{WalkBuilder, WalkState};
+use ignore::{DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
+use memmap2::Mmap;
 use multimap::MultiMap;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
-use std::io::BufWriter;
-use std::path::Path;
+use std::io::{BufReader, Cursor, Read};
 use std::path::PathBuf;
-use std::sync::mpsc;
+use std::path::{Component, Path};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::vec::Vec;
-use xml::reader::{EventReader, XmlEvent};
+use xml::common::Position;
+use xml::reader::{ParserConfig, XmlEvent};
 
+#[derive(Clone)]
 pub struct Indexer {
     java_root: PathBuf,
     res_root: PathBuf,
     manifest_root: PathBuf,
     cache_dir: PathBuf,
+    progress: ProgressMode,
+    threads: usize,
+    backend: IndexBackend,
+    remote: Option<RemoteCache>,
+    include_generated: bool,
+    no_ignore: bool,
+    hidden: bool,
+    vcs_only: bool,
+    shard: Option<GlobSet>,
+    subtrees: Option<Vec<PathBuf>>,
+    follow_symlinks: bool,
+    include_comments: bool,
+    max_filesize: Option<u64>,
+    verbose: bool,
+    quiet: bool,
+    strict: bool,
+    ignore_public: bool,
+    per_directory_cache: bool,
+    stale: StalePolicy,
+    /// The stamp of the working tree, taken when first needed.
+    stamp: Arc<Mutex<Option<Stamp>>>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ResourceFile {
-    path: String,
-    string_definitions: Vec<String>,
-    string_usages: Vec<String>,
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StringDefinition<N = Symbol> {
+    pub name: N,
+    pub value: String,
+    pub line: u64,
+    /// Marked with an xdel:keep comment or attribute, so never reported unused.
+    pub keep: bool,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ResourceIndex {
-    files: Vec<ResourceFile>,
+/// The types of resource that can be checked for usages. Strings are indexed
+/// in detail, as `StringDefinition`s; the others only by where they're
+/// defined and used.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ResourceKind {
+    String,
+    Drawable,
+    Color,
+    Dimen,
+    Style,
+    Layout,
+    /// A destination in a navigation graph, named by its id.
+    Destination,
 }
 
-impl ResourceIndex {
-    pub fn new(files: Vec<ResourceFile>) -> ResourceIndex {
-        ResourceIndex { files }
+impl ResourceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResourceKind::String => "string",
+            ResourceKind::Drawable => "drawable",
+            ResourceKind::Color => "color",
+            ResourceKind::Dimen => "dimen",
+            ResourceKind::Style => "style",
+            ResourceKind::Layout => "layout",
+            ResourceKind::Destination => "destination",
+        }
     }
 
-    pub fn files_for_definition(&self) -> MultiMap<&String, String> {
-        let mut definitions_to_files = MultiMap::new();
-        for file in &self.files {
-            for key in &file.string_definitions {
-                definitions_to_files.insert(key, file.path.to_owned());
-            }
+    /// The kind of resource a reference of the given type, like the id in
+    /// R.id.home or @id/home, refers to. Ids are only tracked as navigation
+    /// destinations.
+    fn from_reference(type_name: &str) -> Result<ResourceKind> {
+        match type_name {
+            "id" => Ok(ResourceKind::Destination),
+            type_name => type_name.parse(),
         }
-        definitions_to_files
     }
+}
 
-    pub fn files_for_usage(&self) -> MultiMap<&String, String> {
-        let mut usages_to_files = MultiMap::new();
-        for file in &self.files {
-            for key in &file.string_usages {
-                usages_to_files.insert(key, file.path.to_owned());
-            }
+impl FromStr for ResourceKind {
+    type Err = anyhow::Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "string" => Ok(ResourceKind::String),
+            "drawable" => Ok(ResourceKind::Drawable),
+            "color" => Ok(ResourceKind::Color),
+            "dimen" => Ok(ResourceKind::Dimen),
+            "style" => Ok(ResourceKind::Style),
+            "layout" => Ok(ResourceKind::Layout),
+            "destination" => Ok(ResourceKind::Destination),
+            _ => Err(anyhow!("Unrecognized resource kind {}", value)),
         }
-        usages_to_files
     }
+}
 
-    pub fn defined_strings(&self) -> HashSet<&String> {
-        let mut defined_strings = HashSet::with_capacity(self.files.len());
-        for file in &self.files {
-            for key in &file.string_definitions {
-                defined_strings.insert(key);
-            }
-        }
+/// A resource other than a string, defined in a file. A drawable is defined
+/// once by each of its density variants; a color or dimen by an element in a
+/// values file, or a color by a color state list file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResourceDefinition<N = Symbol> {
+    pub kind: ResourceKind,
+    pub name: N,
+    /// 0 for a file that's a resource as a whole, like a drawable.
+    pub line: u64,
+    /// The style this one inherits from, named by its parent attribute or
+    /// else by the part of its name before the last dot. Framework parents
+    /// are left out.
+    pub parent: Option<N>,
+}
 
-        defined_strings
-    }
+/// A reference to a resource other than a string.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResourceUsage<N = Symbol> {
+    pub kind: ResourceKind,
+    pub name: N,
+}
 
-    pub fn used_strings(&self) -> HashSet<&String> {
-        let mut used_strings = HashSet::with_capacity(self.files.len());
-        for file in &self.files {
-            for key in &file.string_usages {
-                used_strings.insert(key);
-            }
-        }
+/// The type of the resource directory holding `path`, like drawable for
+/// drawable-hdpi.
+fn resource_dir_type(path: &Path) -> Option<&str> {
+    path.parent()?.file_name()?.to_str()?.split('-').next()
+}
 
-        used_strings
+/// The resource that the file at `path` is as a whole, if it's in a
+/// drawable, color or layout directory (of any density or other
+/// configuration). It's named by the file name up to the first dot, so that
+/// icon.9.png defines icon.
+pub fn file_resource(path: &Path) -> Option<ResourceDefinition<String>> {
+    let kind = match resource_dir_type(path)? {
+        "drawable" => ResourceKind::Drawable,
+        "color" => ResourceKind::Color,
+        "layout" => ResourceKind::Layout,
+        _ => return None,
+    };
+    let name = path.file_name()?.to_str()?.split('.').next()?;
+    if name.is_empty() {
+        return None;
     }
 
-    pub fn unused_strings(&self) -> HashSet<&String> {
-        let defined_strings = self.defined_strings();
-        let used_strings = self.used_strings();
+    Some(ResourceDefinition {
+        kind,
+        name: name.to_string(),
+        line: 0,
+        parent: None,
+    })
+}
 
-        defined_strings.difference(&used_strings).copied().collect()
+/// The parent of the style `name`, given its parent attribute if it has
+/// one: e.g. Base for parent="@style/Base", and for no attribute, Base for
+/// Base.Card.
+fn style_parent(name: &str, parent: Option<&str>) -> Option<String> {
+    match parent {
+        Some(parent) => {
+            let parent = parent.trim();
+            let parent = parent
+                .strip_prefix("@style/")
+                .or_else(|| parent.strip_prefix("style/"))
+                .unwrap_or(parent);
+            if parent.is_empty() || parent.contains(':') {
+                None
+            } else {
+                Some(parent.to_string())
+            }
+        }
+        None => name.rsplit_once('.').map(|(parent, _)| parent.to_string()),
     }
 }
 
-fn get_default_cache_dir() -> Result<PathBuf> {
-    let standard_cache_dir = dirs::cache_dir();
+/// The configuration of the values directory holding the resource file at
+/// `path`, e.g. fr for values-fr, or default for values.
+pub fn locale_of(path: &str) -> &str {
+    let dir = Path::new(path)
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+    match dir.strip_prefix("values-") {
+        Some(qualifiers) => qualifiers,
+        None => "default",
+    }
+}
 
-    let cache_dir = match standard_cache_dir {
-        Some(cache_dir) => cache_dir,
-        None => match dirs::home_dir() {
-            Some(home_dir) => home_dir.join(".art"),
-            None => return Err(anyhow!("Unable to determine cache dir"))
+/// The locale qualifier of the values directory holding the resource file at
+/// `path`, e.g. fr-rCA for values-fr-rCA-night, or None for one that isn't
+/// for a locale, like values or values-night.
+pub fn language_of(path: &str) -> Option<&str> {
+    let qualifiers = locale_of(path);
+    if qualifiers == "default" {
+        return None;
+    }
+    // Network codes come before the locale.
+    let mut start = 0;
+    for qualifier in qualifiers.split('-') {
+        let is_network = (qualifier.starts_with("mcc") || qualifier.starts_with("mnc"))
+            && qualifier[3..].bytes().all(|b| b.is_ascii_digit());
+        if !is_network {
+            break;
         }
-    };
-
-    Ok(cache_dir.join("art"))
+        start += qualifier.len() + 1;
+    }
+    let rest = qualifiers.get(start..)?;
+    let language = rest.split('-').next()?;
+    if language.starts_with("b+") {
+        return Some(language);
+    }
+    // Three letters could also be the car UI mode.
+    let is_language = (2..=3).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_lowercase())
+        && language != "car";
+    if !is_language {
+        return None;
+    }
+    let region = rest[language.len()..].strip_prefix('-').and_then(|rest| {
+        let region = rest.split('-').next()?.strip_prefix('r')?;
+        let is_region = region.len() == 2 && region.bytes().all(|b| b.is_ascii_uppercase())
+            || region.len() == 3 && region.bytes().all(|b| b.is_ascii_digit());
+        is_region.then_some(region.len() + 2)
+    });
+    Some(&rest[..language.len() + region.unwrap_or(0)])
 }
 
-impl Indexer {
-    pub fn new(
-        java_root: PathBuf,
-        res_root: PathBuf,
-        manifest_root: Option<PathBuf>,
-        cache_dir: Option<PathBuf>,
-    ) -> Result<Indexer> {
-        let cache_dir = match cache_dir {
-            Some(cache_dir) => cache_dir,
-            None => get_default_cache_dir()?,
-        };
+/// The source set a file belongs to. Usages from test source sets don't ship,
+/// so a string used only from them is usually dead product code.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceSet {
+    #[default]
+    Main,
+    /// Unit tests, in test/ or a variant's testDebug/ and the like.
+    Test,
+    /// Instrumentation tests, in androidTest/ or a variant's androidTestDebug/.
+    AndroidTest,
+}
 
-        // Default the manifest root to the res
-        let manifest_root = match manifest_root {
-            Some(manifest_root) => manifest_root,
-            None => res_root.clone(),
-        };
+impl SourceSet {
+    /// Splits a source set directory name into what it holds and the
+    /// variant it's for: testPaid into Test and Paid, test into Test and "",
+    /// and paid into Main and paid.
+    pub fn split(dir: &str) -> (SourceSet, &str) {
+        for (prefix, set) in [
+            ("androidTest", SourceSet::AndroidTest),
+            ("test", SourceSet::Test),
+        ] {
+            // A test directory, or a variant's, like testDebug.
+            if let Some(variant) = dir.strip_prefix(prefix) {
+                if variant.chars().next().is_none_or(char::is_uppercase) {
+                    return (set, variant);
+                }
+            }
+        }
 
-        Ok(Indexer {
-            java_root,
-            res_root,
-            manifest_root,
-            cache_dir,
-        })
+        (SourceSet::Main, dir)
     }
 
-    fn index_xml_file(path: &Path) -> Result<ResourceFile> {
-        let file = File::open(path)?;
-        let file = BufReader::new(file);
-        let mut parser = EventReader::new(file);
+    /// Classifies `path`, relative to the root it was found under, by the
+    /// directories it's in.
+    pub fn of(path: &Path) -> SourceSet {
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            let dir = component.as_os_str().to_str().unwrap_or("");
+            match SourceSet::split(dir) {
+                (SourceSet::Main, _) => continue,
+                (set, _) => return set,
+            }
+        }
 
-        let mut string_definitions = Vec::new();
-        let mut string_usages = Vec::new();
+        SourceSet::Main
+    }
 
-        let string_id_usage_pattern = Regex::new(r"(?m)@string/(\w+)")?;
+    pub fn is_test(self) -> bool {
+        self != SourceSet::Main
+    }
 
-        loop {
-            let e = parser.next();
-            match e {
-                Ok(XmlEvent::StartElement {
-                    name, attributes, ..
-                }) => {
-                    //                    let pos = parser.position();
-                    for attr in attributes {
-                        if attr.value.contains("@string") {
-                            if let Some(captures) = string_id_usage_pattern.captures(&attr.value) {
-                                if let Some(id) = captures.get(1) {
-                                    string_usages.push(String::from(id.as_str()));
-                                }
-                            }
-                        }
-                        if name.local_name.eq("string") && attr.name.local_name.eq("name") {
-                            string_definitions.push(attr.value);
-                        }
-                    }
-                }
-                Ok(XmlEvent::CData(data)) => {
-                    if data.contains("@string") {
-                        if let Some(captures) = string_id_usage_pattern.captures(&data) {
-                            if let Some(id) = captures.get(1) {
-                                string_usages.push(String::from(id.as_str()));
-                            }
-                        }
-                    }
-                }
-                Ok(XmlEvent::EndDocument) => break,
-                Err(e) => return Err(anyhow::Error::new(e).context(format!("In {:?}", path))),
-                _ => {}
-            }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceSet::Main => "main",
+            SourceSet::Test => "test",
+            SourceSet::AndroidTest => "androidTest",
+        }
+    }
+}
+
+impl FromStr for SourceSet {
+    type Err = anyhow::Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "main" => Ok(SourceSet::Main),
+            "test" => Ok(SourceSet::Test),
+            "androidTest" => Ok(SourceSet::AndroidTest),
+            _ => Err(anyhow!("Unrecognized source set {}", value)),
         }
+    }
+}
+
+/// What was found in one file. Files are indexed with plain string names,
+/// which are interned into `Symbol`s once they're added to a `ResourceIndex`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(bound(deserialize = "N: Deserialize<'de>"))]
+pub struct ResourceFile<N = Symbol> {
+    path: String,
+    // Defaults let an imported file give only what it has, like usages.
+    #[serde(default)]
+    source_set: SourceSet,
+    #[serde(default)]
+    string_definitions: Vec<StringDefinition<N>>,
+    #[serde(default)]
+    string_usages: Vec<N>,
+    /// Strings declared in public.xml, part of a library's API.
+    #[serde(default)]
+    public_strings: Vec<N>,
+    #[serde(default)]
+    resource_definitions: Vec<ResourceDefinition<N>>,
+    #[serde(default)]
+    resource_usages: Vec<ResourceUsage<N>>,
+}
 
-        Ok(ResourceFile {
-            path: path.to_str().unwrap().to_string(),
+impl<N> ResourceFile<N> {
+    pub fn new(
+        path: String,
+        string_definitions: Vec<StringDefinition<N>>,
+        string_usages: Vec<N>,
+    ) -> ResourceFile<N> {
+        ResourceFile {
+            path,
+            source_set: SourceSet::Main,
             string_definitions,
             string_usages,
-        })
+            public_strings: Vec::new(),
+            resource_definitions: Vec::new(),
+            resource_usages: Vec::new(),
+        }
     }
 
-    fn index_source_file(path: &Path) -> Result<ResourceFile> {
-        let mut string_usages = Vec::new();
-        let matcher = RegexMatcher::new(r"R.string.(\w+)")?;
-        Searcher::new().search_path(
-            &matcher,
-            path,
-            UTF8(|_, line| {
-                matcher.find_iter(line.as_bytes(), |found| {
-                    let found = String::from(&line[found][9..]);
-                    string_usages.push(found);
+    pub fn with_resources(
+        mut self,
+        definitions: Vec<ResourceDefinition<N>>,
+        usages: Vec<ResourceUsage<N>>,
+    ) -> ResourceFile<N> {
+        self.resource_definitions = definitions;
+        self.resource_usages = usages;
+        self
+    }
 
-                    true
-                })?;
+    pub fn with_public_strings(mut self, public_strings: Vec<N>) -> ResourceFile<N> {
+        self.public_strings = public_strings;
+        self
+    }
 
-                Ok(true)
-            }),
-        )?;
+    pub fn with_source_set(mut self, source_set: SourceSet) -> ResourceFile<N> {
+        self.source_set = source_set;
+        self
+    }
 
-        Ok(ResourceFile {
-            path: path.to_str().unwrap().to_string(),
-            string_definitions: Vec::new(),
-            string_usages,
-        })
+    pub fn path(&self) -> &str {
+        &self.path
     }
 
-    fn index_xml_files(&self, root: &PathBuf, pattern: &str) -> Result<Vec<ResourceFile>> {
-        let mut builder = WalkBuilder::new(&root);
-        let mut overrides = OverrideBuilder::new(&root);
-        overrides.add(&pattern)?;
-        builder.threads(36);
-        builder.overrides(overrides.build()?);
+    pub fn source_set(&self) -> SourceSet {
+        self.source_set
+    }
 
-        let (tx, rx) = mpsc::channel();
-        let (tx2, rx2) = mpsc::channel();
+    pub fn string_definitions(&self) -> &[StringDefinition<N>] {
+        &self.string_definitions
+    }
 
-        thread::spawn(move || {
-            let mut results = Vec::new();
-            for received in rx {
-                results.push(received);
-            }
+    pub fn string_usages(&self) -> &[N] {
+        &self.string_usages
+    }
 
-            tx2.send(results).unwrap();
-        });
+    pub fn public_strings(&self) -> &[N] {
+        &self.public_strings
+    }
 
-        let walker = builder.build_parallel();
-        walker.run(move || {
-            let tx = tx.clone();
-            Box::new(move |result| {
-                let result = result.unwrap();
-                let path = result.path();
-                if path.is_file() {
-                    let index = Indexer::index_xml_file(&path);
-                    match index {
-                        Ok(index) => tx.send(index).unwrap(),
-                        Err(e) => {
-                            eprintln!("Warning: Failed to parse xml file :{:?}", e);
-                        }
-                    }
-                }
-                WalkState::Continue
-            })
-        });
+    pub fn resource_definitions(&self) -> &[ResourceDefinition<N>] {
+        &self.resource_definitions
+    }
 
-        let results = rx2.recv().unwrap();
-        Ok(results)
+    pub fn resource_usages(&self) -> &[ResourceUsage<N>] {
+        &self.resource_usages
     }
 
-    fn index_source_files(&self) -> Result<Vec<ResourceFile>> {
-        let mut builder = WalkBuilder::new(&self.java_root);
-        let mut overrides = OverrideBuilder::new(&self.java_root);
-        overrides.add("*.java")?;
-        overrides.add("*.kt")?;
-        builder.threads(36);
-        builder.overrides(overrides.build()?);
+    /// Converts every name in the file with `f`, to intern or resolve them.
+    fn map_names<M>(self, f: &mut impl FnMut(N) -> M) -> ResourceFile<M> {
+        ResourceFile {
+            path: self.path,
+            source_set: self.source_set,
+            string_definitions: self
+                .string_definitions
+                .into_iter()
+                .map(|definition| StringDefinition {
+                    name: f(definition.name),
+                    value: definition.value,
+                    line: definition.line,
+                    keep: definition.keep,
+                })
+                .collect(),
+            string_usages: self.string_usages.into_iter().map(&mut *f).collect(),
+            public_strings: self.public_strings.into_iter().map(&mut *f).collect(),
+            resource_definitions: self
+                .resource_definitions
+                .into_iter()
+                .map(|definition| ResourceDefinition {
+                    kind: definition.kind,
+                    name: f(definition.name),
+                    line: definition.line,
+                    parent: definition.parent.map(&mut *f),
+                })
+                .collect(),
+            resource_usages: self
+                .resource_usages
+                .into_iter()
+                .map(|usage| ResourceUsage {
+                    kind: usage.kind,
+                    name: f(usage.name),
+                })
+                .collect(),
+        }
+    }
+}
 
-        let (tx, rx) = mpsc::channel();
-        let (tx2, rx2) = mpsc::channel();
+/// Adds `other` to `files`, replacing the files with the same paths.
+pub fn merge_files(files: &mut Vec<ResourceFile<String>>, other: Vec<ResourceFile<String>>) {
+    let paths: HashSet<String> = other.iter().map(|file| file.path().to_string()).collect();
+    files.retain(|file| !paths.contains(file.path()));
+    files.extend(other);
+}
 
-        thread::spawn(move || {
-            let mut results = Vec::new();
-            for received in rx {
-                results.push(received);
-            }
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResourceIndex {
+    symbols: SymbolTable,
+    files: Vec<ResourceFile>,
+    /// Problems found while indexing. Only kept for the index just built, not
+    /// stored with it.
+    #[serde(skip)]
+    diagnostics: Vec<Diagnostic>,
+}
 
-            tx2.send(results).unwrap();
-        });
+/// A stored index that can answer the quick queries (counts, ls-unused)
+/// without decoding everything, and load the full index when it has to.
+pub trait IndexView {
+    fn defined_count(&self) -> Result<usize>;
+    fn used_count(&self) -> Result<usize>;
+    /// Returns the names of unused strings, with the files defining them.
+    fn unused_strings(&self) -> Result<Vec<(String, Vec<String>)>>;
+    fn load(&self) -> Result<ResourceIndex>;
+}
 
-        let walker = builder.build_parallel();
-        walker.run(move || {
-            let tx = tx.clone();
-            Box::new(move |result| {
-                let index = Indexer::index_source_file(&result.unwrap().path());
-                if let Ok(index) = index {
-                    tx.send(index).unwrap();
-                }
-                WalkState::Continue
-            })
-        });
+/// What's known about the cached index, for `index status`.
+pub struct CacheStatus {
+    /// The index file, or the directory of fragments.
+    pub path: PathBuf,
+    pub roots: Vec<(&'static str, PathBuf)>,
+    /// When it was built, from its stamp, or else when it was last written.
+    pub built: SystemTime,
+    pub stamp: Option<Stamp>,
+    pub files: usize,
+    pub size: u64,
+    /// Why it's stale, if it is.
+    pub staleness: Option<String>,
+}
 
-        let results = rx2.recv().unwrap();
-        Ok(results)
+/// An index that's already in memory, answering quick queries directly.
+impl IndexView for ResourceIndex {
+    fn defined_count(&self) -> Result<usize> {
+        Ok(self.defined_strings().len())
     }
 
-    pub fn serialize(&self, index: &ResourceIndex) -> Result<()> {
-        // TODO: use a hash of the java and res root.
-        // TODO: compress with gz
-        let now = Instant::now();
+    fn used_count(&self) -> Result<usize> {
+        Ok(self.used_strings().len())
+    }
 
-        let cache_file = self.cache_dir.join("res_cache.bin");
-        let file = File::create(cache_file)?;
-        let file = BufWriter::new(file);
-        bincode::serialize_into(file, &index)?;
+    fn unused_strings(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let files_for_definition = self.files_for_definition();
+        let mut unused: Vec<(String, Vec<String>)> = ResourceIndex::unused_strings(self)
+            .into_iter()
+            .map(|symbol| {
+                let files = files_for_definition.get_vec(&symbol).into_iter().flatten();
+                (
+                    self.name(symbol).to_string(),
+                    files.map(|path| path.to_string()).collect(),
+                )
+            })
+            .collect();
+        unused.sort();
 
-        println!("Saved index in {}s", now.elapsed().as_secs());
+        Ok(unused)
+    }
 
-        Ok(())
+    fn load(&self) -> Result<ResourceIndex> {
+        Ok(self.clone())
     }
+}
 
-    pub fn deserialize(&self) -> Result<ResourceIndex> {
-        let cache_file = self.cache_dir.join("res_cache.bin");
-        let file = File::open(cache_file)?;
-        let file = BufReader::new(&file);
+/// Where the index is stored in the cache dir.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexBackend {
+    /// A single memory mapped file.
+    File,
+    /// An sqlite database, which can also be queried directly.
+    Sqlite,
+}
 
-        Ok(bincode::deserialize_from(file)?)
+impl FromStr for IndexBackend {
+    type Err = anyhow::Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "file" => Ok(IndexBackend::File),
+            "sqlite" => Ok(IndexBackend::Sqlite),
+            _ => Err(anyhow!("Unrecognized index backend {}", value)),
+        }
     }
+}
 
-    pub fn index(&self) -> Result<ResourceIndex> {
-        println!("Indexing resources...");
+/// Opens an index file written by either backend, such as a snapshot copied
+/// out of the cache dir. Files ending in .sqlite are read as sqlite.
+pub fn open_index_file(path: &Path) -> Result<Box<dyn IndexView>> {
+    if !path.exists() {
+        return Err(anyhow!("No index at {:?}", path));
+    }
+    Ok(match path.extension().and_then(|e| e.to_str()) {
+        Some("sqlite") => Box::new(SqliteIndex::open(path)?),
+        _ => Box::new(MappedIndex::open(path)?),
+    })
+}
 
-        let now = Instant::now();
-        let mut xml_files = self.index_xml_files(&self.res_root, "*.xml")?;
-        println!(
-            "Indexed {} xml files in {}s",
-            xml_files.len(),
-            now.elapsed().as_secs()
-        );
+/// The roots of a checkout by name. Paths under them are stored relative to
+/// them, as `$res/values/strings.xml`, so that an index built in one
+/// checkout, say in CI, can be used in another.
+#[derive(Clone)]
+pub struct PathRoots(Vec<(&'static str, PathBuf)>);
+
+impl PathRoots {
+    /// `path` as it's stored, relative to the innermost root containing it.
+    /// Paths outside the roots, like those of imported files, are stored as
+    /// they are.
+    pub fn portable(&self, path: &str) -> String {
+        self.relative(&paths::from_stored(path))
+            .map(|(name, relative)| {
+                // Separated by / whichever platform built it.
+                let components: Vec<String> = relative
+                    .components()
+                    .map(|component| paths::to_stored(Path::new(component.as_os_str())))
+                    .collect();
+                format!("${}/{}", name, components.join("/"))
+            })
+            .unwrap_or_else(|| path.to_string())
+    }
 
-        // Process AndroidManifest.xml files if the res root != the manifest root.
-        if !&self.manifest_root.eq(&self.res_root) {
-            let now = Instant::now();
-            let mut manifest_files =
-                self.index_xml_files(&self.manifest_root, "AndroidManifest.xml")?;
-            println!(
-                "Indexed {} AndroidManifest.xml files in {}s",
-                manifest_files.len(),
-                now.elapsed().as_secs()
-            );
-            xml_files.append(&mut manifest_files);
+    /// The name of the innermost root containing `path`, and `path` relative
+    /// to it.
+    pub fn relative<'a>(&self, path: &'a Path) -> Option<(&'static str, &'a Path)> {
+        self.0
+            .iter()
+            .filter_map(|(name, root)| Some((*name, path.strip_prefix(root).ok()?)))
+            .min_by_key(|(_, relative)| relative.components().count())
+    }
+
+    /// Where a stored path is in this checkout.
+    pub fn local(&self, path: &str) -> String {
+        let (name, relative) = match path.strip_prefix('$').and_then(|p| p.split_once('/')) {
+            Some(parts) => parts,
+            None => return path.to_string(),
+        };
+        match self.0.iter().find(|(root, _)| *root == name) {
+            Some((_, root)) => paths::to_stored(&root.join(paths::from_stored(relative))),
+            None => path.to_string(),
         }
+    }
+}
 
-        let now = Instant::now();
-        let mut source_files = self.index_source_files()?;
-        println!(
-            "Indexed {} source files in {}s",
-            source_files.len(),
-            now.elapsed().as_secs()
-        );
+/// A stored index, with its paths resolved against the roots of this
+/// checkout.
+struct LocalPaths {
+    view: Box<dyn IndexView>,
+    roots: PathRoots,
+}
 
-        source_files.append(&mut xml_files);
+impl IndexView for LocalPaths {
+    fn defined_count(&self) -> Result<usize> {
+        self.view.defined_count()
+    }
+
+    fn used_count(&self) -> Result<usize> {
+        self.view.used_count()
+    }
 
-        let index = ResourceIndex::new(source_files);
+    fn unused_strings(&self) -> Result<Vec<(String, Vec<String>)>> {
+        Ok(self
+            .view
+            .unused_strings()?
+            .into_iter()
+            .map(|(name, files)| {
+                let files = files.iter().map(|path| self.roots.local(path)).collect();
+                (name, files)
+            })
+            .collect())
+    }
 
-        Ok(index)
+    fn load(&self) -> Result<ResourceIndex> {
+        Ok(self.view.load()?.map_paths(|path| self.roots.local(path)))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempdir::TempDir;
+impl ResourceIndex {
+    #[tracing::instrument(skip_all)]
+    pub fn new(files: Vec<ResourceFile<String>>) -> ResourceIndex {
+        let mut symbols = SymbolTable::default();
+        let files = files
+            .into_iter()
+            .map(|file| file.map_names(&mut |name| symbols.intern(name)))
+            .collect();
+
+        ResourceIndex {
+            symbols,
+            files,
+            diagnostics: Vec::new(),
+        }
+    }
 
-    fn write_test_file(temp_dir: &TempDir, filename: &str, content: &str) -> Result<PathBuf> {
-        let file = temp_dir.path().join(&filename);
-        let parent = file.parent().unwrap();
+    /// Returns the resource name for a symbol from this index.
+    pub fn name(&self, symbol: Symbol) -> &str {
+        self.symbols.resolve(symbol)
+    }
 
-        std::fs::create_dir_all(parent)?;
+    pub fn files(&self) -> &[ResourceFile] {
+        &self.files
+    }
 
-        File::create(&file)?.write_all(content.as_bytes())?;
+    /// The files with their names rather than symbols, as they were before
+    /// they were indexed.
+    pub fn named_files(&self) -> Vec<ResourceFile<String>> {
+        self.files
+            .iter()
+            .map(|file| {
+                file.clone()
+                    .map_names(&mut |symbol| self.name(symbol).to_string())
+            })
+            .collect()
+    }
 
-        Ok(file)
+    /// Rewrites the path of every file, say between how it's stored and
+    /// where it is in this checkout.
+    pub fn map_paths(mut self, f: impl Fn(&str) -> String) -> ResourceIndex {
+        for file in &mut self.files {
+            file.path = f(&file.path);
+        }
+        self
     }
 
-    #[test]
-    fn test_index_java_with_multiple_refs_single_line() -> Result<()> {
-        let tmp_dir = TempDir::new("index")?;
-        let file = write_test_file(
-            &tmp_dir,
-            "Test.java",
-            r"
-            class Cool {
-                int values = [ R.string.foo, R.string.bar ];
-            }
-        ",
-        )?;
+    pub fn with_diagnostics(mut self, diagnostics: Vec<Diagnostic>) -> ResourceIndex {
+        self.diagnostics = diagnostics;
+        self
+    }
 
-        let result = Indexer::index_source_file(&file)?;
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 
-        assert_eq!(result.string_usages.len(), 2);
-        assert!(result.string_usages.contains(&"foo".to_string()));
-        assert!(result.string_usages.contains(&"bar".to_string()));
+    pub fn symbol_count(&self) -> usize {
+        self.symbols.len()
+    }
 
-        Ok(())
+    /// Returns the symbol for a resource name, if it appears in this index.
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.lookup(name)
     }
 
-    #[test]
-    fn test_index_manifest_files() -> Result<()> {
-        let tmp_dir = TempDir::new("index")?;
+    pub fn files_for_definition(&self) -> MultiMap<Symbol, &str> {
+        let mut definitions_to_files = MultiMap::new();
+        for file in &self.files {
+            for definition in &file.string_definitions {
+                definitions_to_files.insert(definition.name, file.path.as_str());
+            }
+        }
+        definitions_to_files
+    }
+
+    pub fn files_for_usage(&self) -> MultiMap<Symbol, &str> {
+        let mut usages_to_files = MultiMap::new();
+        for file in &self.files {
+            for key in &file.string_usages {
+                usages_to_files.insert(*key, file.path.as_str());
+            }
+        }
+        usages_to_files
+    }
+
+    pub fn defined_strings(&self) -> HashSet<Symbol> {
+        let mut defined_strings = HashSet::with_capacity(self.symbols.len());
+        for file in &self.files {
+            for definition in &file.string_definitions {
+                defined_strings.insert(definition.name);
+            }
+        }
+
+        defined_strings
+    }
+
+    pub fn used_strings(&self) -> HashSet<Symbol> {
+        let mut used_strings = HashSet::with_capacity(self.symbols.len());
+        for file in &self.files {
+            used_strings.extend(&file.string_usages);
+        }
+
+        used_strings
+    }
+
+    /// Returns every definition of the given string, along with the path of
+    /// the file it's defined in.
+    pub fn definitions_of(&self, name: Symbol) -> Vec<(&str, &StringDefinition)> {
+        let mut definitions = Vec::new();
+        for file in &self.files {
+            for definition in &file.string_definitions {
+                if definition.name == name {
+                    definitions.push((file.path.as_str(), definition));
+                }
+            }
+        }
+
+        definitions
+    }
+
+    /// Returns strings that are never reported unused: those with at least
+    /// one definition marked xdel:keep, and those declared in public.xml.
+    pub fn kept_strings(&self) -> HashSet<Symbol> {
+        let mut kept_strings = HashSet::new();
+        for file in &self.files {
+            for definition in &file.string_definitions {
+                if definition.keep {
+                    kept_strings.insert(definition.name);
+                }
+            }
+            kept_strings.extend(&file.public_strings);
+        }
+
+        kept_strings
+    }
+
+    /// Forgets public.xml declarations, so public strings can be reported
+    /// unused like any other.
+    pub fn ignore_public(&mut self) {
+        for file in &mut self.files {
+            file.public_strings.clear();
+        }
+    }
+
+    /// Returns strings that are used, but only from test source sets. Kept
+    /// strings aren't included.
+    pub fn test_only_strings(&self) -> HashSet<Symbol> {
+        let mut used_by_main = HashSet::new();
+        let mut used_by_tests = HashSet::new();
+        for file in &self.files {
+            if file.source_set.is_test() {
+                used_by_tests.extend(&file.string_usages);
+            } else {
+                used_by_main.extend(&file.string_usages);
+            }
+        }
+        let defined_strings = self.defined_strings();
+        let kept_strings = self.kept_strings();
+
+        used_by_tests
+            .difference(&used_by_main)
+            .filter(|s| defined_strings.contains(s) && !kept_strings.contains(s))
+            .copied()
+            .collect()
+    }
+
+    pub fn unused_strings(&self) -> HashSet<Symbol> {
+        let defined_strings = self.defined_strings();
+        let used_strings = self.used_strings();
+        let kept_strings = self.kept_strings();
+
+        defined_strings
+            .difference(&used_strings)
+            .filter(|s| !kept_strings.contains(s))
+            .copied()
+            .collect()
+    }
+
+    /// Returns the unused resources of a kind other than strings, with every
+    /// file defining them, by name. A resource is used if any of its
+    /// variants is referred to, and a style if any style inheriting from it
+    /// is. Names are compared as they are in R, with dots as underscores.
+    pub fn unused_resources(&self, kind: ResourceKind) -> Vec<(String, Vec<String>)> {
+        let field = |symbol: Symbol| self.name(symbol).replace('.', "_");
+        let mut used: HashSet<String> = self
+            .files
+            .iter()
+            .flat_map(|file| &file.resource_usages)
+            .filter(|usage| usage.kind == kind)
+            .map(|usage| field(usage.name))
+            .collect();
+        let mut parents: MultiMap<String, String> = MultiMap::new();
+        for file in &self.files {
+            for definition in &file.resource_definitions {
+                if let (true, Some(parent)) = (definition.kind == kind, definition.parent) {
+                    parents.insert(field(definition.name), field(parent));
+                }
+            }
+        }
+        let mut reachable: Vec<String> = used.iter().cloned().collect();
+        while let Some(name) = reachable.pop() {
+            for parent in parents.get_vec(&name).into_iter().flatten() {
+                if used.insert(parent.clone()) {
+                    reachable.push(parent.clone());
+                }
+            }
+        }
+
+        let mut unused: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for file in &self.files {
+            for definition in &file.resource_definitions {
+                if definition.kind == kind && !used.contains(&field(definition.name)) {
+                    unused
+                        .entry(self.name(definition.name))
+                        .or_default()
+                        .push(file.path.clone());
+                }
+            }
+        }
+
+        unused
+            .into_iter()
+            .map(|(name, mut paths)| {
+                paths.sort();
+                paths.dedup();
+                (name.to_string(), paths)
+            })
+            .collect()
+    }
+}
+
+/// `path` with symbolic links and relative parts resolved, so that paths to
+/// the same file compare equal. A file that doesn't exist, say as it was
+/// deleted, is resolved by its directory. On Windows it keeps the `\\?\`
+/// prefix, which long paths need, so it's only for comparing and opening.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        match (path.parent().map(Path::canonicalize), path.file_name()) {
+            (Some(Ok(parent)), Some(name)) => parent.join(name),
+            _ => path.to_path_buf(),
+        }
+    })
+}
+
+/// What identifies a file however it's reached: through overlapping roots,
+/// symbolic links, hard links or, on case-insensitive filesystems, another
+/// case.
+#[derive(PartialEq, Eq, Hash)]
+enum FileIdentity {
+    /// The device and inode, where there are inodes.
+    Inode(u64, u64),
+    /// The canonical path, case folded where the filesystems macOS and
+    /// Windows use by default fold case, so that `Strings.xml` and
+    /// `strings.xml` are one file there.
+    Path(PathBuf),
+}
+
+fn file_identity(path: &Path) -> FileIdentity {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = path.metadata() {
+            return FileIdentity::Inode(metadata.dev(), metadata.ino());
+        }
+    }
+    let canonical = canonical_path(path);
+    if cfg!(any(target_os = "macos", windows)) {
+        return FileIdentity::Path(PathBuf::from(paths::to_stored(&canonical).to_lowercase()));
+    }
+    FileIdentity::Path(canonical)
+}
+
+/// Keeps one of each file reached by several paths, the first of them, and
+/// reports the others.
+fn dedupe_files(
+    mut files: Vec<ResourceFile<String>>,
+    diagnostics: &Diagnostics,
+) -> Vec<ResourceFile<String>> {
+    files.sort_by(|a, b| a.path().cmp(b.path()));
+    let mut seen: HashMap<FileIdentity, String> = HashMap::new();
+    files.retain(|file| {
+        let path = paths::from_stored(file.path());
+        match seen.entry(file_identity(&path)) {
+            Entry::Occupied(kept) => {
+                let message = format!("the same file as {}", paths::display(kept.get()));
+                diagnostics.push(Kind::Duplicate, Some(&path), message);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(file.path().to_string());
+                true
+            }
+        }
+    });
+    files
+}
+
+/// `path` as a walk of `root` would find it, if it's under `root`.
+fn path_under(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = canonical_path(path)
+        .strip_prefix(canonical_path(root))
+        .ok()?
+        .to_path_buf();
+    Some(root.join(relative))
+}
+
+/// The source set of `path`, judged only by the directories below `root` so
+/// that a checkout under, say, ~/test isn't all test code.
+fn source_set_of(root: &Path, path: &Path) -> SourceSet {
+    SourceSet::of(path.strip_prefix(root).unwrap_or(path))
+}
+
+/// The source set directory holding `path`, like main, paid or testPaid: the
+/// one in src/<set>/java, kotlin or res, so that a plain src/com/... tree
+/// isn't taken for one. Files outside of any are shared by every variant.
+pub fn source_set_dir(path: &str) -> Option<&str> {
+    let dirs: Vec<&str> = Path::new(path)
+        .parent()?
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(dir) => dir.to_str(),
+            _ => None,
+        })
+        .collect();
+    dirs.windows(3)
+        .rev()
+        .find(|dirs| dirs[0] == "src" && ["java", "kotlin", "res"].contains(&dirs[2]))
+        .map(|dirs| dirs[1])
+}
+
+fn get_default_cache_dir() -> Result<PathBuf> {
+    let standard_cache_dir = dirs::cache_dir();
+
+    let cache_dir = match standard_cache_dir {
+        Some(cache_dir) => cache_dir,
+        None => match dirs::home_dir() {
+            Some(home_dir) => home_dir.join(".art"),
+            None => return Err(anyhow!("Unable to determine cache dir")),
+        },
+    };
+
+    Ok(cache_dir.join("art"))
+}
+
+/// Indexes files on one walker thread, buffering its results until the walk
+/// is over so that threads don't contend with each other while they work.
+struct IndexingVisitor<'s, F> {
+    index_file: &'s F,
+    progress: &'s Progress,
+    diagnostics: &'s Diagnostics,
+    results: Vec<ResourceFile<String>>,
+    merged: &'s Mutex<Vec<ResourceFile<String>>>,
+}
+
+impl<F> ParallelVisitor for IndexingVisitor<'_, F>
+where
+    F: Fn(&Path) -> Option<ResourceFile<String>> + Sync,
+{
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        match entry {
+            Ok(entry) => {
+                let path = entry.path();
+                if path.is_file() {
+                    self.progress.walked();
+                    if let Some(index) = (self.index_file)(path) {
+                        self.progress.indexed();
+                        self.results.push(index);
+                    }
+                }
+            }
+            Err(e) => self.diagnostics.push(Kind::Walk, None, e),
+        }
+        WalkState::Continue
+    }
+}
+
+impl<F> Drop for IndexingVisitor<'_, F> {
+    fn drop(&mut self) {
+        self.merged.lock().unwrap().append(&mut self.results);
+    }
+}
+
+struct IndexingVisitorBuilder<'s, F> {
+    index_file: &'s F,
+    progress: &'s Progress,
+    diagnostics: &'s Diagnostics,
+    merged: &'s Mutex<Vec<ResourceFile<String>>>,
+}
+
+impl<'s, F> ParallelVisitorBuilder<'s> for IndexingVisitorBuilder<'s, F>
+where
+    F: Fn(&Path) -> Option<ResourceFile<String>> + Sync,
+{
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(IndexingVisitor {
+            index_file: self.index_file,
+            progress: self.progress,
+            diagnostics: self.diagnostics,
+            results: Vec::new(),
+            merged: self.merged,
+        })
+    }
+}
+
+/// Whether the walk of `root` should go into `entry` to index a shard:
+/// directories are walked to find the files in the shard, which match
+/// `shard` themselves or are in a directory that does.
+fn in_shard(shard: &GlobSet, root: &Path, entry: &DirEntry) -> bool {
+    if entry.file_type().is_some_and(|t| t.is_dir()) {
+        return true;
+    }
+    let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+    relative
+        .ancestors()
+        .any(|path| !path.as_os_str().is_empty() && shard.is_match(path))
+}
+
+/// Whether `path` is in one of `subtrees`, or is a directory on the way to
+/// one.
+fn in_subtrees(subtrees: &[PathBuf], path: &Path) -> bool {
+    subtrees
+        .iter()
+        .any(|subtree| path.starts_with(subtree) || subtree.starts_with(path))
+}
+
+/// Walks the files selected by `builder` in parallel, returning whatever
+/// `index_file` produces for them.
+fn walk_parallel<F>(
+    builder: WalkBuilder,
+    progress: &Progress,
+    diagnostics: &Diagnostics,
+    index_file: F,
+) -> Vec<ResourceFile<String>>
+where
+    F: Fn(&Path) -> Option<ResourceFile<String>> + Sync,
+{
+    let merged = Mutex::new(Vec::new());
+    builder.build_parallel().visit(&mut IndexingVisitorBuilder {
+        index_file: &index_file,
+        progress,
+        diagnostics,
+        merged: &merged,
+    });
+
+    merged.into_inner().unwrap()
+}
+
+/// How source files refer to resources, capturing their kind and name.
+const USAGE_PATTERN: &str = r"R\.(string|drawable|color|dimen|style|layout|id)\.(\w+)";
+
+/// The patterns for finding resource usages in source files, compiled once
+/// for a whole walk rather than for each file.
+struct UsageMatchers {
+    /// Finds the lines with usages. Its literal R. prefix lets the searcher
+    /// skip ahead to candidate lines without running the regex.
+    lines: RegexMatcher,
+    /// Finds usages in matching lines and lexed source.
+    usages: Regex,
+}
+
+impl UsageMatchers {
+    fn new() -> Result<UsageMatchers> {
+        Ok(UsageMatchers {
+            lines: RegexMatcherBuilder::new()
+                .line_terminator(Some(b'\n'))
+                .build(USAGE_PATTERN)?,
+            usages: Regex::new(USAGE_PATTERN)?,
+        })
+    }
+}
+
+/// Indexes `path` with `index_file`, and if that fails because the file
+/// isn't valid UTF-8, again reading it as ISO-8859-1. Files that needed that
+/// are added to `diagnostics`.
+fn with_latin1_fallback(
+    path: &Path,
+    diagnostics: &Diagnostics,
+    index_file: impl Fn(&Path, bool) -> Result<ResourceFile<String>>,
+) -> Result<ResourceFile<String>> {
+    let error = match index_file(path, false) {
+        Ok(file) => return Ok(file),
+        Err(e) => e,
+    };
+    match std::fs::read(path) {
+        Ok(bytes) if std::str::from_utf8(&bytes).is_err() => {
+            let file = index_file(path, true)?;
+            diagnostics.push(Kind::Latin1, Some(path), "");
+            Ok(file)
+        }
+        _ => Err(error),
+    }
+}
+
+/// Whether `path` looks binary: it has a NUL byte in its first 8KiB, like
+/// git and ripgrep check for. UTF-16 files, which are full of them, have a
+/// byte order mark to tell them apart.
+fn is_binary(path: &Path) -> bool {
+    let mut start = [0; 8192];
+    let read = match File::open(path).and_then(|mut file| file.read(&mut start)) {
+        Ok(read) => &start[..read],
+        Err(_) => return false,
+    };
+    !read.starts_with(&[0xff, 0xfe]) && !read.starts_with(&[0xfe, 0xff]) && read.contains(&0)
+}
+
+/// Directories holding build output or generated code, which are skipped
+/// unless generated files are included.
+const GENERATED_DIRS: &[&str] = &["build", ".gradle", "generated"];
+
+/// Whether `entry` is build output or generated code, like the R classes.
+fn is_generated(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    match entry.file_type() {
+        Some(file_type) if file_type.is_dir() => {
+            entry.depth() > 0 && GENERATED_DIRS.contains(&name.as_ref())
+        }
+        _ => name == "R.java",
+    }
+}
+
+impl Indexer {
+    pub fn new(
+        java_root: PathBuf,
+        res_root: PathBuf,
+        manifest_root: Option<PathBuf>,
+        cache_dir: Option<PathBuf>,
+    ) -> Result<Indexer> {
+        let cache_dir = match cache_dir {
+            Some(cache_dir) => cache_dir,
+            None => get_default_cache_dir()?,
+        };
+
+        // Default the manifest root to the res
+        let manifest_root = match manifest_root {
+            Some(manifest_root) => manifest_root,
+            None => res_root.clone(),
+        };
+
+        Ok(Indexer {
+            java_root,
+            res_root,
+            manifest_root,
+            cache_dir,
+            progress: ProgressMode::None,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            backend: IndexBackend::File,
+            remote: None,
+            include_generated: false,
+            no_ignore: false,
+            hidden: false,
+            vcs_only: false,
+            shard: None,
+            subtrees: None,
+            follow_symlinks: false,
+            include_comments: false,
+            max_filesize: None,
+            verbose: false,
+            quiet: false,
+            strict: false,
+            ignore_public: false,
+            per_directory_cache: false,
+            stale: StalePolicy::Warn,
+            stamp: Default::default(),
+        })
+    }
+
+    pub fn remote_cache(&mut self, remote: RemoteCache) -> &mut Indexer {
+        self.remote = Some(remote);
+
+        self
+    }
+
+    /// Downloads the index for the checked out commit from the remote cache.
+    /// Returns false if there's no remote cache or it has no such index.
+    pub fn fetch(&self) -> Result<bool> {
+        let remote = match &self.remote {
+            Some(remote) => remote,
+            None => return Ok(false),
+        };
+
+        let commit = git::head_commit(&self.java_root)?;
+        std::fs::create_dir_all(&self.cache_dir)?;
+        match remote.fetch(&commit, &self.remote_name(), &self.cache_file()) {
+            Ok(()) => {
+                self.status(format_args!(
+                    "Fetched index for {} from the remote cache",
+                    commit
+                ));
+                // It was built from the commit, without local changes.
+                Stamp {
+                    dirty: Default::default(),
+                    ..self.take_stamp()
+                }
+                .write(&self.stamp_file())?;
+                Ok(true)
+            }
+            Err(e) => {
+                eprintln!("No index for {} in the remote cache: {}", commit, e);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Uploads the cached index to the remote cache, keyed by the checked out
+    /// commit.
+    pub fn push(&self) -> Result<()> {
+        let remote = self
+            .remote
+            .as_ref()
+            .ok_or_else(|| anyhow!("No remote cache configured"))?;
+
+        let commit = git::head_commit(&self.java_root)?;
+        remote.push(&commit, &self.remote_name(), &self.cache_file())?;
+        eprintln!("Pushed index for {} to the remote cache", commit);
+
+        Ok(())
+    }
+
+    pub fn backend(&mut self, backend: IndexBackend) -> &mut Indexer {
+        self.backend = backend;
+
+        self
+    }
+
+    /// Also indexes build output and generated code, which are skipped by
+    /// default.
+    pub fn include_generated(&mut self, include_generated: bool) -> &mut Indexer {
+        self.include_generated = include_generated;
+
+        self
+    }
+
+    /// Also indexes files excluded by .gitignore, .ignore and similar files.
+    pub fn no_ignore(&mut self, no_ignore: bool) -> &mut Indexer {
+        self.no_ignore = no_ignore;
+
+        self
+    }
+
+    /// Also indexes hidden files and directories.
+    pub fn hidden(&mut self, hidden: bool) -> &mut Indexer {
+        self.hidden = hidden;
+
+        self
+    }
+
+    /// Only indexes files tracked by git.
+    pub fn vcs_only(&mut self, vcs_only: bool) -> &mut Indexer {
+        self.vcs_only = vcs_only;
+
+        self
+    }
+
+    /// Only indexes the files matching one of `globs`, or in a directory
+    /// matching one, relative to the root they're under. For indexing a
+    /// subtree as a shard to merge with others.
+    pub fn shard(&mut self, globs: &[String]) -> Result<&mut Indexer> {
+        self.shard = if globs.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(Glob::new(glob)?);
+            }
+            Some(builder.build()?)
+        };
+
+        Ok(self)
+    }
+
+    /// Only indexes the files under these directories.
+    pub fn subtrees(&mut self, subtrees: Vec<PathBuf>) -> &mut Indexer {
+        self.subtrees = Some(subtrees);
+
+        self
+    }
+
+    /// Follows symbolic links while walking, indexing each file once however
+    /// many links lead to it.
+    pub fn follow_symlinks(&mut self, follow_symlinks: bool) -> &mut Indexer {
+        self.follow_symlinks = follow_symlinks;
+
+        self
+    }
+
+    /// Counts references to strings in comments and string literals as
+    /// usages, as they were before source files were lexed.
+    pub fn include_comments(&mut self, include_comments: bool) -> &mut Indexer {
+        self.include_comments = include_comments;
+
+        self
+    }
+
+    /// Skips files larger than this many bytes.
+    pub fn max_filesize(&mut self, max_filesize: Option<u64>) -> &mut Indexer {
+        self.max_filesize = max_filesize;
+
+        self
+    }
+
+    /// Lists the files skipped while indexing, rather than just counting
+    /// them.
+    pub fn verbose(&mut self, verbose: bool) -> &mut Indexer {
+        self.verbose = verbose;
+
+        self
+    }
+
+    /// Caches the index in fragments, one for each top-level directory of
+    /// the roots, so that indexing again only indexes and writes the
+    /// directories that changed.
+    pub fn per_directory_cache(&mut self, per_directory_cache: bool) -> &mut Indexer {
+        self.per_directory_cache = per_directory_cache;
+
+        self
+    }
+
+    /// What to do when the cached index was built from another commit, or
+    /// uncommitted files have changed since.
+    pub fn stale(&mut self, stale: StalePolicy) -> &mut Indexer {
+        self.stale = stale;
+
+        self
+    }
+
+    pub fn is_per_directory_cache(&self) -> bool {
+        self.per_directory_cache
+    }
+
+    /// Leaves out the messages saying what was indexed and how long it took.
+    pub fn quiet(&mut self, quiet: bool) -> &mut Indexer {
+        self.quiet = quiet;
+
+        self
+    }
+
+    fn status(&self, message: std::fmt::Arguments) {
+        if !self.quiet {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Fails indexing if any file couldn't be read or parsed, rather than
+    /// leaving it out of the index.
+    pub fn strict(&mut self, strict: bool) -> &mut Indexer {
+        self.strict = strict;
+
+        self
+    }
+
+    /// Reports strings declared in public.xml unused like any other, rather
+    /// than treating them as a library's API.
+    pub fn ignore_public(&mut self, ignore_public: bool) -> &mut Indexer {
+        self.ignore_public = ignore_public;
+
+        self
+    }
+
+    pub fn threads(&mut self, threads: usize) -> &mut Indexer {
+        self.threads = threads;
+
+        self
+    }
+
+    pub fn progress(&mut self, mode: ProgressMode) -> &mut Indexer {
+        self.progress = mode;
+
+        self
+    }
+
+    pub fn java_root(&self) -> &Path {
+        &self.java_root
+    }
+
+    pub fn res_root(&self) -> &Path {
+        &self.res_root
+    }
+
+    pub fn manifest_root(&self) -> &Path {
+        &self.manifest_root
+    }
+
+    /// Indexes a resource file. With `latin1`, its bytes are read as
+    /// ISO-8859-1 rather than UTF-8.
+    #[tracing::instrument(skip_all, fields(path = %path.display()))]
+    fn index_xml_file(path: &Path, latin1: bool) -> Result<ResourceFile<String>> {
+        let file: Box<dyn Read> = if latin1 {
+            // Decoded up front, since the declaration may well claim UTF-8.
+            let bytes = std::fs::read(path)?;
+            let text: String = bytes.into_iter().map(char::from).collect();
+            Box::new(Cursor::new(text.into_bytes()))
+        } else {
+            Box::new(BufReader::new(File::open(path)?))
+        };
+        let mut parser = ParserConfig::new()
+            .ignore_comments(false)
+            .create_reader(file);
+
+        let mut string_definitions = Vec::new();
+        let mut string_usages = Vec::new();
+
+        let string_id_usage_pattern = Regex::new(r"(?m)@string/(\w+)")?;
+        let resource_reference_pattern =
+            Regex::new(r"@(drawable|color|dimen|style|layout|id)/([\w.]+)")?;
+        let mut resource_definitions: Vec<_> = file_resource(path).into_iter().collect();
+        let mut resource_usages = Vec::new();
+        let find_resource_usages =
+            |text: &str, usages: &mut Vec<ResourceUsage<String>>| -> Result<()> {
+                for captures in resource_reference_pattern.captures_iter(text) {
+                    usages.push(ResourceUsage {
+                        kind: ResourceKind::from_reference(&captures[1])?,
+                        name: captures[2].to_string(),
+                    });
+                }
+                Ok(())
+            };
+        // In a navigation graph, the destination ids of the elements we're
+        // in, so a deep link can be attributed to its destination.
+        let is_navigation = resource_dir_type(path) == Some("navigation");
+        let mut destinations: Vec<Option<String>> = Vec::new();
+
+        // The definition whose value we're currently collecting, and how many
+        // elements deep into it we are.
+        let mut current_definition: Option<StringDefinition<String>> = None;
+        let mut definition_depth = 0;
+        // Whether the last thing we saw was an xdel:keep comment.
+        let mut keep_next = false;
+        let mut public_strings = Vec::new();
+        // The type of the <public-group> we're in, if any.
+        let mut public_group_type = None;
+
+        loop {
+            let e = parser.next();
+            match e {
+                Ok(XmlEvent::StartElement {
+                    name, attributes, ..
+                }) => {
+                    let pos = parser.position();
+                    if current_definition.is_some() {
+                        definition_depth += 1;
+                    }
+                    if is_navigation {
+                        // Every element but the graph itself and its actions
+                        // is a destination, whatever navigator it's for.
+                        let id = attributes
+                            .iter()
+                            .find(|attr| {
+                                attr.name.prefix.as_deref() == Some("android")
+                                    && attr.name.local_name == "id"
+                            })
+                            .and_then(|attr| {
+                                let id = attr.value.trim();
+                                id.strip_prefix("@+id/").or_else(|| id.strip_prefix("@id/"))
+                            })
+                            .filter(|_| !destinations.is_empty() && name.local_name != "action")
+                            .map(str::to_string);
+                        if let Some(id) = &id {
+                            resource_definitions.push(ResourceDefinition {
+                                kind: ResourceKind::Destination,
+                                name: id.clone(),
+                                line: pos.row + 1,
+                                parent: None,
+                            });
+                        }
+                        // A deep link makes its destination reachable from
+                        // outside the app.
+                        if name.local_name == "deepLink" {
+                            if let Some(Some(destination)) =
+                                destinations.iter().rev().find(|id| id.is_some())
+                            {
+                                resource_usages.push(ResourceUsage {
+                                    kind: ResourceKind::Destination,
+                                    name: destination.clone(),
+                                });
+                            }
+                        }
+                        destinations.push(id);
+                    }
+                    let keep = std::mem::take(&mut keep_next)
+                        || attributes.iter().any(|attr| {
+                            attr.name.prefix.as_deref() == Some("xdel")
+                                && attr.name.local_name == "keep"
+                                && attr.value != "false"
+                        });
+                    let attribute = |local_name: &str| {
+                        attributes
+                            .iter()
+                            .find(|attr| {
+                                attr.name.prefix.is_none() && attr.name.local_name == local_name
+                            })
+                            .map(|attr| attr.value.clone())
+                    };
+                    match name.local_name.as_str() {
+                        "public-group" => public_group_type = attribute("type"),
+                        "public" => {
+                            let public_type = attribute("type").or(public_group_type.clone());
+                            if public_type.as_deref() == Some("string") {
+                                public_strings.extend(attribute("name"));
+                            }
+                        }
+                        _ => {}
+                    }
+                    // Values defined by element, or by <item type=...>, like
+                    // the aliases <item type="drawable" name="..."> define.
+                    let resource_kind = match name.local_name.as_str() {
+                        "color" => Some(ResourceKind::Color),
+                        "dimen" => Some(ResourceKind::Dimen),
+                        "style" => Some(ResourceKind::Style),
+                        "item" => attribute("type").and_then(|kind| kind.parse().ok()),
+                        _ => None,
+                    };
+                    match (resource_kind, attribute("name")) {
+                        (Some(ResourceKind::String) | None, _) | (_, None) => {}
+                        (Some(kind), Some(resource_name)) => {
+                            let parent = if kind == ResourceKind::Style {
+                                style_parent(&resource_name, attribute("parent").as_deref())
+                            } else {
+                                None
+                            };
+                            resource_definitions.push(ResourceDefinition {
+                                kind,
+                                name: resource_name,
+                                line: pos.row + 1,
+                                parent,
+                            })
+                        }
+                    }
+                    for attr in attributes {
+                        // A style's parent is only used if the style is.
+                        if !(name.local_name == "style" && attr.name.local_name == "parent") {
+                            find_resource_usages(&attr.value, &mut resource_usages)?;
+                        }
+                        if attr.value.contains("@string") {
+                            if let Some(captures) = string_id_usage_pattern.captures(&attr.value) {
+                                if let Some(id) = captures.get(1) {
+                                    string_usages.push(String::from(id.as_str()));
+                                }
+                            }
+                        }
+                        if current_definition.is_none()
+                            && name.local_name.eq("string")
+                            && attr.name.local_name.eq("name")
+                        {
+                            current_definition = Some(StringDefinition {
+                                name: attr.value,
+                                value: String::new(),
+                                line: pos.row + 1,
+                                keep,
+                            });
+                        }
+                    }
+                }
+                Ok(XmlEvent::EndElement { name }) if name.local_name == "public-group" => {
+                    public_group_type = None;
+                }
+                Ok(XmlEvent::EndElement { .. }) if is_navigation => {
+                    destinations.pop();
+                }
+                Ok(XmlEvent::EndElement { .. }) if current_definition.is_some() => {
+                    if definition_depth == 0 {
+                        string_definitions.extend(current_definition.take());
+                    } else {
+                        definition_depth -= 1;
+                    }
+                }
+                Ok(XmlEvent::Comment(comment)) => {
+                    keep_next = comment.trim() == "xdel:keep";
+                }
+                Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) => {
+                    if !data.trim().is_empty() {
+                        keep_next = false;
+                    }
+                    find_resource_usages(&data, &mut resource_usages)?;
+                    if let Some(definition) = current_definition.as_mut() {
+                        definition.value.push_str(&data);
+                    }
+                }
+                Ok(XmlEvent::CData(data)) => {
+                    find_resource_usages(&data, &mut resource_usages)?;
+                    if data.contains("@string") {
+                        if let Some(captures) = string_id_usage_pattern.captures(&data) {
+                            if let Some(id) = captures.get(1) {
+                                string_usages.push(String::from(id.as_str()));
+                            }
+                        }
+                    }
+                    if let Some(definition) = current_definition.as_mut() {
+                        definition.value.push_str(&data);
+                    }
+                }
+                Ok(XmlEvent::EndDocument) => break,
+                Err(e) => return Err(anyhow::Error::new(e).context(format!("In {:?}", path))),
+                _ => {}
+            }
+        }
+
+        Ok(
+            ResourceFile::new(paths::to_stored(path), string_definitions, string_usages)
+                .with_public_strings(public_strings)
+                .with_resources(resource_definitions, resource_usages),
+        )
+    }
+
+    /// Indexes a source file. With `latin1`, its bytes are read as
+    /// ISO-8859-1 rather than UTF-8. Usages in comments and string literals
+    /// only count with `include_comments`.
+    #[tracing::instrument(skip_all, fields(path = %path.display()))]
+    fn index_source_file(
+        path: &Path,
+        matchers: &UsageMatchers,
+        latin1: bool,
+        include_comments: bool,
+    ) -> Result<ResourceFile<String>> {
+        let mut usages = Vec::new();
+        if include_comments {
+            let mut searcher = SearcherBuilder::new();
+            // SAFETY: like ripgrep, we accept that a file changing while it's
+            // mapped may give odd results. The searcher falls back to reading
+            // files it can't or shouldn't map.
+            searcher.memory_map(unsafe { MmapChoice::auto() });
+            if latin1 {
+                searcher.encoding(Some(Encoding::new("latin1")?));
+            }
+            searcher.build().search_path(
+                &matchers.lines,
+                path,
+                UTF8(|_, line| {
+                    for captures in matchers.usages.captures_iter(line) {
+                        usages.push((captures[1].to_string(), captures[2].to_string()));
+                    }
+
+                    Ok(true)
+                }),
+            )?;
+        } else {
+            // Ruling out usages that are commented out or quoted means lexing
+            // the whole file, so it's mapped once, and only lexed if it looks
+            // like it uses resources at all. Files that can't be mapped are
+            // read instead.
+            // SAFETY: as above, a file changing while it's mapped may give
+            // odd results.
+            let mapped = File::open(path).and_then(|file| unsafe { Mmap::map(&file) });
+            let read;
+            let bytes: &[u8] = match &mapped {
+                Ok(map) => map,
+                Err(_) => {
+                    read = std::fs::read(path)?;
+                    &read
+                }
+            };
+            if matchers.lines.is_match(bytes)? {
+                let source: Cow<str> = if latin1 {
+                    Cow::Owned(bytes.iter().copied().map(char::from).collect())
+                } else {
+                    Cow::Borrowed(std::str::from_utf8(bytes)?)
+                };
+                let kotlin = path.extension().is_some_and(|extension| extension == "kt");
+                let code = lexer::strip_comments_and_literals(&source, kotlin);
+                usages = matchers
+                    .usages
+                    .captures_iter(&code)
+                    .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+                    .collect();
+            }
+        }
+
+        let mut string_usages = Vec::new();
+        let mut resource_usages = Vec::new();
+        for (kind, name) in usages {
+            match ResourceKind::from_reference(&kind)? {
+                ResourceKind::String => string_usages.push(name),
+                kind => resource_usages.push(ResourceUsage { kind, name }),
+            }
+        }
+
+        Ok(
+            ResourceFile::new(paths::to_stored(path), Vec::new(), string_usages)
+                .with_resources(Vec::new(), resource_usages),
+        )
+    }
+
+    /// Starts a walk of `root` with the options shared by every phase.
+    fn walker(&self, root: &Path) -> Result<WalkBuilder> {
+        let mut builder = WalkBuilder::new(root);
+        builder.threads(self.threads);
+        builder.hidden(!self.hidden);
+        // The walk detects cycles itself, reporting them as errors.
+        builder.follow_links(self.follow_symlinks);
+        if self.no_ignore {
+            builder
+                .ignore(false)
+                .git_ignore(false)
+                .git_global(false)
+                .git_exclude(false)
+                .parents(false);
+        }
+        // The walk takes a single filter, so they're all checked in one.
+        let skip_generated = !self.include_generated;
+        let mut tracked = None;
+        if self.vcs_only {
+            let files = tracked.get_or_insert_with(HashSet::new);
+            for file in git::tracked_files(root)? {
+                let file = root.join(file);
+                files.extend(file.ancestors().map(Path::to_path_buf));
+            }
+        }
+        let shard = self.shard.clone();
+        let shard_root = root.to_path_buf();
+        let subtrees = self.subtrees.clone();
+        builder.filter_entry(move |entry| {
+            !(skip_generated && is_generated(entry))
+                && tracked
+                    .as_ref()
+                    .is_none_or(|tracked| tracked.contains(entry.path()))
+                && shard
+                    .as_ref()
+                    .is_none_or(|shard| in_shard(shard, &shard_root, entry))
+                && subtrees
+                    .as_ref()
+                    .is_none_or(|subtrees| in_subtrees(subtrees, entry.path()))
+        });
+
+        Ok(builder)
+    }
+
+    #[tracing::instrument(skip_all, fields(phase = phase))]
+    pub fn index_xml_files(
+        &self,
+        root: &Path,
+        pattern: &str,
+        phase: &'static str,
+        diagnostics: &Diagnostics,
+    ) -> Result<Vec<ResourceFile<String>>> {
+        let mut builder = self.walker(root)?;
+        let mut overrides = OverrideBuilder::new(root);
+        overrides.add(pattern)?;
+        builder.overrides(overrides.build()?);
+
+        let progress = self.progress.start(phase);
+        let results = walk_parallel(builder, &progress, diagnostics, |path| {
+            if let Some(kind) = self.skip_reason(path) {
+                diagnostics.push(kind, Some(path), "");
+                return None;
+            }
+            match with_latin1_fallback(path, diagnostics, Indexer::index_xml_file) {
+                Ok(index) => Some(index.with_source_set(source_set_of(root, path))),
+                Err(e) => {
+                    let kind = if e.is::<std::io::Error>() {
+                        Kind::Unreadable
+                    } else {
+                        Kind::XmlParse
+                    };
+                    diagnostics.push(kind, Some(path), format!("{:#}", e));
+                    None
+                }
+            }
+        });
+        progress.finish();
+
+        Ok(results)
+    }
+
+    /// Indexes the drawables that aren't xml (images, mostly) as defined by
+    /// their files; xml ones are indexed with the other xml files. Their
+    /// contents don't matter, so binary files aren't skipped.
+    #[tracing::instrument(skip_all)]
+    pub fn index_drawable_files(
+        &self,
+        diagnostics: &Diagnostics,
+    ) -> Result<Vec<ResourceFile<String>>> {
+        let mut builder = self.walker(&self.res_root)?;
+        let mut overrides = OverrideBuilder::new(&self.res_root);
+        overrides.add("**/drawable*/*")?;
+        overrides.add("!*.xml")?;
+        builder.overrides(overrides.build()?);
+
+        let progress = self.progress.start("drawables");
+        let results = walk_parallel(builder, &progress, diagnostics, |path| {
+            let definition = file_resource(path)?;
+            Some(
+                ResourceFile::new(paths::to_stored(path), Vec::new(), Vec::new())
+                    .with_resources(vec![definition], Vec::new())
+                    .with_source_set(source_set_of(&self.res_root, path)),
+            )
+        });
+        progress.finish();
+
+        Ok(results)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn index_source_files(
+        &self,
+        diagnostics: &Diagnostics,
+    ) -> Result<Vec<ResourceFile<String>>> {
+        let mut builder = self.walker(&self.java_root)?;
+        let mut overrides = OverrideBuilder::new(&self.java_root);
+        overrides.add("*.java")?;
+        overrides.add("*.kt")?;
+        builder.overrides(overrides.build()?);
+
+        let matchers = UsageMatchers::new()?;
+        let progress = self.progress.start("sources");
+        let results = walk_parallel(builder, &progress, diagnostics, |path| {
+            if let Some(kind) = self.skip_reason(path) {
+                diagnostics.push(kind, Some(path), "");
+                return None;
+            }
+            match with_latin1_fallback(path, diagnostics, |path, latin1| {
+                Indexer::index_source_file(path, &matchers, latin1, self.include_comments)
+            }) {
+                Ok(file) => Some(file.with_source_set(source_set_of(&self.java_root, path))),
+                Err(e) => {
+                    diagnostics.push(Kind::Unreadable, Some(path), format!("{:#}", e));
+                    None
+                }
+            }
+        });
+        progress.finish();
+
+        Ok(results)
+    }
+
+    /// Why `path` shouldn't be indexed, if it shouldn't.
+    fn skip_reason(&self, path: &Path) -> Option<Kind> {
+        let too_large = |max| std::fs::metadata(path).is_ok_and(|m| m.len() > max);
+        if self.max_filesize.is_some_and(too_large) {
+            return Some(Kind::TooLarge);
+        }
+        if is_binary(path) {
+            return Some(Kind::Binary);
+        }
+        None
+    }
+
+    /// The top-level directory `path` is in, under the outermost root it's
+    /// under, or the file itself if it's directly in that root: what the
+    /// per-directory cache splits the index by.
+    pub fn top_level_dir(&self, path: &Path) -> Option<PathBuf> {
+        let roots = [&self.res_root, &self.java_root, &self.manifest_root];
+        let root = roots
+            .iter()
+            .copied()
+            .filter(|root| path.starts_with(root))
+            .min_by_key(|root| root.components().count())?;
+        let first = path.strip_prefix(root).ok()?.components().next()?;
+        Some(root.join(first))
+    }
+
+    /// Fingerprints the files indexing would read in each top-level
+    /// directory by their paths, sizes and modification times, without
+    /// reading them. A directory's fingerprint changes when any of its files
+    /// is added, removed or modified.
+    #[tracing::instrument(skip_all)]
+    pub fn fingerprints(&self) -> Result<BTreeMap<PathBuf, u64>> {
+        let mut roots = vec![
+            (&self.res_root, vec!["*.xml", "**/drawable*/*"]),
+            (&self.java_root, vec!["*.java", "*.kt"]),
+        ];
+        if self.manifest_root != self.res_root {
+            roots.push((&self.manifest_root, vec!["AndroidManifest.xml"]));
+        }
+
+        let fingerprints = Mutex::new(BTreeMap::new());
+        for (root, patterns) in roots {
+            let mut builder = self.walker(root)?;
+            let mut overrides = OverrideBuilder::new(root);
+            for pattern in patterns {
+                overrides.add(pattern)?;
+            }
+            builder.overrides(overrides.build()?);
+            builder.build_parallel().run(|| {
+                Box::new(|entry| {
+                    let entry = match entry {
+                        Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => entry,
+                        _ => return WalkState::Continue,
+                    };
+                    let (key, metadata) = match (self.top_level_dir(entry.path()), entry.metadata())
+                    {
+                        (Some(key), Ok(metadata)) => (key, metadata),
+                        _ => return WalkState::Continue,
+                    };
+                    let mut hash = cache::Fnv::default();
+                    hash.write(entry.path().to_string_lossy().as_bytes());
+                    hash.write(&metadata.len().to_le_bytes());
+                    if let Ok(modified) = metadata.modified() {
+                        let since_epoch = modified.duration_since(std::time::UNIX_EPOCH);
+                        hash.write(&since_epoch.map_or(0, |d| d.as_nanos()).to_le_bytes());
+                    }
+                    // Files are walked in any order, so their hashes are
+                    // combined in a way that doesn't depend on it.
+                    let mut fingerprints = fingerprints.lock().unwrap();
+                    let fingerprint: &mut u64 = fingerprints.entry(key).or_default();
+                    *fingerprint = fingerprint.wrapping_add(hash.finish());
+                    WalkState::Continue
+                })
+            });
+        }
+
+        Ok(fingerprints.into_inner().unwrap())
+    }
+
+    /// Walks the roots as indexing does without reading any files, returning
+    /// how many files indexing would read. For timing the walk on its own.
+    #[tracing::instrument(skip_all)]
+    pub fn walk_only(&self) -> Result<usize> {
+        let mut roots = vec![
+            (&self.res_root, vec!["*.xml"]),
+            (&self.java_root, vec!["*.java", "*.kt"]),
+        ];
+        if self.manifest_root != self.res_root {
+            roots.push((&self.manifest_root, vec!["AndroidManifest.xml"]));
+        }
+
+        let count = AtomicUsize::new(0);
+        for (root, patterns) in roots {
+            let mut builder = self.walker(root)?;
+            let mut overrides = OverrideBuilder::new(root);
+            for pattern in patterns {
+                overrides.add(pattern)?;
+            }
+            builder.overrides(overrides.build()?);
+            builder.build_parallel().run(|| {
+                Box::new(|entry| {
+                    if entry.is_ok_and(|entry| entry.file_type().is_some_and(|t| t.is_file())) {
+                        count.fetch_add(1, Ordering::Relaxed);
+                    }
+                    WalkState::Continue
+                })
+            });
+        }
+
+        Ok(count.into_inner())
+    }
+
+    fn cache_extension(&self) -> &'static str {
+        match self.backend {
+            IndexBackend::File => "bin",
+            IndexBackend::Sqlite => "sqlite",
+        }
+    }
+
+    /// The cache file for this project. Each combination of roots gets its own
+    /// file so that indexes for different checkouts don't clobber each other.
+    pub fn cache_file(&self) -> PathBuf {
+        let mut hash = cache::Fnv::default();
+        for root in &[&self.java_root, &self.res_root, &self.manifest_root] {
+            let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+            hash.write(root.to_string_lossy().as_bytes());
+            hash.write(&[0]);
+        }
+
+        self.cache_dir.join(format!(
+            "{}-{:016x}.{}",
+            cache::CACHE_FILE_PREFIX,
+            hash.finish(),
+            self.cache_extension()
+        ))
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Where imported files are kept beside the cached index, so that
+    /// indexing again keeps them.
+    fn imports_file(&self) -> PathBuf {
+        self.cache_file().with_extension("imports.json")
+    }
+
+    /// The files brought in by `import`, to merge into each index built.
+    pub fn imported_files(&self) -> Result<Vec<ResourceFile<String>>> {
+        match self.imports_file() {
+            file if file.exists() => Ok(index_json::read(&file)?.files),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Merges the files in the index fragment at `path` into the cached
+    /// index, replacing any with the same paths, and keeps them to merge
+    /// into every index built from now on. Returns the number of files
+    /// imported.
+    pub fn import(&self, path: &Path) -> Result<usize> {
+        let imported = index_json::read(path)?.files;
+        let count = imported.len();
+
+        let mut imports = self.imported_files()?;
+        merge_files(&mut imports, imported.clone());
+        std::fs::create_dir_all(&self.cache_dir)?;
+        index_json::write_file(imports, &self.imports_file())?;
+
+        // Fragments don't hold imports; they're merged in as they're loaded.
+        if self.per_directory_cache {
+            return Ok(count);
+        }
+        if self.cache_file().exists() {
+            let mut files = self.deserialize()?.named_files();
+            merge_files(&mut files, imported);
+            self.serialize(&ResourceIndex::new(files))?;
+        } else {
+            self.serialize(&self.index()?)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Where the stamp of the working tree the cached index was built from
+    /// is kept.
+    fn stamp_file(&self) -> PathBuf {
+        cache::stamp_file(&self.cache_file())
+    }
+
+    /// Stamps the working tree, once per run, as each query checks it.
+    fn take_stamp(&self) -> Stamp {
+        let mut stamp = self.stamp.lock().unwrap();
+        stamp
+            .get_or_insert_with(|| {
+                Stamp::take(
+                    &[&self.java_root, &self.res_root, &self.manifest_root],
+                    &self.cache_dir,
+                )
+            })
+            .clone()
+    }
+
+    /// Stamps the cached index as built from the working tree as it is now.
+    pub fn write_stamp(&self) -> Result<()> {
+        self.take_stamp().write(&self.stamp_file())
+    }
+
+    /// Describes the cached index without building or refreshing it, or
+    /// returns None if there isn't one.
+    pub fn cache_status(&self) -> Result<Option<CacheStatus>> {
+        let (path, files, size) = if self.per_directory_cache {
+            match fragments::summary(self)? {
+                Some(summary) => summary,
+                None => return Ok(None),
+            }
+        } else {
+            let path = self.cache_file();
+            if !path.exists() {
+                return Ok(None);
+            }
+            let files = self.read_index(&path)?.files().len();
+            let size = path.metadata()?.len();
+            (path, files, size)
+        };
+
+        let stamp = Stamp::read(&self.stamp_file())?;
+        let built = match &stamp {
+            Some(stamp) => UNIX_EPOCH + Duration::from_secs(stamp.built),
+            None => path.metadata()?.modified()?,
+        };
+        let staleness = stamp
+            .as_ref()
+            .and_then(|stamp| stamp.staleness(&self.take_stamp()));
+        let mut roots = vec![
+            ("java", self.java_root.clone()),
+            ("res", self.res_root.clone()),
+        ];
+        if self.manifest_root != self.res_root {
+            roots.push(("manifest", self.manifest_root.clone()));
+        }
+
+        Ok(Some(CacheStatus {
+            path,
+            roots,
+            built,
+            stamp,
+            files,
+            size,
+            staleness,
+        }))
+    }
+
+    /// Warns, fails or indexes again, as configured, if the cached index was
+    /// built from another commit or uncommitted files changed since.
+    fn check_stale(&self) -> Result<()> {
+        let stamp = match Stamp::read(&self.stamp_file())? {
+            Some(stamp) => stamp,
+            None => return Ok(()),
+        };
+        let reason = match stamp.staleness(&self.take_stamp()) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+        match self.stale {
+            StalePolicy::Warn => eprintln!(
+                "Warning: the index is stale ({}). Run the index command, or pass --stale refresh",
+                reason
+            ),
+            StalePolicy::Error => {
+                return Err(anyhow!(
+                    "The index is stale ({}), failing because of --stale error",
+                    reason
+                ))
+            }
+            StalePolicy::Refresh => {
+                self.status(format_args!("The index is stale ({}), refreshing", reason));
+                if self.per_directory_cache {
+                    fragments::update(self)?;
+                } else {
+                    self.serialize(&self.index()?)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Indexes the file at `path` the way indexing every file would, or if
+    /// it's been deleted, returns None. Either way, also returns its path as
+    /// it's stored in the index.
+    fn index_one(
+        &self,
+        path: &Path,
+        diagnostics: &Diagnostics,
+    ) -> Result<(String, Option<ResourceFile<String>>)> {
+        let has_extension = |extension: &str| path.extension().is_some_and(|e| e == extension);
+        let is_manifest = path
+            .file_name()
+            .is_some_and(|name| name == "AndroidManifest.xml");
+        let in_res = path_under(&self.res_root, path);
+        let (root, stored) = if has_extension("xml") && in_res.is_some() {
+            (&self.res_root, in_res)
+        } else if is_manifest && path_under(&self.manifest_root, path).is_some() {
+            (&self.manifest_root, path_under(&self.manifest_root, path))
+        } else if has_extension("java") || has_extension("kt") {
+            (&self.java_root, path_under(&self.java_root, path))
+        } else if file_resource(path).is_some() {
+            (&self.res_root, in_res)
+        } else {
+            (&self.res_root, None)
+        };
+        let stored = stored.ok_or_else(|| {
+            anyhow!(
+                "{} isn't a resource, manifest or source file under the roots",
+                path.display()
+            )
+        })?;
+        let name = paths::to_stored(&stored);
+        if !stored.exists() {
+            return Ok((name, None));
+        }
+
+        let file = if has_extension("xml") {
+            with_latin1_fallback(&stored, diagnostics, Indexer::index_xml_file)
+        } else if has_extension("java") || has_extension("kt") {
+            let matchers = UsageMatchers::new()?;
+            with_latin1_fallback(&stored, diagnostics, |path, latin1| {
+                Indexer::index_source_file(path, &matchers, latin1, self.include_comments)
+            })
+        } else {
+            Ok(ResourceFile::new(name.clone(), Vec::new(), Vec::new())
+                .with_resources(file_resource(&stored).into_iter().collect(), Vec::new()))
+        }
+        .with_context(|| format!("Unable to index {}", stored.display()))?;
+
+        Ok((
+            name,
+            Some(file.with_source_set(source_set_of(root, &stored))),
+        ))
+    }
+
+    /// Indexes just the files at `paths` again and patches them into the
+    /// cached index, removing any that were deleted, for editors and hooks
+    /// that know what changed. Returns how many were updated and removed.
+    pub fn update_files(&self, paths: &[PathBuf]) -> Result<(usize, usize)> {
+        let diagnostics = Diagnostics::default();
+        let mut updated = Vec::new();
+        let mut removed = Vec::new();
+        for path in paths {
+            match self.index_one(path, &diagnostics)? {
+                (_, Some(file)) => updated.push(file),
+                (name, None) => removed.push(name),
+            }
+        }
+        diagnostics::report(&diagnostics.into_sorted(), self.verbose, self.progress);
+        let counts = (updated.len(), removed.len());
+
+        if self.per_directory_cache {
+            fragments::patch(self, updated, &removed)?;
+        } else {
+            if !self.cache_file().exists() {
+                return Err(anyhow!("No index found. Run the index command first"));
+            }
+            let mut files = self.open_cache_file()?.load()?.named_files();
+            files.retain(|file| !removed.iter().any(|path| path == file.path()));
+            merge_files(&mut files, updated);
+            self.write_index(&ResourceIndex::new(files), &self.cache_file())?;
+        }
+
+        // The index is as fresh as it was, but for these files, which may
+        // have changed since the working tree was stamped.
+        self.stamp.lock().unwrap().take();
+        if let Some(mut stamp) = Stamp::read(&self.stamp_file())? {
+            stamp.refresh(&self.take_stamp(), paths);
+            stamp.write(&self.stamp_file())?;
+        }
+
+        Ok(counts)
+    }
+
+    /// The name index files are stored under in the remote cache, where
+    /// they're keyed by commit rather than by local paths.
+    fn remote_name(&self) -> String {
+        format!("{}.{}", cache::CACHE_FILE_PREFIX, self.cache_extension())
+    }
+
+    pub fn serialize(&self, index: &ResourceIndex) -> Result<()> {
+        // TODO: compress with gz
+        let now = Instant::now();
+
+        std::fs::create_dir_all(&self.cache_dir)?;
+        self.write_index(index, &self.cache_file())?;
+        self.write_stamp()?;
+
+        self.status(format_args!("Saved index in {}s", now.elapsed().as_secs()));
+
+        Ok(())
+    }
+
+    /// Reads the index at `path`, written with the configured backend.
+    pub fn read_index(&self, path: &Path) -> Result<ResourceIndex> {
+        self.open_file(path)?
+            .load()
+            .with_context(|| format!("Unable to read the index at {}", path.display()))
+    }
+
+    /// Merges the indexes at `shards`, say of different subtrees indexed on
+    /// different machines, into one written to `out`. A file in several
+    /// shards is only kept once, from the last of them. Returns the number
+    /// of files in the merged index.
+    pub fn merge(&self, shards: &[PathBuf], out: &Path) -> Result<usize> {
+        let mut files = Vec::new();
+        for shard in shards {
+            merge_files(&mut files, self.read_index(shard)?.named_files());
+        }
+        let count = files.len();
+        self.write_index(&ResourceIndex::new(files), out)?;
+
+        Ok(count)
+    }
+
+    /// Writes `index` to `path` with the configured backend.
+    #[tracing::instrument(skip_all, fields(path = %path.display()))]
+    pub fn write_index(&self, index: &ResourceIndex, path: &Path) -> Result<()> {
+        let roots = self.path_roots();
+        let index = &index.clone().map_paths(|path| roots.portable(path));
+        match self.backend {
+            IndexBackend::File => cache::replace(path, |out| mapped::write(index, out)),
+            IndexBackend::Sqlite => sqlite::write(index, path),
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn deserialize(&self) -> Result<ResourceIndex> {
+        let mut index = self.open_stored()?.load().context(
+            "Unable to read the index. It may be corrupt; run the index command to rebuild it",
+        )?;
+        if self.ignore_public {
+            index.ignore_public();
+        }
+
+        Ok(index)
+    }
+
+    /// Opens the index at `path`, written with the configured backend.
+    fn open_file(&self, path: &Path) -> Result<Box<dyn IndexView>> {
+        let view: Box<dyn IndexView> = match self.backend {
+            IndexBackend::File => Box::new(MappedIndex::open(path)?),
+            IndexBackend::Sqlite => Box::new(SqliteIndex::open(path)?),
+        };
+        Ok(Box::new(LocalPaths {
+            view,
+            roots: self.path_roots(),
+        }))
+    }
+
+    /// The roots paths are stored relative to.
+    pub fn path_roots(&self) -> PathRoots {
+        PathRoots(vec![
+            ("java", self.java_root.clone()),
+            ("res", self.res_root.clone()),
+            ("manifest", self.manifest_root.clone()),
+        ])
+    }
+
+    fn open_cache_file(&self) -> Result<Box<dyn IndexView>> {
+        self.open_file(&self.cache_file())
+    }
+
+    /// Opens the cached index without decoding it, for quick queries. If the
+    /// cache was written in a format we can no longer read, the index is
+    /// rebuilt first.
+    fn open_stored(&self) -> Result<Box<dyn IndexView>> {
+        if self.per_directory_cache {
+            self.check_stale()?;
+            return Ok(Box::new(fragments::load(self)?));
+        }
+        if !self.cache_file().exists() && !self.fetch()? {
+            return Err(anyhow!("No index found. Run the index command first"));
+        }
+        self.check_stale()?;
+
+        match self.open_cache_file() {
+            Err(e) if e.downcast_ref::<UnsupportedVersion>().is_some() => {
+                eprintln!("The index needs to be rebuilt: {}", e);
+                let index = self.index()?;
+                self.serialize(&index)?;
+                self.open_cache_file()
+            }
+            result => result,
+        }
+    }
+
+    /// Opens the index for quick queries. The stored quick queries count
+    /// public strings as kept, so with `ignore_public` they're answered from
+    /// the full index instead.
+    pub fn open(&self) -> Result<Box<dyn IndexView>> {
+        if !self.ignore_public {
+            return self.open_stored();
+        }
+
+        Ok(Box::new(self.deserialize()?))
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn index(&self) -> Result<ResourceIndex> {
+        self.status(format_args!("Indexing resources..."));
+
+        let diagnostics = Diagnostics::default();
+        let now = Instant::now();
+        let mut xml_files =
+            self.index_xml_files(&self.res_root, "*.xml", "resources", &diagnostics)?;
+        self.status(format_args!(
+            "Indexed {} xml files in {}s",
+            xml_files.len(),
+            now.elapsed().as_secs()
+        ));
+
+        // Process AndroidManifest.xml files if the res root != the manifest root.
+        if !&self.manifest_root.eq(&self.res_root) {
+            let now = Instant::now();
+            let mut manifest_files = self.index_xml_files(
+                &self.manifest_root,
+                "AndroidManifest.xml",
+                "manifests",
+                &diagnostics,
+            )?;
+            self.status(format_args!(
+                "Indexed {} AndroidManifest.xml files in {}s",
+                manifest_files.len(),
+                now.elapsed().as_secs()
+            ));
+            xml_files.append(&mut manifest_files);
+        }
+
+        let now = Instant::now();
+        let mut drawable_files = self.index_drawable_files(&diagnostics)?;
+        self.status(format_args!(
+            "Indexed {} drawable files in {}s",
+            drawable_files.len(),
+            now.elapsed().as_secs()
+        ));
+        xml_files.append(&mut drawable_files);
+
+        let now = Instant::now();
+        let mut source_files = self.index_source_files(&diagnostics)?;
+        self.status(format_args!(
+            "Indexed {} source files in {}s",
+            source_files.len(),
+            now.elapsed().as_secs()
+        ));
+
+        source_files.append(&mut xml_files);
+        let mut source_files = dedupe_files(source_files, &diagnostics);
+
+        let imports = self.imported_files()?;
+        if !imports.is_empty() {
+            self.status(format_args!("Merged {} imported files", imports.len()));
+            merge_files(&mut source_files, imports);
+        }
+
+        let index = ResourceIndex::new(source_files).with_diagnostics(diagnostics.into_sorted());
+        diagnostics::report(index.diagnostics(), self.verbose, self.progress);
+        if self.strict {
+            let errors = index
+                .diagnostics()
+                .iter()
+                .filter(|d| d.kind.is_error())
+                .count();
+            if errors > 0 {
+                return Err(anyhow!(
+                    "{} files couldn't be indexed, failing because of --strict",
+                    errors
+                ));
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn write_test_file(temp_dir: &TempDir, filename: &str, content: &str) -> Result<PathBuf> {
+        let file = temp_dir.path().join(filename);
+        let parent = file.parent().unwrap();
+
+        std::fs::create_dir_all(parent)?;
+
+        File::create(&file)?.write_all(content.as_bytes())?;
+
+        Ok(file)
+    }
+
+    #[test]
+    fn test_index_java_with_multiple_refs_single_line() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let file = write_test_file(
+            &tmp_dir,
+            "Test.java",
+            r"
+            class Cool {
+                int values = [ R.string.foo, R.string.bar ];
+            }
+        ",
+        )?;
+
+        let result = Indexer::index_source_file(&file, &UsageMatchers::new()?, false, false)?;
+
+        assert_eq!(result.string_usages.len(), 2);
+        assert!(result.string_usages.contains(&"foo".to_string()));
+        assert!(result.string_usages.contains(&"bar".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_source_skips_comments_and_literals() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let file = write_test_file(
+            &tmp_dir,
+            "Test.java",
+            r#"
+            class Cool {
+                // R.string.commented
+                /* R.string.block */
+                String s = "R.string.quoted";
+                int used = R.string.used;
+            }
+        "#,
+        )?;
+        let kotlin = write_test_file(
+            &tmp_dir,
+            "Test.kt",
+            "val s = \"\"\"R.string.raw\"\"\" // R.string.commented\nval used = R.string.kotlin\n",
+        )?;
+        let matchers = UsageMatchers::new()?;
+
+        let result = Indexer::index_source_file(&file, &matchers, false, false)?;
+        assert_eq!(result.string_usages, vec!["used"]);
+        let result = Indexer::index_source_file(&kotlin, &matchers, false, false)?;
+        assert_eq!(result.string_usages, vec!["kotlin"]);
+        let result = Indexer::index_source_file(&file, &matchers, false, true)?;
+        assert_eq!(
+            result.string_usages,
+            vec!["commented", "block", "quoted", "used"]
+        );
+
+        // Files without usages aren't decoded, so stray bytes don't matter.
+        let latin1 = tmp_dir.path().join("Latin1.java");
+        std::fs::write(&latin1, b"// Caf\xe9\nclass A {}")?;
+        let result = Indexer::index_source_file(&latin1, &matchers, false, false)?;
+        assert!(result.string_usages.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_manifest_files() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
         let manifest_dir = tmp_dir.path().join("manifest");
         write_test_file(
             &tmp_dir,
-            "manifest/AndroidManifest.xml",
-            r#"<manifest><application label="@string/some_app"></application></manifest>"#,
+            "manifest/AndroidManifest.xml",
+            r#"<manifest><application label="@string/some_app"></application></manifest>"#,
+        )?;
+
+        let res_dir = tmp_dir.path().join("res");
+        write_test_file(
+            &tmp_dir,
+            "res/strings.xml",
+            r#"<resources><string name="some_app" value="My App" /></resources>"#,
+        )?;
+
+        let src_dir = tmp_dir.path().join("src");
+        std::fs::create_dir_all(&src_dir)?;
+        let indexer = Indexer::new(src_dir, res_dir, Some(manifest_dir), None)?;
+
+        let index = indexer.index()?;
+
+        let some_app = index.symbol("some_app").unwrap();
+        assert!(index.defined_strings().contains(&some_app));
+        assert!(index.used_strings().contains(&some_app));
+        assert!(index.unused_strings().is_empty());
+
+        Ok(())
+    }
+
+    // Hard links are only told apart by inode on unix.
+    #[cfg(unix)]
+    #[test]
+    fn test_index_dedupes_files() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let manifest = write_test_file(
+            &tmp_dir,
+            "res/AndroidManifest.xml",
+            r#"<manifest><application label="@string/some_app"></application></manifest>"#,
+        )?;
+        let main = write_test_file(&tmp_dir, "src/Main.java", "R.string.some_app;")?;
+        std::fs::hard_link(&main, tmp_dir.path().join("src/Linked.java"))?;
+        // The manifest root spells the res root another way, so the manifest
+        // is reached through both.
+        let res_dir = tmp_dir.path().join("res");
+        let manifest_dir = res_dir.join("../res");
+        let indexer = Indexer::new(
+            tmp_dir.path().join("src"),
+            res_dir,
+            Some(manifest_dir.clone()),
+            None,
+        )?;
+
+        let index = indexer.index()?;
+        assert_eq!(index.files().len(), 2);
+        let found: Vec<(Kind, Option<String>)> = index
+            .diagnostics()
+            .iter()
+            .map(|d| (d.kind, d.path.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Kind::Duplicate, Some(manifest.display().to_string())),
+                (Kind::Duplicate, Some(main.display().to_string())),
+            ]
+        );
+        assert_eq!(
+            index.diagnostics()[0].message,
+            format!(
+                "the same file as {}",
+                manifest_dir.join("AndroidManifest.xml").display()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_threads() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let mut strings = String::from(r#"<resources><string name="unused">U</string>"#);
+        for i in 0..20 {
+            write_test_file(
+                &tmp_dir,
+                &format!("src/Main{}.java", i),
+                &format!("R.string.used_{};", i),
+            )?;
+            strings.push_str(&format!(r#"<string name="used_{}">{}</string>"#, i, i));
+        }
+        strings.push_str("</resources>");
+        write_test_file(&tmp_dir, "res/values/strings.xml", &strings)?;
+
+        // However many threads walk the roots, they find the same things.
+        let mut indexed = Vec::new();
+        for threads in [1, 4] {
+            let mut indexer = Indexer::new(
+                tmp_dir.path().join("src"),
+                tmp_dir.path().join("res"),
+                None,
+                None,
+            )?;
+            let index = indexer.threads(threads).index()?;
+            let mut files: Vec<&str> = index.files().iter().map(|f| f.path()).collect();
+            files.sort();
+            let mut unused: Vec<&str> = index
+                .unused_strings()
+                .into_iter()
+                .map(|s| index.name(s))
+                .collect();
+            unused.sort();
+            indexed.push(format!("{:?} {:?}", files, unused));
+        }
+        assert_eq!(indexed[0], indexed[1]);
+        assert!(indexed[0].ends_with(r#"["unused"]"#));
+        assert_eq!(indexed[0].matches(".java").count(), 20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_walk_parallel() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let mut expected = Vec::new();
+        for dir in 0..10 {
+            for file in 0..50 {
+                let path = write_test_file(&tmp_dir, &format!("{}/{}.txt", dir, file), "")?;
+                if file % 2 == 0 {
+                    expected.push(path.display().to_string());
+                }
+            }
+        }
+        expected.sort();
+
+        // Every thread's results make it out, once.
+        let mut builder = WalkBuilder::new(tmp_dir.path());
+        builder.threads(8);
+        let progress = ProgressMode::None.start("files");
+        let diagnostics = Diagnostics::default();
+        let mut walked: Vec<String> = walk_parallel(builder, &progress, &diagnostics, |path| {
+            let even = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<usize>().ok())
+                .is_some_and(|n| n % 2 == 0);
+            even.then(|| ResourceFile::new(path.display().to_string(), Vec::new(), Vec::new()))
+        })
+        .into_iter()
+        .map(|file| file.path)
+        .collect();
+        walked.sort();
+        assert_eq!(walked, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_keep_annotations() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let file = write_test_file(
+            &tmp_dir,
+            "strings.xml",
+            r#"<resources xmlns:xdel="http://example.com/xdel">
+                <!-- xdel:keep -->
+                <string name="commented">A</string>
+                <string name="attributed" xdel:keep="true">B</string>
+                <string name="plain">C</string>
+                <!-- Just a comment -->
+                <string name="other_comment">D</string>
+            </resources>"#,
+        )?;
+
+        let result = Indexer::index_xml_file(&file, false)?;
+
+        let kept: Vec<&str> = result
+            .string_definitions
+            .iter()
+            .filter(|definition| definition.keep)
+            .map(|definition| definition.name.as_str())
+            .collect();
+        assert_eq!(kept, vec!["commented", "attributed"]);
+        assert_eq!(ResourceIndex::new(vec![result]).unused_strings().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_cdata_values() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        let file = write_test_file(
+            &tmp_dir,
+            "strings.xml",
+            r#"<resources>
+                <string name="rich"><![CDATA[<b>Bold</b> & <a href="@string/link">it</a>]]></string>
+                <string name="mixed">Hi <![CDATA[<i>there</i>]]> &amp; bye</string>
+                <string name="split"><![CDATA[a ]]]]><![CDATA[> b]]></string>
+            </resources>"#,
+        )?;
+
+        let result = Indexer::index_xml_file(&file, false)?;
+        let values: Vec<(&str, &str)> = result
+            .string_definitions
+            .iter()
+            .map(|definition| (definition.name.as_str(), definition.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("rich", r#"<b>Bold</b> & <a href="@string/link">it</a>"#),
+                ("mixed", "Hi <i>there</i> & bye"),
+                ("split", "a ]]> b"),
+            ]
+        );
+        assert_eq!(result.string_usages, vec!["link"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_skips_generated_files() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="app">App</string></resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/build/generated/Gen.java",
+            "class Gen { int a = R.string.app; }",
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/com/example/R.java",
+            "class R { int a = R.string.app; }",
         )?;
 
-        let res_dir = tmp_dir.path().join("res");
+        let mut indexer = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?;
+        assert_eq!(indexer.index()?.unused_strings().len(), 1);
+
+        indexer.include_generated(true);
+        assert!(indexer.index()?.unused_strings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_test_source_sets() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
         write_test_file(
             &tmp_dir,
-            "res/strings.xml",
-            r#"<resources><string name="some_app" value="My App" /></resources>"#,
+            "res/values/strings.xml",
+            r#"<resources><string name="app">App</string><string name="espresso">E</string><string name="unit">U</string></resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/main/java/Main.java",
+            "class Main { int a = R.string.app; }",
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/androidTest/java/MainTest.java",
+            "class MainTest { int a = R.string.app; int b = R.string.espresso; }",
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/testDebug/java/UnitTest.java",
+            "class UnitTest { int a = R.string.unit; }",
         )?;
 
-        let src_dir = tmp_dir.path().join("src");
-        std::fs::create_dir_all(&src_dir)?;
-        let indexer = Indexer::new(src_dir, res_dir, Some(manifest_dir), None)?;
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let mut test_only: Vec<&str> = index
+            .test_only_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        test_only.sort();
+        assert_eq!(test_only, vec!["espresso", "unit"]);
+        assert!(index.unused_strings().is_empty());
+        assert_eq!(
+            SourceSet::of(Path::new("app/src/testing/Foo.java")),
+            SourceSet::Main
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_public_strings() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="api">A</string><string name="grouped">G</string><string name="other">O</string></resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/public.xml",
+            r#"<resources><public type="string" name="api"/><public type="drawable" name="other"/><public-group type="string" first-id="0x7f0a0000"><public name="grouped"/></public-group></resources>"#,
+        )?;
+        write_test_file(&tmp_dir, "src/Main.java", "class Main {}")?;
+
+        let mut index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let unused = |index: &ResourceIndex| {
+            let mut names: Vec<String> = index
+                .unused_strings()
+                .into_iter()
+                .map(|symbol| index.name(symbol).to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(unused(&index), vec!["other"]);
+        index.ignore_public();
+        assert_eq!(unused(&index), vec!["api", "grouped", "other"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_latin1_files() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="used">U</string><string name="unused">N</string></resources>"#,
+        )?;
+        let mut strings = b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".to_vec();
+        strings.extend(b"<resources><string name=\"caf\xe9\">Caf\xe9</string></resources>");
+        std::fs::write(tmp_dir.path().join("res/values/latin1.xml"), strings)?;
+        write_test_file(&tmp_dir, "src/Main.java", "class Main {}")?;
+        std::fs::write(
+            tmp_dir.path().join("src/Latin1.java"),
+            b"// \xa9 2010\nclass Latin1 { String s = \"\xe9\" + getString(R.string.used); }",
+        )?;
+
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let mut unused: Vec<&str> = index
+            .unused_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        unused.sort();
+        assert_eq!(unused, vec!["caf\u{e9}", "unused"]);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_index_follow_symlinks() -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "modules/lib/res/values/strings.xml",
+            r#"<resources><string name="lib">L</string></resources>"#,
+        )?;
+        write_test_file(&tmp_dir, "src/Main.java", "class Main {}")?;
+        let res_dir = tmp_dir.path().join("res");
+        std::fs::create_dir_all(&res_dir)?;
+        symlink(tmp_dir.path().join("modules/lib/res"), res_dir.join("lib"))?;
+        symlink(
+            tmp_dir.path().join("modules/lib/res"),
+            res_dir.join("lib_too"),
+        )?;
+        symlink(&res_dir, res_dir.join("cycle"))?;
 
+        let mut indexer = Indexer::new(tmp_dir.path().join("src"), res_dir, None, None)?;
+        assert!(indexer.index()?.defined_strings().is_empty());
+
+        indexer.follow_symlinks(true);
+        let index = indexer.index()?;
+        let paths: Vec<&str> = index
+            .files()
+            .iter()
+            .filter(|file| !file.string_definitions().is_empty())
+            .map(|file| file.path())
+            .collect();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].ends_with("res/lib/values/strings.xml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_skips_binary_and_large_files() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="used">U</string><string name="blob">B</string></resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/large.xml",
+            &format!(
+                r#"<resources><string name="large">{}</string></resources>"#,
+                "x".repeat(2048)
+            ),
+        )?;
+        write_test_file(&tmp_dir, "src/Main.java", "R.string.used;")?;
+        std::fs::write(
+            tmp_dir.path().join("src/Blob.java"),
+            b"R.string.blob;\0\0\x01\x02",
+        )?;
+
+        let mut indexer = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?;
+        indexer.max_filesize(Some(1024));
         let index = indexer.index()?;
+        let mut defined: Vec<&str> = index
+            .defined_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        defined.sort();
+        assert_eq!(defined, vec!["blob", "used"]);
+        let unused: Vec<&str> = index
+            .unused_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        assert_eq!(unused, vec!["blob"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_ignored_and_hidden_files() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="a">A</string><string name="b">B</string></resources>"#,
+        )?;
+        write_test_file(&tmp_dir, "src/.ignore", "scratch/\n")?;
+        write_test_file(
+            &tmp_dir,
+            "src/scratch/I.java",
+            "class I { int a = R.string.a; }",
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/.hidden/H.java",
+            "class H { int b = R.string.b; }",
+        )?;
+
+        let mut indexer = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?;
+        assert_eq!(indexer.index()?.unused_strings().len(), 2);
+
+        indexer.no_ignore(true);
+        assert_eq!(indexer.index()?.unused_strings().len(), 1);
+
+        indexer.hidden(true);
+        assert!(indexer.index()?.unused_strings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_vcs_only() -> Result<()> {
+        let tmp_dir = TempDir::new("index")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="a">A</string></resources>"#,
+        )?;
+        write_test_file(&tmp_dir, "src/Tracked.java", "class T {}")?;
+        write_test_file(
+            &tmp_dir,
+            "src/scratch/Scratch.java",
+            "class S { int a = R.string.a; }",
+        )?;
+        for args in &[&["init", "-q"][..], &["add", "res", "src/Tracked.java"]] {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(tmp_dir.path())
+                .args(*args)
+                .status()?;
+            assert!(status.success());
+        }
+
+        let mut indexer = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?;
+        assert!(indexer.index()?.unused_strings().is_empty());
+
+        indexer.vcs_only(true);
+        assert_eq!(indexer.index()?.unused_strings().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_drawables() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        for path in [
+            "res/drawable-hdpi/logo.png",
+            "res/drawable-xhdpi/logo.png",
+            "res/drawable-hdpi/old.9.png",
+            "res/drawable-xhdpi/old.9.png",
+            "res/mipmap-hdpi/launcher.png",
+        ] {
+            write_test_file(&tmp_dir, path, "\u{89}PNG\0")?;
+        }
+        write_test_file(&tmp_dir, "res/drawable/bg.xml", "<shape/>")?;
+        write_test_file(&tmp_dir, "res/drawable/unused_shape.xml", "<shape/>")?;
+        write_test_file(
+            &tmp_dir,
+            "res/layout/main.xml",
+            r#"<FrameLayout xmlns:android="http://schemas.android.com/apk/res/android"
+    android:background="@drawable/bg"/>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/Main.kt",
+            "val logo = R.drawable.logo // R.drawable.old",
+        )?;
+
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let res = tmp_dir.path().join("res");
+        let path = |path: &str| res.join(path).to_str().unwrap().to_string();
+        assert_eq!(
+            index.unused_resources(ResourceKind::Drawable),
+            vec![
+                (
+                    "old".to_string(),
+                    vec![
+                        path("drawable-hdpi/old.9.png"),
+                        path("drawable-xhdpi/old.9.png")
+                    ]
+                ),
+                (
+                    "unused_shape".to_string(),
+                    vec![path("drawable/unused_shape.xml")]
+                ),
+            ]
+        );
+        assert!(index.unused_strings().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_colors_and_dimens() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/colors.xml",
+            r#"<resources>
+    <color name="primary">#000</color>
+    <color name="pressed">#111</color>
+    <color name="text">#222</color>
+    <color name="unused_color">#333</color>
+    <item name="alias" type="color">@color/primary</item>
+    <dimen name="margin">4dp</dimen>
+    <dimen name="unused_dimen">8dp</dimen>
+</resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/styles.xml",
+            r#"<resources><style name="Text"><item name="android:textColor">@color/text</item></style></resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/color/selector.xml",
+            r#"<selector xmlns:android="http://schemas.android.com/apk/res/android">
+    <item android:color="@color/pressed"/>
+</selector>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "src/Main.java",
+            "int m = R.dimen.margin; int c = R.color.selector;",
+        )?;
+
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let names = |kind| -> Vec<String> {
+            index
+                .unused_resources(kind)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        assert_eq!(names(ResourceKind::Color), vec!["alias", "unused_color"]);
+        assert_eq!(names(ResourceKind::Dimen), vec!["unused_dimen"]);
+        let colors = tmp_dir.path().join("res/values/colors.xml");
+        let unused_color = index
+            .files()
+            .iter()
+            .find(|file| Path::new(file.path()) == colors)
+            .and_then(|file| {
+                file.resource_definitions()
+                    .iter()
+                    .find(|d| index.name(d.name) == "unused_color")
+            })
+            .unwrap();
+        assert_eq!(unused_color.line, 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_styles() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/styles.xml",
+            r#"<resources>
+    <style name="AppTheme" parent="Theme.MaterialComponents"/>
+    <style name="Base" parent=""/>
+    <style name="Base.Card"/>
+    <style name="Card" parent="@style/Base.Card"/>
+    <style name="Orphan"/>
+    <style name="Orphan.Child" parent="@style/Card"/>
+    <style name="Text"/>
+    <style name="Text.Big"/>
+</resources>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/AndroidManifest.xml",
+            r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android">
+    <application android:theme="@style/AppTheme"/>
+</manifest>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/layout/main.xml",
+            r#"<TextView style="@style/Card"/>"#,
+        )?;
+        write_test_file(&tmp_dir, "src/Main.java", "int s = R.style.Text_Big;")?;
+
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let unused: Vec<String> = index
+            .unused_resources(ResourceKind::Style)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(unused, vec!["Orphan", "Orphan.Child"]);
+        assert_eq!(style_parent("A.B", None).as_deref(), Some("A"));
+        assert_eq!(style_parent("A.B", Some("@android:style/Theme")), None);
+        assert_eq!(style_parent("A", Some("style/B")).as_deref(), Some("B"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_navigation_graphs() -> Result<()> {
+        let tmp_dir = TempDir::new("test")?;
+        write_test_file(
+            &tmp_dir,
+            "res/navigation/main.xml",
+            r#"<navigation xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:app="http://schemas.android.com/apk/res-auto"
+    xmlns:tools="http://schemas.android.com/tools"
+    android:id="@+id/main" app:startDestination="@id/home">
+    <fragment android:id="@+id/home" android:label="@string/home_title"
+        tools:layout="@layout/fragment_home">
+        <action android:id="@+id/to_detail" app:destination="@id/detail"/>
+    </fragment>
+    <fragment android:id="@+id/detail">
+        <argument android:name="title" android:defaultValue="@string/default_title"/>
+    </fragment>
+    <fragment android:id="@+id/linked">
+        <deepLink app:uri="example.com/linked"/>
+    </fragment>
+    <fragment android:id="@+id/navigated_in_code"/>
+    <fragment android:id="@+id/orphan" tools:layout="@layout/fragment_orphan"/>
+</navigation>"#,
+        )?;
+        write_test_file(
+            &tmp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="home_title">H</string><string name="default_title">T</string></resources>"#,
+        )?;
+        for layout in ["fragment_home", "fragment_orphan", "fragment_unused"] {
+            write_test_file(&tmp_dir, &format!("res/layout/{}.xml", layout), "<View/>")?;
+        }
+        write_test_file(
+            &tmp_dir,
+            "src/Main.kt",
+            "navController.navigate(R.id.navigated_in_code)",
+        )?;
+
+        let index = Indexer::new(
+            tmp_dir.path().join("src"),
+            tmp_dir.path().join("res"),
+            None,
+            None,
+        )?
+        .index()?;
+        let names = |kind| -> Vec<String> {
+            index
+                .unused_resources(kind)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        assert!(index.unused_strings().is_empty());
+        assert_eq!(names(ResourceKind::Destination), vec!["orphan"]);
+        assert_eq!(names(ResourceKind::Layout), vec!["fragment_unused"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_shards() -> Result<()> {
+        let temp_dir = TempDir::new("merge")?;
+        write_test_file(
+            &temp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="feed">F</string><string name="old">O</string></resources>"#,
+        )?;
+        write_test_file(&temp_dir, "app/Main.java", "R.string.title;")?;
+        write_test_file(&temp_dir, "feed/Feed.java", "R.string.feed;")?;
+        let res_dir = temp_dir.path().join("res");
+        let shard = |module: &str| -> Result<PathBuf> {
+            let indexer = Indexer::new(temp_dir.path().join(module), res_dir.clone(), None, None)?;
+            let path = temp_dir.path().join(format!("{}.bin", module));
+            indexer.write_index(&indexer.index()?, &path)?;
+            Ok(path)
+        };
+        let shards = vec![shard("app")?, shard("feed")?];
+
+        let indexer = Indexer::new(temp_dir.path().join("app"), res_dir.clone(), None, None)?;
+        let merged = temp_dir.path().join("full.bin");
+        // Both shards hold strings.xml; it's kept once.
+        assert_eq!(indexer.merge(&shards, &merged)?, 3);
+        let index = indexer.read_index(&merged)?;
+        let unused: Vec<&str> = index
+            .unused_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        assert_eq!(unused, vec!["old"]);
+        assert!(indexer
+            .read_index(&temp_dir.path().join("missing.bin"))
+            .is_err());
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_update_files() -> Result<()> {
+        let temp_dir = TempDir::new("update")?;
+        write_test_file(
+            &temp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="old">O</string></resources>"#,
+        )?;
+        write_test_file(&temp_dir, "src/Main.java", "R.string.title;")?;
+        write_test_file(&temp_dir, "src/Feed.java", "R.string.old;")?;
+        let indexer = Indexer::new(
+            temp_dir.path().join("src"),
+            temp_dir.path().join("res"),
+            None,
+            Some(temp_dir.path().join("cache")),
+        )?;
+        let unused = || -> Result<Vec<String>> {
+            let index = indexer.deserialize()?;
+            let mut names: Vec<String> = index
+                .unused_strings()
+                .into_iter()
+                .map(|symbol| index.name(symbol).to_string())
+                .collect();
+            names.sort();
+            Ok(names)
+        };
+        let feed = temp_dir.path().join("src/Feed.java");
+        assert!(indexer.update_files(std::slice::from_ref(&feed)).is_err());
+
+        indexer.serialize(&indexer.index()?)?;
+        assert!(unused()?.is_empty());
+
+        write_test_file(
+            &temp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="old">O</string><string name="new">N</string></resources>"#,
+        )?;
+        std::fs::remove_file(&feed)?;
+        let strings = temp_dir.path().join("src/../res/values/strings.xml");
+        assert_eq!(indexer.update_files(&[strings, feed])?, (1, 1));
+        assert_eq!(unused()?, vec!["new", "old"]);
+        assert_eq!(indexer.deserialize()?.files().len(), 2);
+
+        write_test_file(&temp_dir, "notes.txt", "R.string.new")?;
+        assert!(indexer
+            .update_files(&[temp_dir.path().join("notes.txt")])
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_status() -> Result<()> {
+        let temp_dir = TempDir::new("status")?;
+        write_test_file(
+            &temp_dir,
+            "res/values/strings.xml",
+            r#"<resources><string name="title">T</string></resources>"#,
+        )?;
+        write_test_file(&temp_dir, "src/Main.java", "R.string.title;")?;
+        let indexer = Indexer::new(
+            temp_dir.path().join("src"),
+            temp_dir.path().join("res"),
+            None,
+            Some(temp_dir.path().join("cache")),
+        )?;
+        assert!(indexer.cache_status()?.is_none());
+
+        indexer.serialize(&indexer.index()?)?;
+        let status = indexer.cache_status()?.unwrap();
+        assert_eq!(status.path, indexer.cache_file());
+        assert_eq!(status.files, 2);
+        assert_eq!(status.size, indexer.cache_file().metadata()?.len());
+        let roots: Vec<&str> = status.roots.iter().map(|(name, _)| *name).collect();
+        assert_eq!(roots, vec!["java", "res"]);
+        assert!(status.stamp.is_some() && status.staleness.is_none());
+        assert!(status.built.elapsed()?.as_secs() < 60);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_portable_paths() -> Result<()> {
+        let temp_dir = TempDir::new("portable")?;
+        let checkout = |name: &str| -> Result<Indexer> {
+            write_test_file(
+                &temp_dir,
+                &format!("{}/res/values/strings.xml", name),
+                r#"<resources><string name="title">T</string></resources>"#,
+            )?;
+            write_test_file(&temp_dir, &format!("{}/src/Main.java", name), "")?;
+            let root = temp_dir.path().join(name);
+            Indexer::new(root.join("src"), root.join("res"), Some(root), None)
+        };
+        let ci = checkout("ci")?;
+        let local = checkout("local")?;
+
+        let roots = ci.path_roots();
+        let strings = temp_dir.path().join("ci/res/values/strings.xml");
+        let stored = roots.portable(strings.to_str().unwrap());
+        assert_eq!(stored, "$res/values/strings.xml");
+        assert_eq!(roots.local(&stored), strings.to_str().unwrap());
+        assert_eq!(roots.portable("backend/feed"), "backend/feed");
+        assert_eq!(roots.local("backend/feed"), "backend/feed");
+
+        // An index built in one checkout resolves to the files of another.
+        let file = temp_dir.path().join("index.bin");
+        ci.write_index(&ci.index()?, &file)?;
+        let index = local.read_index(&file)?;
+        let mut paths: Vec<&str> = index.files().iter().map(|file| file.path()).collect();
+        paths.sort();
+        let expected = |path: &str| temp_dir.path().join(path).to_str().unwrap().to_string();
         assert_eq!(
-            index.defined_strings().contains(&"some_app".to_string()),
-            true
+            paths,
+            vec![
+                expected("local/res/values/strings.xml"),
+                expected("local/src/Main.java")
+            ]
         );
-        assert_eq!(index.used_strings().contains(&"some_app".to_string()), true);
-        assert_eq!(index.unused_strings().is_empty(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_shard() -> Result<()> {
+        let temp_dir = TempDir::new("shard")?;
+        write_test_file(
+            &temp_dir,
+            "app/res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="feed">F</string><string name="old">O</string></resources>"#,
+        )?;
+        write_test_file(&temp_dir, "app/src/Main.java", "R.string.title;")?;
+        write_test_file(&temp_dir, "feed/src/Feed.java", "R.string.feed;")?;
+        let root = temp_dir.path().to_path_buf();
+        let shard = |globs: &[&str]| -> Result<PathBuf> {
+            let mut indexer = Indexer::new(root.clone(), root.clone(), None, None)?;
+            let globs: Vec<String> = globs.iter().map(|glob| glob.to_string()).collect();
+            let index = indexer.shard(&globs)?.index()?;
+            let path = temp_dir.path().join(format!("{}.bin", index.files().len()));
+            indexer.write_index(&index, &path)?;
+            Ok(path)
+        };
+        // A directory takes in everything under it, as does a ** glob.
+        let shards = vec![shard(&["app"])?, shard(&["feed/**/*.java"])?];
+        assert!(shards[0].ends_with("2.bin") && shards[1].ends_with("1.bin"));
+
+        let indexer = Indexer::new(root.clone(), root.clone(), None, None)?;
+        let merged = temp_dir.path().join("full.bin");
+        assert_eq!(indexer.merge(&shards, &merged)?, 3);
+        let index = indexer.read_index(&merged)?;
+        let unused: Vec<&str> = index
+            .unused_strings()
+            .into_iter()
+            .map(|symbol| index.name(symbol))
+            .collect();
+        assert_eq!(unused, vec!["old"]);
 
         Ok(())
     }
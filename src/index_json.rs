@@ -0,0 +1,176 @@
+use crate::index::{ResourceFile, ResourceIndex};
+use crate::mapped::FORMAT_VERSION;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// The whole index as json: every file, with the strings and other resources
+/// it defines (with their values and lines) and uses, by name.
+#[derive(Serialize, Deserialize)]
+pub struct IndexJson {
+    /// The index format version the export was written by. Fragments from
+    /// elsewhere can leave it out.
+    #[serde(default)]
+    pub version: u32,
+    pub files: Vec<ResourceFile<String>>,
+}
+
+fn write(files: Vec<ResourceFile<String>>, out: &mut dyn Write) -> Result<()> {
+    serde_json::to_writer_pretty(
+        &mut *out,
+        &IndexJson {
+            version: FORMAT_VERSION,
+            files,
+        },
+    )?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// Writes every file in the index, sorted by path. Returns the number of
+/// files written.
+pub fn export(index: &ResourceIndex, out: &mut dyn Write) -> Result<usize> {
+    let mut files = index.named_files();
+    files.sort_by(|a, b| a.path().cmp(b.path()));
+    let count = files.len();
+    write(files, out)?;
+
+    Ok(count)
+}
+
+/// Reads an index shaped like `export` writes it. Each file needs only a
+/// path, so a fragment listing the strings a backend service sends to
+/// clients can be just `{"files": [{"path": "...", "string_usages": [...]}]}`.
+pub fn read(path: &Path) -> Result<IndexJson> {
+    let file = File::open(path).map_err(|e| anyhow!("Unable to open {}: {}", path.display(), e))?;
+    let index: IndexJson = serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("Unable to read {} as an index", path.display()))?;
+    if index.version > FORMAT_VERSION {
+        return Err(anyhow!(
+            "{} was written by a newer version (index format {}, expected at most {})",
+            path.display(),
+            index.version,
+            FORMAT_VERSION
+        ));
+    }
+
+    Ok(index)
+}
+
+/// Writes `files` to `path` as an index fragment.
+pub fn write_file(files: Vec<ResourceFile<String>>, path: &Path) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(files, &mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use serde_json::Value;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export() -> Result<()> {
+        let tmp_dir = TempDir::new("index_json")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("drawable"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            "<resources>\n<string name=\"title\">Title</string>\n</resources>",
+        )?;
+        fs::write(res_dir.join("drawable/icon.png"), "")?;
+        fs::write(
+            src_dir.join("Main.java"),
+            "R.string.title; R.drawable.icon;",
+        )?;
+        let index = Indexer::new(src_dir, res_dir.clone(), None, None)?.index()?;
+
+        let mut out = Vec::new();
+        assert_eq!(export(&index, &mut out)?, 3);
+        let json: Value = serde_json::from_slice(&out)?;
+        assert_eq!(json["version"], FORMAT_VERSION);
+        let files = json["files"].as_array().unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f["path"].as_str().unwrap()).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+
+        let file = |suffix: &str| {
+            files
+                .iter()
+                .find(|f| f["path"].as_str().unwrap().ends_with(suffix))
+                .unwrap()
+        };
+        let strings = file("strings.xml");
+        assert_eq!(strings["string_definitions"][0]["name"], "title");
+        assert_eq!(strings["string_definitions"][0]["value"], "Title");
+        assert_eq!(strings["string_definitions"][0]["line"], 2);
+        let main = file("Main.java");
+        assert_eq!(main["string_usages"][0], "title");
+        assert_eq!(main["resource_usages"][0]["name"], "icon");
+        assert_eq!(
+            file("icon.png")["resource_definitions"][0]["kind"],
+            "Drawable"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let tmp_dir = TempDir::new("index_json")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="title">T</string><string name="promo">P</string></resources>"#,
+        )?;
+        fs::write(src_dir.join("Main.java"), "R.string.title;")?;
+        let indexer = Indexer::new(src_dir, res_dir, None, Some(tmp_dir.path().join("cache")))?;
+        indexer.serialize(&indexer.index()?)?;
+        let unused = |index: &ResourceIndex| -> Vec<String> {
+            let mut names: Vec<String> = index
+                .unused_strings()
+                .into_iter()
+                .map(|symbol| index.name(symbol).to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(unused(&indexer.deserialize()?), vec!["promo"]);
+
+        let fragment = tmp_dir.path().join("backend.json");
+        fs::write(
+            &fragment,
+            r#"{"files": [{"path": "backend/feed", "string_usages": ["promo"]}]}"#,
+        )?;
+        assert_eq!(indexer.import(&fragment)?, 1);
+        assert!(unused(&indexer.deserialize()?).is_empty());
+        // Imports are merged into indexes built later too.
+        assert!(unused(&indexer.index()?).is_empty());
+
+        // Importing a file again replaces it.
+        fs::write(&fragment, r#"{"files": [{"path": "backend/feed"}]}"#)?;
+        indexer.import(&fragment)?;
+        assert_eq!(unused(&indexer.deserialize()?), vec!["promo"]);
+        assert_eq!(unused(&indexer.index()?), vec!["promo"]);
+
+        fs::write(&fragment, r#"{"version": 1000, "files": []}"#)?;
+        assert!(indexer.import(&fragment).is_err());
+
+        Ok(())
+    }
+}
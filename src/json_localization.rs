@@ -0,0 +1,225 @@
+use crate::index::{self, ResourceIndex};
+use crate::xliff::{self, Imported};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Strings by locale, then name. Locales are Android qualifiers (fr, pt-rBR)
+/// or "default" for the values directory.
+type Strings = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Writes every string in every locale as a flat JSON object of
+/// `{locale: {name: value}}`, the shape localization platforms take. Where
+/// several files in one locale define a name, the first by path wins. Values
+/// directories for other configurations, like values-night, are left out.
+/// Returns the number of strings written.
+pub fn export(index: &ResourceIndex, out: &mut dyn Write) -> Result<usize> {
+    let mut files: Vec<_> = index
+        .files()
+        .iter()
+        .filter(|file| {
+            let locale = index::locale_of(file.path());
+            locale == "default" || index::language_of(file.path()) == Some(locale)
+        })
+        .collect();
+    files.sort_by_key(|file| file.path());
+
+    let mut strings = Strings::new();
+    for file in files {
+        let locale = strings
+            .entry(index::locale_of(file.path()).to_string())
+            .or_default();
+        for definition in file.string_definitions() {
+            locale
+                .entry(index.name(definition.name).to_string())
+                .or_insert_with(|| definition.value.clone());
+        }
+    }
+    serde_json::to_writer_pretty(&mut *out, &strings)?;
+    writeln!(out)?;
+
+    Ok(strings.values().map(BTreeMap::len).sum())
+}
+
+/// What importing did.
+pub struct Import {
+    pub files: Vec<Imported>,
+    /// Names with no default definition to put the translation next to.
+    pub skipped: Vec<String>,
+    /// How many default strings were left alone, without `overwrite_default`.
+    pub defaults_skipped: usize,
+}
+
+/// Writes the strings in the JSON file at `json`, shaped as `export` writes
+/// it, into the values files of each locale. Locales may also be language
+/// tags (pt-BR). A string goes into the values directory beside the default
+/// values file defining it; strings without a default definition are
+/// skipped. The default strings themselves are only written with
+/// `overwrite_default`, since they're the source the rest are translated
+/// from. Locales that aren't a locale qualifier or language tag are refused.
+pub fn import(index: &ResourceIndex, json: &Path, overwrite_default: bool) -> Result<Import> {
+    let strings: Strings = serde_json::from_reader(BufReader::new(File::open(json)?))?;
+
+    // The default values file of each name, the first by path.
+    let mut defaults: HashMap<&str, &Path> = HashMap::new();
+    let mut files: Vec<_> = index
+        .files()
+        .iter()
+        .filter(|file| index::locale_of(file.path()) == "default")
+        .collect();
+    files.sort_by_key(|file| file.path());
+    for file in files {
+        for definition in file.string_definitions() {
+            defaults
+                .entry(index.name(definition.name))
+                .or_insert_with(|| Path::new(file.path()));
+        }
+    }
+
+    let mut by_path: BTreeMap<PathBuf, Vec<(String, String)>> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    let mut defaults_skipped = 0;
+    for (locale, values) in strings {
+        let locale = if locale == "default" {
+            if !overwrite_default {
+                defaults_skipped += values.len();
+                continue;
+            }
+            locale
+        } else {
+            android_locale(&locale)?
+        };
+        for (name, value) in values {
+            match defaults.get(name.as_str()) {
+                Some(default) => by_path
+                    .entry(translation_path(default, &locale))
+                    .or_default()
+                    .push((name, value)),
+                None => skipped.push(name),
+            }
+        }
+    }
+    skipped.sort();
+    skipped.dedup();
+
+    let files = by_path
+        .into_iter()
+        .map(|(path, units)| xliff::write_translations(path, &units))
+        .collect::<Result<_>>()?;
+
+    Ok(Import {
+        files,
+        skipped,
+        defaults_skipped,
+    })
+}
+
+/// `locale`, a locale qualifier or language tag, as the qualifier of its
+/// values directory. Anything else, like a path or a configuration that
+/// isn't a locale, is refused rather than written to.
+fn android_locale(locale: &str) -> Result<String> {
+    let android = xliff::to_android_locale(locale);
+    let is_locale = android
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '+')
+        && index::language_of(&format!("values-{}/strings.xml", android)) == Some(&android);
+    if !is_locale {
+        return Err(anyhow!("{:?} isn't a locale", locale));
+    }
+    Ok(android)
+}
+
+/// The file beside the default values file `default` for `locale`, e.g.
+/// values-fr/strings.xml for values/strings.xml.
+pub fn translation_path(default: &Path, locale: &str) -> PathBuf {
+    if locale == "default" {
+        return default.to_path_buf();
+    }
+    let res_dir = default
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new(""));
+    let values = format!("values-{}", xliff::to_android_locale(locale));
+    res_dir
+        .join(values)
+        .join(default.file_name().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export_and_import() -> Result<()> {
+        let tmp_dir = TempDir::new("json_localization")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-fr"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="hello">Hello &amp; welcome</string><string name="bye">Bye</string></resources>"#,
+        )?;
+        fs::write(
+            res_dir.join("values-fr/strings.xml"),
+            r#"<resources><string name="hello">Bonjour</string></resources>"#,
+        )?;
+        fs::create_dir_all(res_dir.join("values-night"))?;
+        fs::write(
+            res_dir.join("values-night/strings.xml"),
+            r#"<resources><string name="hello">Good evening</string></resources>"#,
+        )?;
+        let index = Indexer::new(src_dir, res_dir.clone(), None, None)?.index()?;
+
+        let mut out = Vec::new();
+        assert_eq!(export(&index, &mut out)?, 3);
+        let exported: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(
+            exported,
+            serde_json::json!({
+                "default": {"bye": "Bye", "hello": "Hello & welcome"},
+                "fr": {"hello": "Bonjour"},
+            })
+        );
+
+        let json = tmp_dir.path().join("strings.json");
+        fs::write(
+            &json,
+            r#"{"fr": {"bye": "Au revoir", "hello": "Salut"}, "pt-BR": {"bye": "Tchau", "gone": "?"}}"#,
+        )?;
+        let imported = import(&index, &json, false)?;
+        assert_eq!(imported.skipped, vec!["gone"]);
+        assert_eq!(imported.files.len(), 2);
+        assert_eq!(
+            fs::read_to_string(res_dir.join("values-fr/strings.xml"))?,
+            r#"<resources><string name="hello">Salut</string>
+    <string name="bye">Au revoir</string>
+</resources>"#
+        );
+        assert!(
+            fs::read_to_string(res_dir.join("values-pt-rBR/strings.xml"))?
+                .contains(r#"<string name="bye">Tchau</string>"#)
+        );
+
+        // The source strings only change when asked to.
+        fs::write(&json, r#"{"default": {"bye": "Goodbye"}}"#)?;
+        assert_eq!(import(&index, &json, false)?.defaults_skipped, 1);
+        assert!(fs::read_to_string(res_dir.join("values/strings.xml"))?.contains(">Bye<"));
+        import(&index, &json, true)?;
+        assert!(fs::read_to_string(res_dir.join("values/strings.xml"))?.contains(">Goodbye<"));
+
+        for locale in ["fr/../../../x", "..", "night", "sw600dp"] {
+            fs::write(&json, format!(r#"{{"{}": {{"bye": "?"}}}}"#, locale))?;
+            assert!(import(&index, &json, false).is_err(), "{}", locale);
+        }
+        assert!(!tmp_dir.path().join("x").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The name of the file listing strings that should never be reported or removed.
+pub const KEEP_FILE: &str = ".xdelignore";
+
+/// Strings that should never be reported or removed, read from a .xdelignore
+/// file. Each line holds a string name, a glob like experiment_*, or a regex
+/// between slashes like /^server_.*_key$/. Blank lines and lines starting
+/// with # are ignored.
+#[derive(Debug)]
+pub struct KeepList {
+    names: HashSet<String>,
+    prefixes: Vec<String>,
+    globs: GlobSet,
+    regexes: Vec<Regex>,
+}
+
+impl Default for KeepList {
+    fn default() -> Self {
+        KeepList {
+            names: HashSet::new(),
+            prefixes: Vec::new(),
+            globs: GlobSet::empty(),
+            regexes: Vec::new(),
+        }
+    }
+}
+
+impl KeepList {
+    /// Reads the nearest .xdelignore in the given directory or its parents,
+    /// or returns an empty list if there isn't one.
+    pub fn find(dir: &Path) -> Result<KeepList> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for ancestor in dir.ancestors() {
+            let path = ancestor.join(KEEP_FILE);
+            if path.is_file() {
+                let content = fs::read_to_string(&path)?;
+                return KeepList::parse(&content)
+                    .with_context(|| format!("Failed to read {}", path.display()));
+            }
+        }
+
+        Ok(KeepList::default())
+    }
+
+    pub fn parse(content: &str) -> Result<KeepList> {
+        let mut names = HashSet::new();
+        let mut globs = GlobSetBuilder::new();
+        let mut regexes = Vec::new();
+
+        for (line_number, line) in (1..).zip(content.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let context = || format!("Invalid entry on line {}", line_number);
+            if line.len() > 1 && line.starts_with('/') && line.ends_with('/') {
+                regexes.push(Regex::new(&line[1..line.len() - 1]).with_context(context)?);
+            } else if line.contains(['*', '?', '[']) {
+                globs.add(Glob::new(line).with_context(context)?);
+            } else {
+                names.insert(line.to_string());
+            }
+        }
+
+        Ok(KeepList {
+            names,
+            prefixes: Vec::new(),
+            globs: globs.build()?,
+            regexes,
+        })
+    }
+
+    /// Also keeps the given names, e.g. those used by external consumers.
+    pub fn extend(&mut self, names: impl IntoIterator<Item = String>) {
+        self.names.extend(names);
+    }
+
+    /// Also keeps every string starting with `prefix`, e.g. a family of
+    /// strings looked up by building their names at runtime. The same as an
+    /// entry like rating_* in .xdelignore.
+    pub fn keep_prefix(&mut self, prefix: &str) {
+        self.prefixes.push(prefix.to_string());
+    }
+
+    /// Whether the named string must be kept.
+    pub fn keeps(&self, name: &str) -> bool {
+        self.names.contains(name)
+            || self.prefixes.iter().any(|prefix| name.starts_with(prefix))
+            || self.globs.is_match(name)
+            || self.regexes.iter().any(|regex| regex.is_match(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_find_keep_list() -> Result<()> {
+        let tmp_dir = TempDir::new("art")?;
+        let res_dir = tmp_dir.path().join("app/res");
+        fs::create_dir_all(&res_dir)?;
+        let mut file = File::create(tmp_dir.path().join(KEEP_FILE))?;
+        writeln!(file, "# Fetched by key from the server")?;
+        writeln!(file, "server_title")?;
+        writeln!(file)?;
+        writeln!(file, "experiment_*")?;
+        writeln!(file, "/^ab_.*_variant$/")?;
+
+        let mut keep = KeepList::find(&res_dir)?;
+        assert!(keep.keeps("server_title"));
+        assert!(keep.keeps("experiment_banner"));
+        assert!(keep.keeps("ab_checkout_variant"));
+        assert!(!keep.keeps("server_subtitle"));
+        assert!(!keep.keeps("ab_checkout"));
+
+        keep.keep_prefix("rating_");
+        assert!(keep.keeps("rating_5"));
+        assert!(!keep.keeps("ratings"));
+
+        assert!(KeepList::parse("/(/").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+/// Where the lexer is in the source.
+enum Context {
+    /// Code, counting the braces opened since it started. Code inside a
+    /// Kotlin string template ends at its unmatched closing brace.
+    Code { braces: usize },
+    /// A string literal; raw ones are triple quoted.
+    Literal { raw: bool },
+}
+
+/// Blanks out the comments and string and character literals in Java or
+/// Kotlin source, so that only code is left to search. Line breaks are kept,
+/// so line numbers still match. Expressions in Kotlin string templates
+/// (`"${...}"`) are code, and are kept too.
+pub fn strip_comments_and_literals(source: &str, kotlin: bool) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let starts_with = |i: usize, prefix: &str| {
+        prefix
+            .chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+
+    let mut out = String::with_capacity(source.len());
+    let mut stack = vec![Context::Code { braces: 0 }];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let in_template = stack.len() > 1;
+        match stack.last_mut() {
+            Some(Context::Code { braces }) => {
+                if starts_with(i, "//") {
+                    while i < chars.len() && chars[i] != '\n' {
+                        out.push(' ');
+                        i += 1;
+                    }
+                    continue;
+                } else if starts_with(i, "/*") {
+                    // Kotlin block comments nest; Java ones don't.
+                    let mut depth = 0;
+                    while i < chars.len() {
+                        if starts_with(i, "/*") && (kotlin || depth == 0) {
+                            depth += 1;
+                        } else if starts_with(i, "*/") {
+                            depth -= 1;
+                        } else {
+                            out.push(blank(chars[i]));
+                            i += 1;
+                            continue;
+                        }
+                        out.push_str("  ");
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    continue;
+                } else if starts_with(i, "\"\"\"") {
+                    out.push_str("\"\"\"");
+                    i += 3;
+                    stack.push(Context::Literal { raw: true });
+                    continue;
+                } else if c == '"' {
+                    stack.push(Context::Literal { raw: false });
+                } else if c == '\'' {
+                    out.push(c);
+                    i += 1;
+                    while i < chars.len() && chars[i] != '\'' && chars[i] != '\n' {
+                        let escaped = if chars[i] == '\\' { 2 } else { 1 };
+                        for c in chars.iter().skip(i).take(escaped) {
+                            out.push(blank(*c));
+                        }
+                        i += escaped;
+                    }
+                    if chars.get(i) == Some(&'\'') {
+                        out.push('\'');
+                        i += 1;
+                    }
+                    continue;
+                } else if c == '{' {
+                    *braces += 1;
+                } else if c == '}' {
+                    if *braces == 0 && in_template {
+                        stack.pop();
+                    } else {
+                        *braces = braces.saturating_sub(1);
+                    }
+                }
+                out.push(c);
+            }
+            Some(Context::Literal { raw }) => {
+                let raw = *raw;
+                if raw && starts_with(i, "\"\"\"") {
+                    out.push_str("\"\"\"");
+                    i += 3;
+                    stack.pop();
+                    continue;
+                } else if !raw && (c == '"' || c == '\n') {
+                    // A line break ends an unterminated literal, so one stray
+                    // quote doesn't hide the rest of the file.
+                    stack.pop();
+                    out.push(c);
+                } else if kotlin && starts_with(i, "${") {
+                    out.push_str("${");
+                    i += 2;
+                    stack.push(Context::Code { braces: 0 });
+                    continue;
+                } else if !raw && c == '\\' {
+                    out.push(' ');
+                    if let Some(escaped) = chars.get(i + 1) {
+                        out.push(blank(*escaped));
+                    }
+                    i += 2;
+                    continue;
+                } else {
+                    out.push(blank(c));
+                }
+            }
+            None => break,
+        }
+        i += 1;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    fn usages(source: &str, kotlin: bool) -> Vec<String> {
+        let stripped = strip_comments_and_literals(source, kotlin);
+        assert_eq!(stripped.lines().count(), source.lines().count());
+        Regex::new(r"R\.string\.(\w+)")
+            .unwrap()
+            .captures_iter(&stripped)
+            .map(|captures| captures[1].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_strip_comments_and_literals() {
+        let java = r#"int a = R.string.code; // R.string.line_comment
+/* R.string.block
+   R.string.block_continued */ int b = R.string.after_block;
+String s = "R.string.in_literal \" R.string.escaped"; char q = '"'; int c = R.string.after_char;
+char e = '\''; String t = "unterminated
+/* /* */ int d = R.string.java_comments_dont_nest;"#;
+        assert_eq!(
+            usages(java, false),
+            vec![
+                "code",
+                "after_block",
+                "after_char",
+                "java_comments_dont_nest"
+            ]
+        );
+
+        let kotlin = r#"val a = "${getString(R.string.template)} R.string.in_literal"
+val b = """R.string.raw ${getString(R.string.raw_template)} "quoted" """
+/* /* R.string.nested */ R.string.still_comment */ val c = mapOf("k" to R.string.code)"#;
+        assert_eq!(
+            usages(kotlin, true),
+            vec!["template", "raw_template", "code"]
+        );
+    }
+}
@@ -0,0 +1,549 @@
+use crate::extract;
+use crate::index::{self, ResourceIndex};
+use crate::output::{outln, Format};
+use crate::paths;
+use anyhow::Result;
+use ignore::WalkBuilder;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Attributes that hold user visible text.
+const TEXT_ATTRIBUTES: &[&str] = &["text", "hint", "label", "title", "contentDescription"];
+
+#[derive(Serialize)]
+struct Finding {
+    path: String,
+    line: u64,
+    column: u64,
+    attribute: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct NameFinding<'a> {
+    path: &'a str,
+    line: u64,
+    name: &'a str,
+    problem: String,
+    /// Whether aapt fails the build on it, rather than it just breaking
+    /// the naming convention.
+    rejected_by_aapt: bool,
+}
+
+/// Java keywords, which can't be the names of fields in R.
+const JAVA_KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Why aapt rejects `name`, if it does: it has to make a Java identifier,
+/// with dots becoming underscores.
+fn aapt_problem(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return Some("is empty".to_string());
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some("starts with a digit".to_string());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+    {
+        return Some(if c == '-' {
+            "contains a hyphen".to_string()
+        } else {
+            format!("contains '{}'", c)
+        });
+    }
+    if JAVA_KEYWORDS.contains(&name) {
+        return Some("is a Java keyword".to_string());
+    }
+    None
+}
+
+/// Reports string names that aapt will reject, or that don't match the
+/// `convention`, at each place they're defined. Returns the number of
+/// findings.
+pub fn names(index: &ResourceIndex, convention: &Regex, format: Format) -> Result<usize> {
+    let mut findings = Vec::new();
+    for file in index.files() {
+        for definition in file.string_definitions() {
+            let name = index.name(definition.name);
+            let (problem, rejected_by_aapt) = match aapt_problem(name) {
+                Some(problem) => (problem, true),
+                None if !convention.is_match(name) => {
+                    (format!("doesn't match {}", convention), false)
+                }
+                None => continue,
+            };
+            findings.push(NameFinding {
+                path: file.path(),
+                line: definition.line,
+                name,
+                problem,
+                rejected_by_aapt,
+            });
+        }
+    }
+    findings.sort_by_key(|finding| (finding.path, finding.line));
+
+    match format {
+        Format::Text => {
+            for finding in &findings {
+                outln!(
+                    "{}:{}: {} {}",
+                    paths::display(finding.path),
+                    finding.line,
+                    finding.name,
+                    finding.problem
+                );
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&findings)?),
+    }
+
+    Ok(findings.len())
+}
+
+#[derive(Serialize)]
+struct EscapingFinding<'a> {
+    locale: &'a str,
+    path: &'a str,
+    line: u64,
+    name: &'a str,
+    problem: String,
+}
+
+/// The problems aapt has with how `value` is escaped: apostrophes outside of
+/// double quotes, unbalanced double quotes, and escape sequences it doesn't
+/// know.
+fn escaping_problems(value: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut quoted = false;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\'' | '"' | '\\' | 'n' | 't' | '@' | '?' | '#') => {}
+                Some('u') => {
+                    let digits: String = chars
+                        .clone()
+                        .take(4)
+                        .take_while(char::is_ascii_hexdigit)
+                        .collect();
+                    for _ in 0..digits.len() {
+                        chars.next();
+                    }
+                    if digits.len() < 4 {
+                        problems.push(format!("has an invalid unicode escape \\u{}", digits));
+                    }
+                }
+                Some(c) => problems.push(format!("has an invalid escape sequence \\{}", c)),
+                None => problems.push("ends with a lone backslash".to_string()),
+            },
+            '"' => quoted = !quoted,
+            '\'' if !quoted => problems.push("has an unescaped apostrophe".to_string()),
+            _ => {}
+        }
+    }
+    if quoted {
+        problems.push("has an unbalanced double quote".to_string());
+    }
+    problems.dedup();
+    problems
+}
+
+/// Reports string values in every locale that aapt will fail on, or mangle,
+/// because of how they're escaped, grouped by locale. Returns the number of
+/// findings.
+pub fn escaping(index: &ResourceIndex, format: Format) -> Result<usize> {
+    let mut findings = Vec::new();
+    for file in index.files() {
+        for definition in file.string_definitions() {
+            for problem in escaping_problems(&definition.value) {
+                findings.push(EscapingFinding {
+                    locale: index::locale_of(file.path()),
+                    path: file.path(),
+                    line: definition.line,
+                    name: index.name(definition.name),
+                    problem,
+                });
+            }
+        }
+    }
+    // The default locale first, then the rest by name.
+    findings.sort_by_key(|f| (f.locale != "default", f.locale, f.path, f.line));
+
+    match format {
+        Format::Text => {
+            for (i, finding) in findings.iter().enumerate() {
+                if i == 0 || findings[i - 1].locale != finding.locale {
+                    let count = findings
+                        .iter()
+                        .filter(|f| f.locale == finding.locale)
+                        .count();
+                    outln!("{} ({})", finding.locale, count);
+                }
+                outln!(
+                    "  {}:{}: {} {}",
+                    paths::display(finding.path),
+                    finding.line,
+                    finding.name,
+                    finding.problem
+                );
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&findings)?),
+    }
+
+    Ok(findings.len())
+}
+
+#[derive(Serialize)]
+struct ConflictingDefinition<'a> {
+    path: &'a str,
+    line: u64,
+    value: &'a str,
+}
+
+#[derive(Serialize)]
+struct Conflict<'a> {
+    name: &'a str,
+    /// The source set, like debug, when it isn't main or plain res/.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_set: Option<&'a str>,
+    configuration: &'a str,
+    definitions: Vec<ConflictingDefinition<'a>>,
+}
+
+/// Reports strings defined in more than one file for the same source set and
+/// configuration with different values, where aapt's merge order silently
+/// picks one of them. A debug/ or flavor's value overriding main's is left
+/// alone, since that's what it's for. Returns the number of conflicts.
+pub fn conflicts(index: &ResourceIndex, format: Format) -> Result<usize> {
+    let mut conflicts = Vec::new();
+    for (symbol, paths) in index.files_for_definition().iter_all() {
+        if paths.len() < 2 {
+            continue;
+        }
+        let mut by_configuration: BTreeMap<(Option<&str>, &str), Vec<ConflictingDefinition>> =
+            BTreeMap::new();
+        for (path, definition) in index.definitions_of(*symbol) {
+            let source_set = index::source_set_dir(path).filter(|set| *set != "main");
+            by_configuration
+                .entry((source_set, index::locale_of(path)))
+                .or_default()
+                .push(ConflictingDefinition {
+                    path,
+                    line: definition.line,
+                    value: definition.value.trim(),
+                });
+        }
+        for ((source_set, configuration), mut definitions) in by_configuration {
+            if definitions.iter().all(|d| d.value == definitions[0].value) {
+                continue;
+            }
+            definitions.sort_by_key(|d| (d.path, d.line));
+            conflicts.push(Conflict {
+                name: index.name(*symbol),
+                source_set,
+                configuration,
+                definitions,
+            });
+        }
+    }
+    conflicts.sort_by_key(|c| {
+        (
+            c.name,
+            c.source_set,
+            c.configuration != "default",
+            c.configuration,
+        )
+    });
+
+    match format {
+        Format::Text => {
+            for conflict in &conflicts {
+                match conflict.source_set {
+                    Some(source_set) => outln!(
+                        "{} ({}, {})",
+                        conflict.name,
+                        source_set,
+                        conflict.configuration
+                    ),
+                    None => outln!("{} ({})", conflict.name, conflict.configuration),
+                }
+                for definition in &conflict.definitions {
+                    outln!(
+                        "  {}:{}: \"{}\"",
+                        paths::display(definition.path),
+                        definition.line,
+                        definition.value
+                    );
+                }
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&conflicts)?),
+    }
+
+    Ok(conflicts.len())
+}
+
+fn manifest_files(manifest_root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = WalkBuilder::new(manifest_root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name == "AndroidManifest.xml")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Reports text attributes in layouts and manifests that use literal values
+/// rather than string resources. Returns the number of findings.
+pub fn hardcoded_text(res_root: &Path, manifest_root: &Path, format: Format) -> Result<usize> {
+    let mut files = extract::layout_files(res_root);
+    files.append(&mut manifest_files(manifest_root));
+
+    let mut findings = Vec::new();
+    for file in files {
+        for hardcoded in extract::find_hardcoded_text(&file, TEXT_ATTRIBUTES)? {
+            findings.push(Finding {
+                path: paths::to_stored(&hardcoded.path),
+                line: hardcoded.position.row + 1,
+                column: hardcoded.position.column + 1,
+                attribute: hardcoded.attribute,
+                value: hardcoded.value,
+            });
+        }
+    }
+
+    match format {
+        Format::Text => {
+            for finding in &findings {
+                outln!(
+                    "{}:{}:{}: {}=\"{}\" is hardcoded",
+                    paths::display(&finding.path),
+                    finding.line,
+                    finding.column,
+                    finding.attribute,
+                    finding.value
+                );
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&findings)?),
+    }
+
+    Ok(findings.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_names() -> Result<()> {
+        let tmp_dir = TempDir::new("lint")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources>
+<string name="good_name">A</string>
+<string name="dotted.name">B</string>
+<string name="BadCase">C</string>
+<string name="2fast">D</string>
+<string name="has-hyphen">E</string>
+<string name="class">F</string>
+</resources>"#,
+        )?;
+        let index = Indexer::new(res_dir.clone(), res_dir, None, None)?.index()?;
+
+        let convention = Regex::new("^[a-z][a-z0-9_.]*$")?;
+        assert_eq!(names(&index, &convention, Format::Json)?, 4);
+        assert_eq!(aapt_problem("BadCase"), None);
+        assert_eq!(
+            aapt_problem("2fast").as_deref(),
+            Some("starts with a digit")
+        );
+        assert_eq!(
+            aapt_problem("has-hyphen").as_deref(),
+            Some("contains a hyphen")
+        );
+        assert_eq!(aapt_problem("a b").as_deref(), Some("contains ' '"));
+        assert_eq!(aapt_problem("class").as_deref(), Some("is a Java keyword"));
+        assert_eq!(names(&index, &Regex::new(".*")?, Format::Text)?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_escaping() -> Result<()> {
+        assert!(escaping_problems(r#"Don\'t "won't" \"quoted\" \n\t\\ \u00e9 \@"#).is_empty());
+        assert_eq!(
+            escaping_problems("Don't stop, can't stop"),
+            vec!["has an unescaped apostrophe"]
+        );
+        assert_eq!(
+            escaping_problems(r#"\x \u12 "open \"#),
+            vec![
+                "has an invalid escape sequence \\x",
+                "has an invalid unicode escape \\u12",
+                "ends with a lone backslash",
+                "has an unbalanced double quote"
+            ]
+        );
+
+        let tmp_dir = TempDir::new("lint")?;
+        let res_dir = tmp_dir.path().join("res");
+        for (dir, value) in [("values", r"Don\'t"), ("values-fr", "Aujourd'hui")] {
+            fs::create_dir_all(res_dir.join(dir))?;
+            fs::write(
+                res_dir.join(dir).join("strings.xml"),
+                format!(
+                    r#"<resources><string name="today">{}</string></resources>"#,
+                    value
+                ),
+            )?;
+        }
+        let index = Indexer::new(res_dir.clone(), res_dir, None, None)?.index()?;
+        assert_eq!(escaping(&index, Format::Text)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts() -> Result<()> {
+        let tmp_dir = TempDir::new("lint")?;
+        let src_dir = tmp_dir.path().join("src");
+        for (path, strings) in [
+            (
+                "main/res/values/a.xml",
+                r#"<string name="title">Title</string><string name="ok">Same</string>"#,
+            ),
+            (
+                "main/res/values/b.xml",
+                r#"<string name="title">Other title</string><string name="ok"> Same </string>"#,
+            ),
+            (
+                "main/res/values-fr/a.xml",
+                r#"<string name="title">Titre</string>"#,
+            ),
+            // Overriding main's value for debug builds isn't a conflict.
+            (
+                "debug/res/values/a.xml",
+                r#"<string name="ok">Debug</string>"#,
+            ),
+        ] {
+            let path = src_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, format!("<resources>{}</resources>", strings))?;
+        }
+        let index = Indexer::new(src_dir.clone(), src_dir, None, None)?.index()?;
+        let output = crate::output::capture(|| {
+            assert_eq!(conflicts(&index, Format::Text)?, 1);
+            Ok(())
+        })?;
+        assert!(output.starts_with("title (default)\n"));
+        assert!(!output.contains("Debug"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hardcoded_text() -> Result<()> {
+        let tmp_dir = TempDir::new("lint")?;
+        let layout_dir = tmp_dir.path().join("res/layout");
+        fs::create_dir_all(&layout_dir)?;
+        for (name, attribute) in [
+            ("literal", r#"android:text="Hello""#),
+            ("resource", r#"android:text="@string/hello""#),
+            ("preview", r#"tools:text="Preview""#),
+        ] {
+            fs::write(
+                layout_dir.join(format!("{}.xml", name)),
+                format!(
+                    r#"<TextView
+    xmlns:android="http://schemas.android.com/apk/res/android"
+    xmlns:tools="http://schemas.android.com/tools"
+    {} />
+"#,
+                    attribute
+                ),
+            )?;
+        }
+
+        let mut found = 0;
+        let printed = crate::output::capture(|| {
+            found = hardcoded_text(&tmp_dir.path().join("res"), tmp_dir.path(), Format::Text)?;
+            Ok(())
+        })?;
+        assert_eq!(found, 1);
+        let literal = layout_dir.join("literal.xml").display().to_string();
+        assert!(printed.starts_with(&literal), "{}", printed);
+        assert!(printed.ends_with(": android:text=\"Hello\" is hardcoded\n"));
+        assert_eq!(printed.lines().count(), 1);
+
+        Ok(())
+    }
+}
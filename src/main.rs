@@ -1,23 +1,74 @@
-use anyhow::{anyhow, Error, Result};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::str;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use output::outln;
+use serde::Serialize;
 use std::path::Path;
 use std::path::PathBuf;
-use std::str::FromStr;
 use structopt::StructOpt;
+use symbols::Symbol;
 
+mod apk;
+mod backup;
+mod bench;
+mod cache;
+mod consumers;
+mod diagnostics;
+mod diff;
+mod dot;
+mod extract;
+mod filter;
+mod fragments;
+mod git;
+mod group;
 mod index;
+mod index_json;
+mod json_localization;
+mod keep;
+mod lexer;
+mod lint;
+mod mapped;
+mod metrics;
+mod output;
+mod owners;
+mod paths;
+mod progress;
+mod pseudo;
+mod remote;
+mod schema;
+mod search;
+mod show;
+mod split;
+mod sqlite;
+mod stamp;
+mod summary;
+mod sweep;
+mod symbols;
+mod tidy;
+mod trace;
+mod tui;
+mod variant;
+mod verify;
 mod xeditor;
+mod xliff;
 
 #[derive(Debug, StructOpt)]
 /// Finds and manipluates string resources
 #[structopt(name = "art", bin_name = "art", no_version)]
 struct Opt {
+    /// The root of the Java and Kotlin sources. Every command but
+    /// completions and schema needs it
     #[structopt(short)]
-    java_root: PathBuf,
+    java_root: Option<PathBuf>,
 
+    /// The root of the resources. Every command but completions and schema
+    /// needs it
     #[structopt(short)]
-    res_root: PathBuf,
+    res_root: Option<PathBuf>,
 
     #[structopt(short)]
     manifest_root: Option<PathBuf>,
@@ -25,122 +76,2147 @@ struct Opt {
     #[structopt(long)]
     cache_dir: Option<PathBuf>,
 
+    /// How to report indexing progress: auto, bar, json or none
+    #[structopt(long, default_value = "auto")]
+    progress: progress::ProgressMode,
+
+    /// Where to store the index: file or sqlite
+    #[structopt(long, default_value = "file")]
+    index_backend: index::IndexBackend,
+
+    /// Caches the index in one fragment per top-level directory, so that
+    /// indexing again only reindexes and rewrites the directories with
+    /// changed files
+    #[structopt(long)]
+    cache_per_directory: bool,
+
+    /// What to do when the index was built from another commit, or
+    /// uncommitted files changed since: error, warn, or refresh it first
+    #[structopt(long, default_value = "warn", possible_values = &["error", "warn", "refresh"])]
+    stale: stamp::StalePolicy,
+
+    /// A shared cache (http(s)://, s3:// or file:// url) to fetch and push
+    /// indexes, keyed by git commit
+    #[structopt(long, parse(try_from_str = remote::RemoteCache::new))]
+    remote_cache: Option<remote::RemoteCache>,
+
+    /// Also index build output and generated code (build, .gradle and
+    /// generated directories, and R.java), which are skipped by default
+    #[structopt(long)]
+    include_generated: bool,
+
+    /// Also index files excluded by .gitignore, .ignore and similar files
+    #[structopt(long)]
+    no_ignore: bool,
+
+    /// Also index hidden files and directories
+    #[structopt(long)]
+    hidden: bool,
+
+    /// Only index files tracked by git, ignoring scratch files and local
+    /// experiments
+    #[structopt(long)]
+    vcs_only: bool,
+
+    /// Follows symbolic links, e.g. to module directories, indexing each file
+    /// once however many links lead to it
+    #[structopt(long)]
+    follow_symlinks: bool,
+
+    /// Counts references to strings in comments and string literals, like
+    /// commented-out code, as usages
+    #[structopt(long)]
+    include_comments: bool,
+
+    /// Skips files larger than this while indexing, e.g. 10M
+    #[structopt(long, parse(try_from_str = cache::parse_size))]
+    max_filesize: Option<u64>,
+
+    /// Lists the files skipped while indexing (binary or too large) rather
+    /// than just counting them
+    #[structopt(short, long)]
+    verbose: bool,
+
+    /// Fails indexing if any file can't be read or parsed, rather than
+    /// leaving its strings out of the index (which could make used strings
+    /// look unused)
+    #[structopt(long)]
+    strict: bool,
+
+    /// Number of threads to index with (defaults to the available parallelism)
+    #[structopt(long)]
+    threads: Option<usize>,
+
+    /// A shipped build variant and the source sets (directories under src/)
+    /// it's built from besides main, e.g. paidRelease=paid,release,paidRelease
+    /// (repeatable). Once any are given, usages from source sets outside of
+    /// them, like a dropped flavor's, no longer count
+    #[structopt(long = "variant", number_of_values = 1)]
+    variants: Vec<variant::Variant>,
+
+    /// Reports strings declared in public.xml unused like any other. By
+    /// default they're a library's API and never unused
+    #[structopt(long)]
+    ignore_public: bool,
+
+    /// Records where time goes while indexing and editing, per thread and
+    /// file, to this file as a Chrome trace (open in chrome://tracing or
+    /// Perfetto)
+    #[structopt(long)]
+    trace_output: Option<PathBuf>,
+
+    /// Prints results without headings, indentation or timing messages, one
+    /// per line with tab-separated columns, for piping to other tools.
+    /// ls-unused marks strings used only from tests with a test-only column
+    #[structopt(long)]
+    plain: bool,
+
+    /// Writes the command's results (lists, reports, json) to this file
+    /// instead of stdout. Progress and log messages stay on stderr
+    #[structopt(long = "output")]
+    output: Option<PathBuf>,
+
+    /// An R.txt or list of string names (one per line) used by a downstream
+    /// app (repeatable). Those strings are never reported or removed
+    #[structopt(long = "external-consumers", number_of_values = 1)]
+    external_consumers: Vec<PathBuf>,
+
+    /// Never reports or removes strings starting with this prefix, e.g. ones
+    /// looked up by building their names at runtime (repeatable). The same as
+    /// a rating_* line in .xdelignore
+    #[structopt(long = "keep-prefix", number_of_values = 1)]
+    keep_prefixes: Vec<String>,
+
     #[structopt(subcommand)]
     subcommand: Subcommand,
 }
 
 #[derive(Debug, StructOpt)]
 enum Subcommand {
-    Counts {},
-    Index {},
+    Counts {
+        /// Exits with an error if more than this many strings are unused,
+        /// e.g. to hold a resource-debt budget in CI
+        #[structopt(long)]
+        fail_if_unused_over: Option<usize>,
+
+        /// Exits with an error if more than this percentage of the defined
+        /// strings are unused
+        #[structopt(long)]
+        fail_if_unused_pct_over: Option<f64>,
+    },
+    Index {
+        /// Use the index for the checked out commit from the remote cache if
+        /// there is one
+        #[structopt(long)]
+        fetch: bool,
+
+        /// Upload the index to the remote cache
+        #[structopt(long)]
+        push: bool,
+
+        /// Only indexes files matching this glob, or in directories matching
+        /// it, relative to each root (repeatable), and writes them to the
+        /// --shard-output file rather than the cache, to merge later
+        #[structopt(
+            long,
+            number_of_values = 1,
+            requires = "shard-output",
+            conflicts_with_all = &["fetch", "push"]
+        )]
+        shard: Vec<String>,
+
+        #[structopt(long, requires = "shard")]
+        shard_output: Option<PathBuf>,
+
+        #[structopt(subcommand)]
+        action: Option<IndexAction>,
+    },
     LsUnused {
         #[structopt(short)]
         show_location: bool,
+
+        /// Buckets results by their first N name segments with prefix:N, or by
+        /// CODEOWNERS/OWNERS with owner
+        #[structopt(long)]
+        group_by: Option<group::GroupBy>,
+
+        /// Shows who added each string and when, from git blame
+        #[structopt(long)]
+        blame: bool,
+
+        /// Only lists strings added at least this long ago (per git blame),
+        /// e.g. 90d, 2w, 6m or 1y
+        #[structopt(long, parse(try_from_str = git::parse_age))]
+        older_than: Option<i64>,
+
+        /// Also lists strings used only from test and androidTest source
+        /// sets, separately
+        #[structopt(long)]
+        include_test_only: bool,
+
+        /// text, json, or ndjson to print each string as a JSON object on its
+        /// own line as it's found
+        #[structopt(long, default_value = "text")]
+        format: output::ListFormat,
+
+        /// The kind of resource to list: string, drawable, color, dimen,
+        /// style, layout or destination (in a navigation graph)
+        #[structopt(long, default_value = "string")]
+        kind: index::ResourceKind,
+
+        #[structopt(flatten)]
+        filter: filter::Filter,
     },
     RmUnused {
         #[structopt(short)]
         prefix: Option<String>,
+
+        /// Commits the removals, with a message listing the removed strings.
+        /// Refuses to if the resources have uncommitted changes
+        #[structopt(long)]
+        commit: bool,
+
+        /// Creates this branch for the commit, e.g. cleanup/unused-strings
+        #[structopt(long, requires = "commit")]
+        branch: Option<String>,
+
+        /// Also removes the comment just above each removed string
+        #[structopt(long)]
+        remove_comments: bool,
+
+        /// Collapses blank lines left around removed strings into one
+        #[structopt(long)]
+        collapse_blank_lines: bool,
+
+        /// Deletes resource files left with no resources in them
+        #[structopt(long)]
+        prune_empty_files: bool,
+
+        /// Removes at most this many strings, in name order, saying how many
+        /// are left for later runs
+        #[structopt(long)]
+        limit: Option<usize>,
+
+        /// Removes resources in batches of this size, committing each batch
+        /// separately with --commit or listing the files it touched without
+        #[structopt(long)]
+        batch_size: Option<usize>,
+
+        /// Writes a Markdown description of the removal, for a pull request,
+        /// to this file (- for stdout). Only for strings
+        #[structopt(long)]
+        summary_file: Option<PathBuf>,
+
+        /// Copies each file into a timestamped directory under the cache
+        /// directory before changing it, with a manifest of what was removed
+        #[structopt(long)]
+        backup: bool,
+
+        /// Like --backup, but copies files into this directory
+        #[structopt(long)]
+        backup_dir: Option<PathBuf>,
+
+        /// The kind of resource to remove: string, drawable, color, dimen,
+        /// style, layout or destination (in a navigation graph).
+        /// Drawables and color state lists are deleted with all their variants
+        #[structopt(long, default_value = "string")]
+        kind: index::ResourceKind,
+
+        #[structopt(flatten)]
+        filter: filter::Filter,
+    },
+    /// Moves strings with a common prefix into their own resource files
+    Split {
+        #[structopt(long)]
+        by_prefix: String,
+
+        /// The new file for the default configuration, e.g. res/values/strings_feature.xml
+        #[structopt(long)]
+        into: PathBuf,
+    },
+    /// Moves literal android:text values in layouts into string resources
+    ExtractHardcoded {
+        /// Lists what would be extracted without changing any files
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Removes strings from every locale that has them when the default
+    /// locale doesn't define them, as earlier removals may have left behind
+    SweepTranslations {
+        /// Deletes values files left with no resources in them
+        #[structopt(long)]
+        prune_empty_files: bool,
+
+        /// Lists what would be removed without changing any files
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Removes comments in values files that only mention names the file no
+    /// longer defines, as removals leave behind, and long runs of blank lines
+    Tidy {
+        /// Keeps at most this many blank lines in a row between elements
+        #[structopt(long, default_value = "1")]
+        max_blank_lines: usize,
+
+        /// Lists what would be removed without changing any files
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Finds strings, in every locale, whose values contain some text
+    Search {
+        /// The text to look for
+        text: String,
+
+        /// Treats the text as a regex
+        #[structopt(long)]
+        regex: bool,
+
+        /// Ignores case when matching
+        #[structopt(short, long)]
+        ignore_case: bool,
+
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Reports strings added, removed, newly unused and newly used between two
+    /// index files, e.g. ones saved from the cache dir at two releases
+    Diff {
+        /// The earlier index file
+        #[structopt(long)]
+        old: PathBuf,
+
+        /// The later index file
+        #[structopt(long)]
+        new: PathBuf,
+
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Checks the unused strings against a built APK or app bundle (AAB):
+    /// lists unused strings the build still contains, and strings the build's
+    /// resource shrinker removed that are still in source
+    Verify {
+        /// The APK or app bundle, e.g. app-release.apk
+        #[structopt(long)]
+        apk: PathBuf,
+
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Shows a string's value in every locale, and where it's defined and used
+    Show {
+        name: String,
+
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Exports strings for translation
+    Export {
+        /// Writes XLIFF with the default strings as sources and existing
+        /// translations into --locale as targets
+        #[structopt(long, requires = "locale")]
+        xliff: bool,
+
+        /// Writes every locale's strings as JSON shaped {locale: {name: value}},
+        /// for localization platforms
+        #[structopt(long, conflicts_with = "xliff")]
+        json_localization: bool,
+
+        /// Writes a Graphviz graph of which files reference which strings
+        #[structopt(long, conflicts_with_all = &["xliff", "json-localization"])]
+        dot: bool,
+
+        /// Only graphs strings with this name
+        #[structopt(long)]
+        name: Option<String>,
+
+        /// Only graphs strings whose names start with this prefix
+        #[structopt(long, conflicts_with = "name")]
+        prefix: Option<String>,
+
+        /// The locale to translate into, e.g. fr or pt-BR
+        #[structopt(long)]
+        locale: Option<String>,
+
+        /// The language of the default strings
+        #[structopt(long, default_value = "en")]
+        source_locale: String,
+
+        /// 1.2 or 2.0
+        #[structopt(long, default_value = "1.2")]
+        xliff_version: xliff::XliffVersion,
+
+        /// Writes to this file rather than stdout
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Imports translated strings
+    Import {
+        /// Writes the translations in this XLIFF file into the values
+        /// directories for its target language
+        #[structopt(long, required_unless = "json-localization")]
+        xliff: Option<PathBuf>,
+
+        /// The locale to import XLIFF into, overriding the file's target
+        /// language
+        #[structopt(long)]
+        locale: Option<String>,
+
+        /// Writes the strings in this JSON file, shaped like
+        /// `export --json-localization` writes it, into the values
+        /// directories beside each string's default definition
+        #[structopt(long, conflicts_with = "xliff")]
+        json_localization: Option<PathBuf>,
+
+        /// Also writes the JSON's default strings over the source values
+        /// files, which are otherwise left alone
+        #[structopt(long, requires = "json-localization")]
+        overwrite_default: bool,
+    },
+    /// Writes a pseudolocalized copy of the default strings, accented,
+    /// padded and bracketed, to check the app's ready for translation
+    /// without translating it
+    Pseudo {
+        /// The pseudo-locale to write, e.g. en-XA
+        #[structopt(long, default_value = "en-XA")]
+        locale: String,
+    },
+    /// Reports common problems in resources
+    Lint(Lint),
+    /// Prints a completion script for bash, zsh, fish, powershell or elvish.
+    /// Doesn't need -j or -r, e.g. art completions zsh > _art
+    Completions {
+        #[structopt(possible_values = &structopt::clap::Shell::variants(), case_insensitive = true)]
+        shell: structopt::clap::Shell,
+    },
+    /// Prints the JSON Schema of a command's --format json output, or of
+    /// every command's keyed by command. Doesn't need -j or -r, e.g.
+    /// art schema lint names
+    Schema {
+        /// e.g. ls-unused, or lint conflicts
+        command: Vec<String>,
+    },
+    /// Reports statistics about the index
+    Stats(Stats),
+    /// Indexes several times, timing each phase (walk, xml parse, source grep,
+    /// invert and serialize), and prints a table of the timings
+    Bench {
+        #[structopt(long, default_value = "5")]
+        iterations: usize,
+
+        /// Saves the timings to this file, to compare later runs against
+        #[structopt(long)]
+        save: Option<PathBuf>,
+
+        /// Compares the timings with ones saved by an earlier run
+        #[structopt(long)]
+        baseline: Option<PathBuf>,
+    },
+    /// Inspects and prunes cached indexes
+    Cache(Cache),
+    /// Interactively reviews unused strings and deletes the ones you mark
+    Tui {},
+}
+
+#[derive(Debug, StructOpt)]
+enum IndexAction {
+    /// Writes the whole index, every file with the strings and resources it
+    /// defines and uses, as json for custom reports
+    Export {
+        #[structopt(long)]
+        json: PathBuf,
+    },
+    /// Merges files from a json index fragment, shaped like export writes
+    /// it, into the index, e.g. the strings a backend service sends to
+    /// clients. They're kept and merged into the index whenever it's rebuilt
+    Import { fragment: PathBuf },
+    /// Merges index files, e.g. shards of different subtrees indexed on
+    /// different machines, into one. Files in several shards are kept once,
+    /// from the last
+    Merge {
+        #[structopt(required = true, min_values = 2)]
+        shards: Vec<PathBuf>,
+
+        #[structopt(short, long)]
+        output: PathBuf,
+    },
+    /// Indexes just the given files again and patches them into the cached
+    /// index, e.g. from an editor's save hook. Files that were deleted are
+    /// removed from it
+    Update {
+        #[structopt(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Shows when the cached index was built and from which roots and
+    /// commit, the files it covers, its size, and whether it's stale
+    Status {},
+}
+
+#[derive(Debug, StructOpt)]
+enum Stats {
+    /// Lists defined strings with how many times they're used
+    Usage {
+        /// Order by usage count ascending (asc), descending (desc) or by name
+        #[structopt(long, default_value = "asc", possible_values = &["asc", "desc", "name"])]
+        sort: String,
+    },
+    /// Prints gauges of the strings defined and unused in each module in the
+    /// Prometheus text format, e.g. for a nightly job to chart
+    Prometheus {
+        /// Writes the metrics to this file instead, replacing it atomically,
+        /// for node_exporter's textfile collector
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum Cache {
+    /// Lists cached indexes, including directories of per-directory fragments
+    Ls {},
+    /// Removes all cached indexes
+    Clean {},
+    /// Removes the oldest cached indexes until the cache fits in a size limit
+    Gc {
+        /// e.g. 500M or 2G
+        #[structopt(long, parse(try_from_str = cache::parse_size))]
+        max_size: u64,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum Lint {
+    /// Lists text attributes in layouts and manifests that aren't string resources
+    HardcodedText {
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Lists string names that aapt will reject, or that break the naming
+    /// convention
+    Names {
+        /// A regex that names have to match
+        #[structopt(long, default_value = "^[a-z][a-z0-9_]*$")]
+        convention: regex::Regex,
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Lists string values in every locale with apostrophes, quotes or
+    /// escape sequences that aapt will fail on
+    Escaping {
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
+    },
+    /// Lists strings defined in several files for the same source set and
+    /// configuration with different values
+    Conflicts {
+        #[structopt(long, default_value = "text")]
+        format: output::Format,
     },
 }
 
+/// Prints the completion script for `shell`, generated from the options and
+/// subcommands above.
+fn print_completions(shell: structopt::clap::Shell) -> Result<()> {
+    let mut script = Vec::new();
+    Opt::clap().gen_completions_to("art", shell, &mut script);
+    output::Stdout.write_all(&script)?;
+    Ok(())
+}
+
+/// Prints the schema of `command`'s json output, or with no command, all of
+/// them.
+fn print_schema(command: &[String]) -> Result<()> {
+    let schema = if command.is_empty() {
+        schema::all()?
+    } else {
+        schema::schema(&command.join(" "))?
+    };
+    outln!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+fn print_index_status(indexer: &index::Indexer) -> Result<()> {
+    let status = match indexer.cache_status()? {
+        Some(status) => status,
+        None => {
+            outln!("No index found. Run the index command first");
+            return Ok(());
+        }
+    };
+
+    outln!("Index: {}", status.path.display());
+    for (name, root) in &status.roots {
+        outln!("{} root: {}", name, root.display());
+    }
+    let commit = match status
+        .stamp
+        .as_ref()
+        .and_then(|stamp| stamp.commit.as_ref())
+    {
+        Some(commit) => format!(" at commit {}", &commit[..commit.len().min(8)]),
+        None => String::new(),
+    };
+    outln!("Built: {}{}", cache::format_age(status.built), commit);
+    outln!("Files: {}", status.files);
+    outln!("Size: {}", cache::format_size(status.size));
+    match (&status.stamp, &status.staleness) {
+        (None, _) => outln!("Stale: unknown, the index has no stamp"),
+        (Some(_), Some(reason)) => outln!("Stale: yes, {}", reason),
+        (Some(_), None) => outln!("Stale: no"),
+    }
+
+    Ok(())
+}
+
 impl Opt {
+    /// Parses the command line, exiting with a usage error if the command
+    /// needs roots that weren't given.
     pub fn parse() -> Result<Opt> {
         let m = Opt::clap().get_matches();
-        Ok(Opt::from_clap(&m))
+        let opt = Opt::from_clap(&m);
+        if opt.subcommand.needs_roots() && (opt.java_root.is_none() || opt.res_root.is_none()) {
+            structopt::clap::Error::with_description(
+                "-j <java-root> and -r <res-root> are required for this command",
+                structopt::clap::ErrorKind::MissingRequiredArgument,
+            )
+            .exit();
+        }
+        Ok(opt)
+    }
+}
+
+impl Subcommand {
+    /// Whether the command works on the roots, which is all but those that
+    /// describe the tool itself.
+    fn needs_roots(&self) -> bool {
+        !matches!(
+            self,
+            Subcommand::Completions { .. } | Subcommand::Schema { .. }
+        )
+    }
+}
+
+/// Fails if more strings are unused than `max` or `max_pct` percent of the
+/// defined ones allow.
+fn check_unused_budget(
+    unused: usize,
+    defined: usize,
+    max: Option<usize>,
+    max_pct: Option<f64>,
+) -> Result<()> {
+    if let Some(max) = max.filter(|max| unused > *max) {
+        return Err(anyhow::anyhow!(
+            "{} unused strings, over the limit of {}",
+            unused,
+            max
+        ));
+    }
+    let pct = if defined == 0 {
+        0.0
+    } else {
+        unused as f64 * 100.0 / defined as f64
+    };
+    if let Some(max_pct) = max_pct.filter(|max_pct| pct > *max_pct) {
+        return Err(anyhow::anyhow!(
+            "{:.1}% of strings are unused, over the limit of {}%",
+            pct,
+            max_pct
+        ));
+    }
+
+    Ok(())
+}
+
+/// An unused string, as ls-unused prints it as JSON.
+#[derive(Serialize)]
+struct UnusedListing<'a> {
+    name: &'a str,
+    locations: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blame: Option<&'a git::Blame>,
+    /// Used only from tests, with --include-test-only.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    test_only: bool,
+}
+
+/// Whether an unused string should be reported and removed.
+fn is_reported(keep: &keep::KeepList, name: &str) -> bool {
+    !name.contains("emoji")
+        && !name.contains("f1gender")
+        && !name.contains("m2gender")
+        && !keep.keeps(name)
+}
+
+fn filtered_unused_strings(
+    index: &index::ResourceIndex,
+    variants: &[variant::Variant],
+    keep: &keep::KeepList,
+) -> Vec<Symbol> {
+    let mut unused_strings: Vec<Symbol> = variant::unused_strings(index, variants)
+        .into_iter()
+        .filter(|s| is_reported(keep, index.name(*s)))
+        .collect();
+
+    unused_strings.sort_by_key(|s| index.name(*s));
+
+    unused_strings
+}
+
+/// Returns the files defining each of `strings`, for definitions accepted by
+/// the filter.
+fn definition_files(
+    index: &index::ResourceIndex,
+    strings: &HashSet<Symbol>,
+    filter: &filter::Filter,
+) -> Vec<(String, Vec<String>)> {
+    let mut files: HashMap<String, Vec<String>> = HashMap::new();
+    for file in index.files() {
+        for definition in file.string_definitions() {
+            if strings.contains(&definition.name)
+                && filter.matches_definition(file.path(), &definition.value)
+            {
+                files
+                    .entry(index.name(definition.name).to_string())
+                    .or_default()
+                    .push(file.path().to_string());
+            }
+        }
     }
+    files.into_iter().collect()
 }
 
-#[derive(Debug)]
-enum Kind {
-    Defined,
-    Used,
-    Unused,
+/// Removes every definition of the given strings accepted by the filter.
+fn remove_strings(
+    index: &index::ResourceIndex,
+    symbols: &[Symbol],
+    filter: &filter::Filter,
+    options: &xeditor::RemoveOptions,
+    mut backup: Option<&mut backup::Backup>,
+) -> Result<Vec<PathBuf>> {
+    let symbols: HashSet<&Symbol> = symbols.iter().collect();
+
+    let mut modified = Vec::new();
+    for file in index.files() {
+        let names: Vec<&str> = file
+            .string_definitions()
+            .iter()
+            .filter(|definition| {
+                symbols.contains(&definition.name)
+                    && filter.matches_definition(file.path(), &definition.value)
+            })
+            .map(|definition| index.name(definition.name))
+            .collect();
+        if names.is_empty() {
+            continue;
+        }
+
+        let path = &paths::from_stored(file.path());
+        if let Some(backup) = backup.as_deref_mut() {
+            backup.save(path, names.iter().map(|name| name.to_string()).collect())?;
+        }
+        let matchers = names
+            .iter()
+            .map(|name| {
+                xeditor::ElementMatcher::for_path(&format!("/resources/string[@name='{}']", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let matchers: Vec<&xeditor::ElementMatcher> = matchers.iter().collect();
+        let report = xeditor::remove_elements(path, &matchers, options)?;
+        if report.deleted {
+            eprintln!("Deleted {} as it was left empty", path.display());
+        }
+        if report.changed() {
+            modified.push(path.to_path_buf());
+        }
+    }
+
+    Ok(modified)
 }
 
-impl FromStr for Kind {
-    type Err = Error;
-    fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match value {
-            "defined" => Ok(Kind::Defined),
-            "used" => Ok(Kind::Used),
-            "unused" => Ok(Kind::Unused),
-            _ => Err(anyhow!("Unrecognized value")),
+/// Removes each of `resources` of `kind` from the files defining them that
+/// the filter accepts: files that are a resource as a whole, like every
+/// density variant of a drawable, are deleted, and definitions in values
+/// files removed. Returns the files changed.
+fn remove_resources(
+    kind: index::ResourceKind,
+    resources: &[(String, Vec<String>)],
+    filter: &filter::Filter,
+    options: &xeditor::RemoveOptions,
+    mut backup: Option<&mut backup::Backup>,
+) -> Result<Vec<PathBuf>> {
+    let mut values_files: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut modified = Vec::new();
+    for (name, paths) in resources {
+        for path in paths.iter().filter(|path| filter.matches_file(path)) {
+            let path_buf = paths::from_stored(path);
+            if index::file_resource(&path_buf).is_none() {
+                values_files.entry(path).or_default().push(name);
+                continue;
+            }
+            let path = path_buf.as_path();
+            if let Some(backup) = backup.as_deref_mut() {
+                backup.save(path, vec![name.clone()])?;
+            }
+            std::fs::remove_file(path)?;
+            modified.push(path.to_path_buf());
+        }
+    }
+
+    for (path, names) in values_files {
+        let path = &paths::from_stored(path);
+        if let Some(backup) = backup.as_deref_mut() {
+            backup.save(path, names.iter().map(|name| name.to_string()).collect())?;
+        }
+        let mut matchers = Vec::new();
+        for name in names {
+            let paths = match kind {
+                index::ResourceKind::Destination => vec![
+                    format!("//*[@android:id='@+id/{}']", name),
+                    format!("//*[@android:id='@id/{}']", name),
+                ],
+                kind => vec![
+                    format!("/resources/{}[@name='{}']", kind.as_str(), name),
+                    format!(
+                        "/resources/item[@type='{}'][@name='{}']",
+                        kind.as_str(),
+                        name
+                    ),
+                ],
+            };
+            for path in paths {
+                matchers.push(xeditor::ElementMatcher::for_path(&path)?);
+            }
+        }
+        let matchers: Vec<&xeditor::ElementMatcher> = matchers.iter().collect();
+        let report = xeditor::remove_elements(path, &matchers, options)?;
+        if report.deleted {
+            eprintln!("Deleted {} as it was left empty", path.display());
+        }
+        if report.changed() {
+            modified.push(path.to_path_buf());
         }
     }
+
+    Ok(modified)
+}
+
+/// The message committing the removal of the unused resources `names`, the
+/// `i`th of `batches` batches.
+fn removal_message<'a>(
+    kind: index::ResourceKind,
+    names: impl ExactSizeIterator<Item = &'a str>,
+    i: usize,
+    batches: usize,
+) -> String {
+    let mut message = format!("Remove {} unused {}s", names.len(), kind.as_str());
+    if batches > 1 {
+        message += &format!(" (batch {} of {})", i + 1, batches);
+    }
+    message.push_str("\n\n");
+    for name in names {
+        message.push_str(name);
+        message.push('\n');
+    }
+    message
 }
 
-fn filtered_unused_strings(index: &index::ResourceIndex) -> Vec<&String> {
-    let mut unused_strings: Vec<&String> = index
-        .unused_strings()
+/// Blames the oldest definition of each string, which is when it was added.
+/// Strings in files that aren't tracked by git are left out.
+fn blame_strings(
+    index: &index::ResourceIndex,
+    unused: &[(String, Vec<String>)],
+) -> HashMap<String, git::Blame> {
+    let wanted: HashSet<(&str, &str)> = unused
         .iter()
-        .cloned()
-        .filter(|s| !s.contains("emoji") && !s.contains("f1gender") && !s.contains("m2gender"))
+        .flat_map(|(name, locations)| {
+            locations
+                .iter()
+                .map(move |loc| (name.as_str(), loc.as_str()))
+        })
         .collect();
 
-    unused_strings.sort();
+    let mut blames: HashMap<String, git::Blame> = HashMap::new();
+    for file in index.files() {
+        let mut file_blame = None;
+        for definition in file.string_definitions() {
+            let name = index.name(definition.name);
+            if !wanted.contains(&(name, file.path())) {
+                continue;
+            }
+            let file_blame =
+                file_blame.get_or_insert_with(|| git::blame(&paths::from_stored(file.path())).ok());
+            if let Some(blame) = file_blame.as_ref().and_then(|b| b.get(&definition.line)) {
+                let oldest = blames
+                    .entry(name.to_string())
+                    .or_insert_with(|| blame.clone());
+                if blame.time < oldest.time {
+                    *oldest = blame.clone();
+                }
+            }
+        }
+    }
 
-    unused_strings
+    blames
 }
 
-/// A simple program that reads an strings.xml file and strips
-/// elements matching the given name out without disrupting the rest
-/// of the file.
 fn main() -> Result<()> {
-    let opt = Opt::parse()?;
+    run(Opt::parse()?)
+}
+
+/// Runs the command given on the command line.
+fn run(opt: Opt) -> Result<()> {
+    let _trace = opt.trace_output.as_deref().map(trace::start).transpose()?;
+    if let Some(path) = &opt.output {
+        output::to_file(path)?;
+    }
+    // Completions and schemas don't depend on the roots every other command
+    // needs.
+    match &opt.subcommand {
+        Subcommand::Completions { shell } => print_completions(*shell)?,
+        Subcommand::Schema { command } => print_schema(command)?,
+        _ => {}
+    }
+    if !opt.subcommand.needs_roots() {
+        output::Stdout.flush()?;
+        return Ok(());
+    }
 
-    let indexer = index::Indexer::new(
-        opt.java_root,
-        opt.res_root,
+    let mut indexer = index::Indexer::new(
+        opt.java_root.expect("checked by Opt::parse"),
+        opt.res_root.expect("checked by Opt::parse"),
         opt.manifest_root,
         opt.cache_dir,
     )?;
+    indexer
+        .progress(match opt.progress {
+            progress::ProgressMode::Auto if opt.plain => progress::ProgressMode::None,
+            mode => mode,
+        })
+        .backend(opt.index_backend)
+        .per_directory_cache(opt.cache_per_directory)
+        .stale(opt.stale)
+        .include_generated(opt.include_generated)
+        .no_ignore(opt.no_ignore)
+        .hidden(opt.hidden)
+        .vcs_only(opt.vcs_only)
+        .follow_symlinks(opt.follow_symlinks)
+        .include_comments(opt.include_comments)
+        .max_filesize(opt.max_filesize)
+        .verbose(opt.verbose)
+        .quiet(opt.plain)
+        .strict(opt.strict)
+        .ignore_public(opt.ignore_public);
+    if let Some(remote) = opt.remote_cache {
+        indexer.remote_cache(remote);
+    }
+    if let Some(threads) = opt.threads {
+        indexer.threads(threads);
+    }
+    let mut keep = keep::KeepList::find(indexer.res_root())?;
+    for path in &opt.external_consumers {
+        keep.extend(consumers::read_string_names(path)?);
+    }
+    for prefix in &opt.keep_prefixes {
+        keep.keep_prefix(prefix);
+    }
 
     match opt.subcommand {
-        Subcommand::Index {} => {
-            let index = indexer.index()?;
-            indexer.serialize(&index)?;
-        }
-        Subcommand::Counts { .. } => {
+        Subcommand::Index {
+            action: Some(IndexAction::Export { json }),
+            ..
+        } => {
             let index = indexer.deserialize()?;
-            println!("{} defined strings", index.defined_strings().len());
-            println!("{} used strings", index.used_strings().len());
-            println!("{} unused strings", filtered_unused_strings(&index).len());
+            let mut out = BufWriter::new(
+                File::create(&json)
+                    .map_err(|e| anyhow::anyhow!("Unable to create {}: {}", json.display(), e))?,
+            );
+            let count = index_json::export(&index, &mut out)?;
+            out.flush()?;
+            outln!("Exported {} files to {}", count, json.display());
+        }
+        Subcommand::Index {
+            action: Some(IndexAction::Import { fragment }),
+            ..
+        } => {
+            let count = indexer.import(&fragment)?;
+            outln!("Imported {} files from {}", count, fragment.display());
         }
-        Subcommand::LsUnused { show_location } => {
+        Subcommand::Index {
+            action: Some(IndexAction::Merge { shards, output }),
+            ..
+        } => {
+            let count = indexer.merge(&shards, &output)?;
+            outln!(
+                "Merged {} files from {} shards into {}",
+                count,
+                shards.len(),
+                output.display()
+            );
+        }
+        Subcommand::Index {
+            action: Some(IndexAction::Status {}),
+            ..
+        } => print_index_status(&indexer)?,
+        Subcommand::Index {
+            action: Some(IndexAction::Update { files }),
+            ..
+        } => {
+            let (updated, removed) = indexer.update_files(&files)?;
+            outln!("Updated {} files, removed {}", updated, removed);
+        }
+        Subcommand::Index {
+            shard,
+            shard_output: Some(output),
+            action: None,
+            ..
+        } => {
+            let index = indexer.shard(&shard)?.index()?;
+            indexer.write_index(&index, &output)?;
+            outln!(
+                "Wrote a shard of {} files to {}",
+                index.files().len(),
+                output.display()
+            );
+        }
+        Subcommand::Index {
+            fetch,
+            push,
+            action: None,
+            ..
+        } => {
+            if indexer.is_per_directory_cache() {
+                if fetch || push {
+                    return Err(anyhow::anyhow!(
+                        "The remote cache holds whole indexes, not --cache-per-directory fragments"
+                    ));
+                }
+                let update = fragments::update(&indexer)?;
+                outln!(
+                    "Reindexed {} directories, kept {}, removed {}",
+                    update.reindexed,
+                    update.kept,
+                    update.removed
+                );
+            } else if !(fetch && indexer.fetch()?) {
+                let index = indexer.index()?;
+                indexer.serialize(&index)?;
+            }
+            if push {
+                indexer.push()?;
+            }
+        }
+        Subcommand::Counts {
+            fail_if_unused_over,
+            fail_if_unused_pct_over,
+        } if !opt.variants.is_empty() => {
             let index = indexer.deserialize()?;
+            let reported = |unused: HashSet<Symbol>| {
+                unused
+                    .into_iter()
+                    .filter(|s| is_reported(&keep, index.name(*s)))
+                    .count()
+            };
+            let defined = index.defined_strings().len();
+            let unused = reported(variant::unused_strings(&index, &opt.variants));
+            outln!("{} defined strings", defined);
+            outln!("{} used strings", index.used_strings().len());
+            outln!("{} unused strings in every variant", unused);
+            for variant in &opt.variants {
+                outln!(
+                    "{} unused strings in {}",
+                    reported(variant::unused_in_variant(&index, variant)),
+                    variant.name
+                );
+            }
+            check_unused_budget(
+                unused,
+                defined,
+                fail_if_unused_over,
+                fail_if_unused_pct_over,
+            )?;
+        }
+        Subcommand::Counts {
+            fail_if_unused_over,
+            fail_if_unused_pct_over,
+        } => {
+            let index = indexer.open()?;
+            let defined = index.defined_count()?;
+            let unused = index
+                .unused_strings()?
+                .iter()
+                .filter(|(name, _)| is_reported(&keep, name))
+                .count();
+            outln!("{} defined strings", defined);
+            outln!("{} used strings", index.used_count()?);
+            outln!("{} unused strings", unused);
+            check_unused_budget(
+                unused,
+                defined,
+                fail_if_unused_over,
+                fail_if_unused_pct_over,
+            )?;
+        }
+        Subcommand::LsUnused {
+            show_location,
+            group_by,
+            blame,
+            older_than,
+            include_test_only,
+            format,
+            kind,
+            filter,
+        } => {
+            let view = indexer.open()?;
+            let needs_index = filter.needs_values()
+                || blame
+                || older_than.is_some()
+                || include_test_only
+                || !opt.variants.is_empty();
+            if needs_index && kind != index::ResourceKind::String {
+                return Err(anyhow::anyhow!(
+                    "Only strings can be listed by value, blame, variant or test usage"
+                ));
+            }
+            let index = if needs_index {
+                Some(view.load()?)
+            } else {
+                None
+            };
+            let mut unused = if let Some(index) = &index {
+                let unused_strings = variant::unused_strings(index, &opt.variants);
+                definition_files(index, &unused_strings, &filter)
+            } else {
+                let mut unused = match kind {
+                    index::ResourceKind::String => view.unused_strings()?,
+                    kind => view.load()?.unused_resources(kind),
+                };
+                for (_, locations) in unused.iter_mut() {
+                    locations.retain(|loc| filter.matches_file(loc));
+                }
+                unused
+            };
+            let mut test_only = match &index {
+                Some(index) if include_test_only => {
+                    definition_files(index, &index.test_only_strings(), &filter)
+                }
+                _ => Vec::new(),
+            };
+            for strings in [&mut unused, &mut test_only] {
+                strings.retain(|(name, locations)| {
+                    is_reported(&keep, name) && filter.matches(name) && !locations.is_empty()
+                });
+                strings.sort();
+            }
+            let blames = match &index {
+                Some(index) if blame || older_than.is_some() => {
+                    let mut blames = blame_strings(index, &unused);
+                    blames.extend(blame_strings(index, &test_only));
+                    blames
+                }
+                _ => HashMap::new(),
+            };
+            if let Some(older_than) = older_than {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs() as i64);
+                for strings in [&mut unused, &mut test_only] {
+                    strings.retain(|(name, _)| {
+                        blames
+                            .get(name)
+                            .is_some_and(|blame| now - blame.time >= older_than)
+                    });
+                }
+            }
+
+            // The strings in each group with --group-by, or all of them.
+            type Strings = Vec<(String, Vec<String>)>;
+            let grouped = |strings: Strings| -> Result<Vec<(Option<String>, Strings)>> {
+                Ok(match &group_by {
+                    Some(group_by) => group_by
+                        .group(indexer.res_root(), strings)?
+                        .into_iter()
+                        .map(|(group, strings)| (Some(group), strings))
+                        .collect(),
+                    None => vec![(None, strings)],
+                })
+            };
+
+            if format != output::ListFormat::Text {
+                let mut listed = Vec::new();
+                for (strings, test_only) in [(unused, false), (test_only, true)] {
+                    let groups = grouped(strings)?;
+                    for (group, strings) in &groups {
+                        for (name, locations) in strings {
+                            let listing = UnusedListing {
+                                name,
+                                locations,
+                                group: group.as_deref(),
+                                blame: blames.get(name).filter(|_| blame),
+                                test_only,
+                            };
+                            match format {
+                                output::ListFormat::Ndjson => {
+                                    outln!("{}", serde_json::to_string(&listing)?)
+                                }
+                                _ => listed.push(serde_json::to_value(&listing)?),
+                            }
+                        }
+                    }
+                }
+                if format == output::ListFormat::Json {
+                    outln!("{}", serde_json::to_string_pretty(&listed)?);
+                }
+                return Ok(());
+            }
+
+            if opt.plain {
+                // One row per string, or per location with -s, with the group
+                // and blame as extra columns, and strings used only from tests
+                // marked with a last test-only column.
+                for (strings, test_only) in [(unused, false), (test_only, true)] {
+                    for (group, strings) in grouped(strings)? {
+                        for (name, locations) in strings {
+                            let mut columns: Vec<String> = group.iter().cloned().collect();
+                            columns.push(name.clone());
+                            if let Some(blame) = blames.get(&name).filter(|_| blame) {
+                                columns.push(blame.date());
+                                columns.push(blame.commit[..8].to_string());
+                                columns.push(blame.author.clone());
+                            }
+                            if test_only {
+                                columns.push("test-only".to_string());
+                            }
+                            if show_location {
+                                for loc in locations {
+                                    outln!("{}\t{}", columns.join("\t"), paths::display(&loc));
+                                }
+                            } else {
+                                outln!("{}", columns.join("\t"));
+                            }
+                        }
+                    }
+                }
+                return Ok(());
+            }
 
-            let files_for_definition = index.files_for_definition();
+            let print = |unused: Vec<(String, Vec<String>)>, indent: &str| {
+                for (name, locations) in unused {
+                    match blames.get(&name).filter(|_| blame) {
+                        Some(blame) => outln!(
+                            "{}{}  {} {} {}",
+                            indent,
+                            name,
+                            blame.date(),
+                            &blame.commit[..8],
+                            blame.author
+                        ),
+                        None => outln!("{}{}", indent, name),
+                    }
+                    if show_location {
+                        for loc in locations {
+                            outln!("{}  {}", indent, paths::display(&loc));
+                        }
+                    }
+                }
+            };
 
-            for unused in filtered_unused_strings(&index) {
-                println!("{}", unused);
-                if show_location {
-                    for loc in files_for_definition.get_vec(unused).unwrap() {
-                        println!("  {}", loc);
+            let print_all = |strings: Vec<(String, Vec<String>)>, indent: &str| -> Result<()> {
+                match &group_by {
+                    Some(group_by) => {
+                        for (group, strings) in group_by.group(indexer.res_root(), strings)? {
+                            outln!("{}{} ({})", indent, group, strings.len());
+                            print(strings, &format!("{}  ", indent));
+                        }
                     }
+                    None => print(strings, indent),
                 }
+                Ok(())
+            };
+            print_all(unused, "")?;
+            if include_test_only {
+                outln!("Used only from tests ({}):", test_only.len());
+                print_all(test_only, "  ")?;
             }
         }
-        Subcommand::RmUnused { prefix } => {
+        Subcommand::RmUnused {
+            prefix,
+            commit,
+            branch,
+            remove_comments,
+            collapse_blank_lines,
+            prune_empty_files,
+            limit,
+            batch_size,
+            summary_file,
+            backup,
+            backup_dir,
+            kind,
+            filter,
+        } => {
+            if summary_file.is_some() && kind != index::ResourceKind::String {
+                return Err(anyhow::anyhow!("Only string removals can be summarized"));
+            }
+            if filter.needs_values() && kind != index::ResourceKind::String {
+                return Err(anyhow::anyhow!("Only strings can be removed by value"));
+            }
             let index = indexer.deserialize()?;
-            let files_for_definition = index.files_for_definition();
 
             let prefix = match prefix {
                 Some(prefix) => prefix,
                 None => "".to_string(),
             };
 
-            for unused in filtered_unused_strings(&index) {
-                if unused.starts_with(&prefix) {
-                    let mut matcher = xeditor::ElementMatcher::for_local_name("string");
-                    matcher.attr("name", unused);
+            let mut unused: Vec<Symbol> = filtered_unused_strings(&index, &opt.variants, &keep)
+                .into_iter()
+                .filter(|unused| {
+                    let name = index.name(*unused);
+                    name.starts_with(&prefix) && filter.matches(name)
+                })
+                .collect();
+            let limit = limit.unwrap_or(usize::MAX);
+            // How many unused resources --limit leaves for later runs.
+            let left_over = |unused: usize| {
+                if unused > limit {
+                    outln!(
+                        "Left {} more unused {}s, over the limit of {}",
+                        unused - limit,
+                        kind.as_str(),
+                        limit
+                    );
+                }
+            };
+            let repo = indexer.res_root();
+            if commit {
+                // Committing the removals would take along any changes
+                // already made to the same files.
+                if let Some(file) = git::dirty_files(repo)?.first() {
+                    return Err(anyhow::anyhow!(
+                        "{} has uncommitted changes, e.g. {}; commit or stash them first",
+                        repo.display(),
+                        file.display()
+                    ));
+                }
+            }
+            if let Some(branch) = &branch {
+                git::create_branch(repo, branch)?;
+            }
+            let options = xeditor::RemoveOptions {
+                remove_comments,
+                collapse_blank_lines,
+                prune_empty_files,
+            };
+            let backup_dir = match backup_dir {
+                Some(dir) => Some(dir),
+                None if backup => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                    Some(indexer.cache_dir().join("backups").join(now.to_string()))
+                }
+                None => None,
+            };
+            let mut backup = backup_dir
+                .map(|dir| backup::Backup::new(dir, indexer.path_roots()))
+                .transpose()?;
+            if kind != index::ResourceKind::String {
+                let mut unused = index.unused_resources(kind);
+                unused.retain(|(name, _)| {
+                    name.starts_with(&prefix) && filter.matches(name) && is_reported(&keep, name)
+                });
+                let found = unused.len();
+                unused.truncate(limit);
+                let batches: Vec<&[(String, Vec<String>)]> = match batch_size {
+                    Some(batch_size) => unused.chunks(batch_size.max(1)).collect(),
+                    None => vec![&unused],
+                };
+                for (i, batch) in batches.iter().enumerate() {
+                    let modified =
+                        remove_resources(kind, batch, &filter, &options, backup.as_mut())?;
+                    if commit && !modified.is_empty() {
+                        let names = batch.iter().map(|(name, _)| name.as_str());
+                        let message = removal_message(kind, names, i, batches.len());
+                        git::commit(repo, &modified, &message)?;
+                    } else if batch_size.is_some() {
+                        outln!("Batch {} ({} {}s)", i + 1, batch.len(), kind.as_str());
+                        for path in modified {
+                            outln!("  {}", path.display());
+                        }
+                    }
+                }
+                if let Some(backup) = &backup {
+                    eprintln!("Backed up changed files to {}", backup.dir().display());
+                }
+                left_over(found);
+                return Ok(());
+            }
+            let found = unused.len();
+            unused.truncate(limit);
+            let batches: Vec<&[Symbol]> = match batch_size {
+                Some(batch_size) => unused.chunks(batch_size.max(1)).collect(),
+                None => vec![&unused],
+            };
+            for (i, batch) in batches.iter().enumerate() {
+                let modified = remove_strings(&index, batch, &filter, &options, backup.as_mut())?;
+                if commit && !modified.is_empty() {
+                    let names = batch.iter().map(|symbol| index.name(*symbol));
+                    let message = removal_message(kind, names, i, batches.len());
+                    git::commit(repo, &modified, &message)?;
+                } else if batch_size.is_some() {
+                    outln!("Batch {} ({} strings)", i + 1, batch.len());
+                    for path in modified {
+                        outln!("  {}", path.display());
+                    }
+                }
+            }
+            if let Some(backup) = &backup {
+                eprintln!("Backed up changed files to {}", backup.dir().display());
+            }
+            match summary_file {
+                Some(path) if path == Path::new("-") => {
+                    summary::write_summary(&mut output::Stdout, &index, &unused, &filter)?
+                }
+                Some(path) => {
+                    let mut file = BufWriter::new(File::create(path)?);
+                    summary::write_summary(&mut file, &index, &unused, &filter)?;
+                    file.flush()?;
+                }
+                None => {}
+            }
+            left_over(found);
+        }
+        Subcommand::Split { by_prefix, into } => {
+            let index = indexer.deserialize()?;
+            let moved = split::split_by_prefix(&index, &by_prefix, &into)?;
+            outln!("Moved {} strings", moved);
+        }
+        Subcommand::ExtractHardcoded { dry_run } => {
+            let index = indexer.deserialize()?;
+            let extracted = extract::extract_hardcoded(&index, indexer.res_root(), dry_run)?;
+            outln!("Extracted {} hardcoded strings", extracted);
+        }
+        Subcommand::SweepTranslations {
+            prune_empty_files,
+            dry_run,
+        } => {
+            let index = indexer.deserialize()?;
+            let orphaned: Vec<(Symbol, Vec<String>)> = sweep::orphaned_translations(&index)
+                .into_iter()
+                .filter(|(name, _)| is_reported(&keep, index.name(*name)))
+                .collect();
+            for (name, files) in &orphaned {
+                outln!("{}", index.name(*name));
+                for file in files {
+                    outln!("  {}", paths::display(file));
+                }
+            }
+            let names: Vec<Symbol> = orphaned.iter().map(|(name, _)| *name).collect();
+            if dry_run {
+                outln!("Would remove {} strings", names.len());
+            } else {
+                let options = xeditor::RemoveOptions {
+                    prune_empty_files,
+                    ..Default::default()
+                };
+                remove_strings(&index, &names, &filter::Filter::default(), &options, None)?;
+                outln!("Removed {} strings", names.len());
+            }
+        }
+        Subcommand::Tidy {
+            max_blank_lines,
+            dry_run,
+        } => {
+            let index = indexer.deserialize()?;
+            let options = xeditor::TidyOptions {
+                max_blank_lines,
+                dry_run,
+            };
+            let removed = tidy::tidy(&index, &options)?;
+            if dry_run {
+                outln!("Would tidy {} places", removed);
+            } else {
+                outln!("Tidied {} places", removed);
+            }
+        }
+        Subcommand::Tui {} => {
+            let index = indexer.deserialize()?;
+            let to_delete = tui::review(
+                &index,
+                filtered_unused_strings(&index, &opt.variants, &keep),
+            )?;
+            remove_strings(
+                &index,
+                &to_delete,
+                &filter::Filter::default(),
+                &xeditor::RemoveOptions::default(),
+                None,
+            )?;
+            outln!("Deleted {} strings", to_delete.len());
+        }
+        Subcommand::Stats(Stats::Usage { sort }) => {
+            let index = indexer.deserialize()?;
+            let files_for_usage = index.files_for_usage();
+
+            let mut counts: Vec<(&str, usize)> = index
+                .defined_strings()
+                .into_iter()
+                .map(|s| {
+                    (
+                        index.name(s),
+                        files_for_usage.get_vec(&s).map_or(0, Vec::len),
+                    )
+                })
+                .collect();
+            match sort.as_str() {
+                "desc" => counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0))),
+                "name" => counts.sort(),
+                _ => counts.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0))),
+            }
 
-                    for loc in files_for_definition.get_vec(unused).unwrap() {
-                        xeditor::remove_element(Path::new(loc), &matcher)?;
+            for (name, count) in counts {
+                outln!("{:>6} {}", count, name);
+            }
+        }
+        Subcommand::Stats(Stats::Prometheus { output }) => {
+            let index = indexer.deserialize()?;
+            let unused = filtered_unused_strings(&index, &opt.variants, &keep)
+                .into_iter()
+                .collect();
+            match output {
+                Some(path) => metrics::write_file(&index, &unused, &path)?,
+                None => metrics::write(&index, &unused, &mut output::Stdout)?,
+            }
+        }
+        Subcommand::Bench {
+            iterations,
+            save,
+            baseline,
+        } => {
+            let baseline = baseline
+                .map(|path| bench::Report::read(&path))
+                .transpose()?;
+            let report = bench::run(&indexer, iterations)?;
+            bench::print(&report, baseline.as_ref(), &mut output::Stdout)?;
+            if let Some(path) = save {
+                report.write(&path)?;
+            }
+        }
+        Subcommand::Cache(Cache::Ls {}) => {
+            cache::ls(indexer.cache_dir(), &indexer.cache_file())?;
+        }
+        Subcommand::Cache(Cache::Clean {}) => cache::clean(indexer.cache_dir())?,
+        Subcommand::Cache(Cache::Gc { max_size }) => cache::gc(indexer.cache_dir(), max_size)?,
+        Subcommand::Diff { old, new, format } => {
+            let old = index::open_index_file(&old)?.load()?;
+            let new = index::open_index_file(&new)?.load()?;
+            diff::print(&old, &new, |name| is_reported(&keep, name), format)?;
+        }
+        Subcommand::Verify { apk, format } => {
+            let index = indexer.deserialize()?;
+            let unused = filtered_unused_strings(&index, &opt.variants, &keep);
+            let built = apk::string_names(&apk)?;
+            verify::print(&verify::verify(&index, &unused, &built), format)?;
+        }
+        Subcommand::Search {
+            text,
+            regex,
+            ignore_case,
+            format,
+        } => {
+            let index = indexer.deserialize()?;
+            let mut pattern = if regex { text } else { regex::escape(&text) };
+            if ignore_case {
+                pattern = format!("(?i){}", pattern);
+            }
+            search::search(&index, &regex::Regex::new(&pattern)?, format)?;
+        }
+        Subcommand::Show { name, format } => {
+            let index = indexer.deserialize()?;
+            show::show(&index, &name, format)?;
+        }
+        Subcommand::Export {
+            xliff,
+            json_localization,
+            dot,
+            name,
+            prefix,
+            locale,
+            source_locale,
+            xliff_version,
+            output,
+        } => {
+            let index = indexer.deserialize()?;
+            let mut out: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(output::Stdout),
+            };
+            let count = match (xliff, locale) {
+                (true, Some(locale)) => xliff::export(
+                    &index,
+                    indexer.res_root(),
+                    &locale,
+                    &source_locale,
+                    xliff_version,
+                    &mut out,
+                )?,
+                _ if json_localization => json_localization::export(&index, &mut out)?,
+                _ if dot => dot::export(
+                    &index,
+                    &[indexer.java_root(), indexer.res_root()],
+                    |string| match (&name, &prefix) {
+                        (Some(name), _) => string == name,
+                        (_, Some(prefix)) => string.starts_with(prefix.as_str()),
+                        _ => true,
+                    },
+                    &mut out,
+                )?,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Choose an export format, --xliff, --json-localization or --dot"
+                    ))
+                }
+            };
+            out.flush()?;
+            eprintln!("Exported {} strings", count);
+        }
+        Subcommand::Import {
+            xliff,
+            locale,
+            json_localization,
+            overwrite_default,
+        } => {
+            let files = match (xliff, json_localization) {
+                (Some(xliff), _) => xliff::import(indexer.res_root(), &xliff, locale.as_deref())?,
+                (None, Some(json)) => {
+                    let index = indexer.deserialize()?;
+                    let import = json_localization::import(&index, &json, overwrite_default)?;
+                    if import.defaults_skipped > 0 {
+                        eprintln!(
+                            "Left {} default strings alone, use --overwrite-default to write them",
+                            import.defaults_skipped
+                        );
+                    }
+                    if !import.skipped.is_empty() {
+                        eprintln!(
+                            "Skipped {} strings with no default definition: {}",
+                            import.skipped.len(),
+                            import.skipped.join(", ")
+                        );
                     }
+                    import.files
                 }
+                (None, None) => unreachable!(),
+            };
+            for imported in files {
+                outln!(
+                    "{}: updated {}, added {}",
+                    imported.path.display(),
+                    imported.updated,
+                    imported.added
+                );
             }
         }
+        Subcommand::Pseudo { locale } => {
+            let index = indexer.deserialize()?;
+            for generated in pseudo::generate(&index, &locale)? {
+                outln!(
+                    "{}: updated {}, added {}",
+                    generated.path.display(),
+                    generated.updated,
+                    generated.added
+                );
+            }
+        }
+        Subcommand::Completions { .. } | Subcommand::Schema { .. } => {
+            unreachable!("printed before the roots are needed")
+        }
+        Subcommand::Lint(Lint::HardcodedText { format }) => {
+            lint::hardcoded_text(indexer.res_root(), indexer.manifest_root(), format)?;
+        }
+        Subcommand::Lint(Lint::Names { convention, format }) => {
+            let index = indexer.deserialize()?;
+            lint::names(&index, &convention, format)?;
+        }
+        Subcommand::Lint(Lint::Escaping { format }) => {
+            let index = indexer.deserialize()?;
+            lint::escaping(&index, format)?;
+        }
+        Subcommand::Lint(Lint::Conflicts { format }) => {
+            let index = indexer.deserialize()?;
+            lint::conflicts(&index, format)?;
+        }
     }
+    output::Stdout.flush()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    /// An indexed project with one used string and two unused ones, one
+    /// translated.
+    fn project(name: &str) -> Result<TempDir> {
+        let tmp_dir = TempDir::new(name)?;
+        let java_dir = tmp_dir.path().join("src/com/example");
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(&java_dir)?;
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-fr"))?;
+        fs::write(
+            java_dir.join("Main.java"),
+            "class Main { int id = R.string.used; }",
+        )?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources>
+    <string name="used">Used</string>
+    <string name="unused_a">A</string>
+    <string name="unused_b">B</string>
+</resources>
+"#,
+        )?;
+        fs::write(
+            res_dir.join("values-fr/strings.xml"),
+            r#"<resources>
+    <string name="unused_a">A</string>
+</resources>
+"#,
+        )?;
+        art(&tmp_dir, &["index"])?;
+        Ok(tmp_dir)
+    }
+
+    /// Runs art on the project in `tmp_dir` with `args`, returning what it
+    /// printed.
+    fn art(tmp_dir: &TempDir, args: &[&str]) -> Result<String> {
+        let root = tmp_dir.path();
+        let mut argv = vec!["art".into(), "-j".into(), root.join("src")];
+        argv.extend(["-r".into(), root.join("res")]);
+        argv.extend(["--cache-dir".into(), root.join("cache")]);
+        argv.extend(args.iter().map(PathBuf::from));
+        let opt = Opt::from_iter_safe(argv)?;
+        output::capture(|| run(opt))
+    }
+
+    #[test]
+    fn test_ls_unused_ndjson() -> Result<()> {
+        let tmp_dir = project("ndjson")?;
+        let text = art(&tmp_dir, &["ls-unused"])?;
+        let ndjson = art(&tmp_dir, &["ls-unused", "--format", "ndjson"])?;
+
+        let listed: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<serde_json::Result<_>>()?;
+        assert_eq!(listed.len(), text.lines().count());
+        assert_eq!(listed[0]["name"], "unused_a");
+        assert_eq!(listed[0]["locations"].as_array().map(Vec::len), Some(2));
+        assert_eq!(listed[1]["name"], "unused_b");
+
+        let json: Vec<serde_json::Value> =
+            serde_json::from_str(&art(&tmp_dir, &["ls-unused", "--format", "json"])?)?;
+        assert_eq!(json, listed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rm_unused_limit() -> Result<()> {
+        let tmp_dir = project("limit")?;
+        let strings = tmp_dir.path().join("res/values/strings.xml");
+        let fr = tmp_dir.path().join("res/values-fr/strings.xml");
+
+        assert_eq!(
+            art(&tmp_dir, &["rm-unused", "--limit", "1"])?,
+            "Left 1 more unused strings, over the limit of 1\n"
+        );
+        let content = fs::read_to_string(&strings)?;
+        assert!(!content.contains("unused_a"));
+        assert!(content.contains("unused_b"));
+        assert!(!fs::read_to_string(&fr)?.contains("unused_a"));
+
+        art(&tmp_dir, &["index"])?;
+        assert_eq!(art(&tmp_dir, &["rm-unused", "--limit", "1"])?, "");
+        let content = fs::read_to_string(&strings)?;
+        assert!(!content.contains("unused_b"));
+        assert!(content.contains("\"used\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_in_file() -> Result<()> {
+        let tmp_dir = project("in_file")?;
+        let strings = tmp_dir.path().join("res/values/strings.xml");
+        let fr = tmp_dir.path().join("res/values-fr/strings.xml");
+        let listed = |file: &Path| {
+            art(
+                &tmp_dir,
+                &[
+                    "--plain",
+                    "ls-unused",
+                    "-s",
+                    "--file",
+                    &file.to_string_lossy(),
+                ],
+            )
+        };
+
+        let expected = format!("unused_a\t{}\n", fr.display());
+        assert_eq!(listed(&fr)?, expected);
+        // The same file by a path that isn't canonical, or relative to the
+        // working directory.
+        assert_eq!(
+            listed(&tmp_dir.path().join("res/values/../values-fr/strings.xml"))?,
+            expected
+        );
+        #[cfg(unix)]
+        {
+            let mut relative = PathBuf::new();
+            for _ in std::env::current_dir()?.components().skip(1) {
+                relative.push("..");
+            }
+            relative.push(fr.strip_prefix("/")?);
+            assert_eq!(listed(&relative)?, expected);
+        }
+
+        art(&tmp_dir, &["rm-unused", "--file", &fr.to_string_lossy()])?;
+        assert!(!fs::read_to_string(&fr)?.contains("unused_a"));
+        let content = fs::read_to_string(&strings)?;
+        assert!(content.contains("unused_a"));
+        assert!(content.contains("unused_b"));
+
+        Ok(())
+    }
+
+    /// Runs git in `dir`, returning its output.
+    fn git(dir: &Path, args: &[&str]) -> Result<String> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()?;
+        assert!(output.status.success(), "git {:?} failed", args);
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// `project` with everything committed to a git repository.
+    fn git_project(name: &str) -> Result<TempDir> {
+        let tmp_dir = project(name)?;
+        let root = tmp_dir.path();
+        fs::write(root.join(".gitignore"), "cache/\n")?;
+        git(root, &["init", "-q", "-b", "main"])?;
+        git(root, &["config", "user.name", "Test"])?;
+        git(root, &["config", "user.email", "test@example.com"])?;
+        git(root, &["add", "."])?;
+        git(root, &["commit", "-q", "-m", "Initial"])?;
+        Ok(tmp_dir)
+    }
+
+    #[test]
+    fn test_rm_unused_commit_on_branch() -> Result<()> {
+        let tmp_dir = git_project("commit")?;
+        let root = tmp_dir.path();
+        let strings = root.join("res/values/strings.xml");
+
+        // Changes to the files it would commit stop it before it branches.
+        let original = fs::read_to_string(&strings)?;
+        fs::write(&strings, original.replace("Used", "Changed"))?;
+        let error = art(&tmp_dir, &["rm-unused", "--commit", "--branch", "cleanup"])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("uncommitted changes"), "{}", error);
+        assert_eq!(git(root, &["branch", "--show-current"])?, "main\n");
+        assert!(fs::read_to_string(&strings)?.contains("unused_a"));
+
+        fs::write(&strings, original)?;
+        art(&tmp_dir, &["rm-unused", "--commit", "--branch", "cleanup"])?;
+        assert_eq!(git(root, &["branch", "--show-current"])?, "cleanup\n");
+        assert_eq!(
+            git(root, &["log", "-1", "--format=%B"])?,
+            "Remove 2 unused strings\n\nunused_a\nunused_b\n\n"
+        );
+        assert_eq!(git(root, &["status", "--porcelain"])?, "");
+        assert_eq!(git(root, &["rev-list", "--count", "main..cleanup"])?, "1\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rm_unused_batches() -> Result<()> {
+        let tmp_dir = git_project("batches")?;
+        let root = tmp_dir.path();
+        art(&tmp_dir, &["rm-unused", "--commit", "--batch-size", "1"])?;
+        assert_eq!(
+            git(root, &["log", "--format=%s"])?,
+            "Remove 1 unused strings (batch 2 of 2)\n\
+             Remove 1 unused strings (batch 1 of 2)\n\
+             Initial\n"
+        );
+        assert_eq!(
+            git(root, &["show", "--name-only", "--format=", "HEAD~"])?,
+            "res/values-fr/strings.xml\nres/values/strings.xml\n"
+        );
+
+        // Without --commit each batch lists the files it changed.
+        let tmp_dir = project("batches")?;
+        let strings = tmp_dir.path().join("res/values/strings.xml");
+        let fr = tmp_dir.path().join("res/values-fr/strings.xml");
+        let listed = art(&tmp_dir, &["rm-unused", "--batch-size", "1"])?;
+        let mut lines: Vec<String> = listed.lines().map(str::to_string).collect();
+        lines[1..3].sort();
+        let file = |path: &Path| format!("  {}", path.display());
+        assert_eq!(
+            lines,
+            [
+                "Batch 1 (1 strings)".to_string(),
+                file(&fr),
+                file(&strings),
+                "Batch 2 (1 strings)".to_string(),
+                file(&strings),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rm_unused_resource_batches() -> Result<()> {
+        let tmp_dir = project("resource_batches")?;
+        let colors = tmp_dir.path().join("res/values/colors.xml");
+        fs::write(
+            &colors,
+            r#"<resources>
+    <color name="red">#f00</color>
+    <color name="blue">#00f</color>
+</resources>
+"#,
+        )?;
+        art(&tmp_dir, &["index"])?;
+
+        let error = art(
+            &tmp_dir,
+            &["rm-unused", "--kind", "color", "--summary-file", "-"],
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Only string removals can be summarized");
+        assert!(fs::read_to_string(&colors)?.contains("red"));
+        let error = art(
+            &tmp_dir,
+            &["rm-unused", "--kind", "color", "--value-regex", "f00"],
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Only strings can be removed by value");
+        assert!(fs::read_to_string(&colors)?.contains("blue"));
+
+        let file = format!("  {}", colors.display());
+        assert_eq!(
+            art(
+                &tmp_dir,
+                &["rm-unused", "--kind", "color", "--batch-size", "1"]
+            )?,
+            format!(
+                "Batch 1 (1 colors)\n{}\nBatch 2 (1 colors)\n{}\n",
+                file, file
+            )
+        );
+        assert!(!fs::read_to_string(&colors)?.contains("<color"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_usage() -> Result<()> {
+        let tmp_dir = project("usage")?;
+        fs::write(
+            tmp_dir.path().join("src/com/example/Other.java"),
+            "class Other { int a = R.string.used; int b = R.string.unused_a; }",
+        )?;
+        art(&tmp_dir, &["index"])?;
+
+        let usage = |sort: &str| art(&tmp_dir, &["stats", "usage", "--sort", sort]);
+        assert_eq!(
+            usage("asc")?,
+            "     0 unused_b\n     1 unused_a\n     2 used\n"
+        );
+        assert_eq!(
+            usage("desc")?,
+            "     2 used\n     1 unused_a\n     0 unused_b\n"
+        );
+        assert_eq!(
+            usage("name")?,
+            "     1 unused_a\n     0 unused_b\n     2 used\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_name_regex() -> Result<()> {
+        let tmp_dir = project("name_regex")?;
+        assert_eq!(
+            art(&tmp_dir, &["ls-unused", "--name-regex", "_b$"])?,
+            "unused_b\n"
+        );
+        assert_eq!(art(&tmp_dir, &["ls-unused", "--name-regex", "^used$"])?, "");
+
+        art(&tmp_dir, &["rm-unused", "--name-regex", "_b$"])?;
+        let content = fs::read_to_string(tmp_dir.path().join("res/values/strings.xml"))?;
+        assert!(content.contains("unused_a"));
+        assert!(!content.contains("unused_b"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unused_value_regex() -> Result<()> {
+        let tmp_dir = project("value_regex")?;
+        let fr = tmp_dir.path().join("res/values-fr/strings.xml");
+        fs::write(
+            &fr,
+            r#"<resources><string name="unused_a">Ä</string></resources>"#,
+        )?;
+        art(&tmp_dir, &["index"])?;
+
+        // Only definitions with matching values count.
+        let strings = tmp_dir.path().join("res/values/strings.xml");
+        assert_eq!(
+            art(
+                &tmp_dir,
+                &["--plain", "ls-unused", "-s", "--value-regex", "^Ä$"]
+            )?,
+            format!("unused_a\t{}\n", fr.display())
+        );
+        assert_eq!(
+            art(
+                &tmp_dir,
+                &["--plain", "ls-unused", "-s", "--value-regex", "^A$"]
+            )?,
+            format!("unused_a\t{}\n", strings.display())
+        );
+
+        art(&tmp_dir, &["rm-unused", "--value-regex", "^[AB]$"])?;
+        assert!(!fs::read_to_string(&strings)?.contains("unused_"));
+        assert!(fs::read_to_string(&fr)?.contains("unused_a"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict() -> Result<()> {
+        let tmp_dir = project("strict")?;
+        fs::write(
+            tmp_dir.path().join("res/values/broken.xml"),
+            "<resources><string",
+        )?;
+
+        // A file that can't be indexed is only a warning without --strict,
+        // and an error, and so a failing exit, with it.
+        assert!(art(&tmp_dir, &["index"]).is_ok());
+        let error = art(&tmp_dir, &["--strict", "index"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1 files couldn't be indexed, failing because of --strict"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ls_unused_plain() -> Result<()> {
+        let tmp_dir = project("plain")?;
+        let test_dir = tmp_dir.path().join("src/test/java");
+        fs::create_dir_all(&test_dir)?;
+        fs::write(
+            test_dir.join("MainTest.java"),
+            "class MainTest { int id = R.string.unused_b; }",
+        )?;
+        art(&tmp_dir, &["index"])?;
+
+        assert_eq!(
+            art(&tmp_dir, &["--plain", "ls-unused", "--include-test-only"])?,
+            "unused_a\nunused_b\ttest-only\n"
+        );
+        let fr = tmp_dir.path().join("res/values-fr/strings.xml");
+        let located = art(
+            &tmp_dir,
+            &["--plain", "ls-unused", "-s", "--include-test-only"],
+        )?;
+        assert!(located
+            .lines()
+            .any(|line| line == format!("unused_a\t{}", fr.display())));
+        assert_eq!(located.lines().count(), 3);
+        assert!(located.lines().all(|line| !line.starts_with(' ')));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_variant() -> Result<()> {
+        let tmp_dir = project("counts_variant")?;
+        let output = art(&tmp_dir, &["--variant", "paid", "counts"])?;
+        assert_eq!(
+            output,
+            "3 defined strings\n1 used strings\n2 unused strings in every variant\n2 unused strings in paid\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_stamps() -> Result<()> {
+        let tmp_dir = project("cache_stamps")?;
+        let cache_dir = tmp_dir.path().join("cache");
+        let stamps = || -> Result<usize> {
+            Ok(fs::read_dir(&cache_dir)?
+                .filter(|entry| {
+                    entry.as_ref().is_ok_and(|entry| {
+                        entry.file_name().to_string_lossy().ends_with(".stamp.json")
+                    })
+                })
+                .count())
+        };
+        assert_eq!(stamps()?, 1);
+
+        // The stamp goes with its index, rather than being one itself.
+        let listed = art(&tmp_dir, &["cache", "ls"])?;
+        assert!(listed.contains("1 cached indexes"), "{}", listed);
+        assert_eq!(
+            art(&tmp_dir, &["cache", "clean"])?,
+            "Removed 1 cached indexes\n"
+        );
+        assert_eq!(stamps()?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_unused_budget() {
+        // Without a budget any number of unused strings is fine.
+        assert!(check_unused_budget(100, 100, None, None).is_ok());
+        assert!(check_unused_budget(0, 0, None, Some(0.0)).is_ok());
+
+        assert!(check_unused_budget(9, 100, Some(10), None).is_ok());
+        assert!(check_unused_budget(10, 100, Some(10), None).is_ok());
+        assert_eq!(
+            check_unused_budget(11, 100, Some(10), None)
+                .unwrap_err()
+                .to_string(),
+            "11 unused strings, over the limit of 10"
+        );
+
+        assert!(check_unused_budget(4, 100, None, Some(5.0)).is_ok());
+        assert!(check_unused_budget(5, 100, None, Some(5.0)).is_ok());
+        assert_eq!(
+            check_unused_budget(6, 100, None, Some(5.0))
+                .unwrap_err()
+                .to_string(),
+            "6.0% of strings are unused, over the limit of 5%"
+        );
+        // Either limit can fail it.
+        assert!(check_unused_budget(6, 100, Some(10), Some(5.0)).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! The on-disk index. Quick queries (counts, ls-unused) are answered straight
+//! from a memory map of a few flat tables at the start of the file; the full
+//! `ResourceIndex` follows as a bincode blob and is only decoded when needed.
+//!
+//! Layout (all integers little endian):
+//!
+//!   magic              8 bytes
+//!   version            u32 (absent in version 1 files)
+//!   symbol_count       u32
+//!   path_count         u32
+//!   location_count     u32
+//!   name_offsets       u32 * (symbol_count + 1), into the names blob
+//!   flags              u8 * symbol_count (DEFINED | USED | KEPT), where
+//!                      public strings count as kept
+//!   location_offsets   u32 * (symbol_count + 1), into locations
+//!   locations          u32 * location_count, indices of defining paths
+//!   path_offsets       u32 * (path_count + 1), into the paths blob
+//!   names              utf-8
+//!   paths              utf-8
+//!   index              bincode encoded ResourceIndex
+
+use crate::index::{IndexView, ResourceIndex};
+use crate::symbols::Symbol;
+use anyhow::{anyhow, Result};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::str;
+
+const MAGIC: &[u8; 8] = b"ART-IDX\0";
+
+/// Version 1 files predate the version field and used a different magic.
+const MAGIC_V1: &[u8; 8] = b"ARTINDEX";
+
+/// Bump this whenever the layout above or the encoding of `ResourceIndex`
+/// changes. Files from `OLDEST_READABLE_VERSION` onwards can still be read.
+/// Version 3 added keep annotations, which older files can't answer for.
+/// Version 4 added source sets to the encoded `ResourceIndex`, version 5
+/// public.xml declarations, version 6 resources other than strings, and
+/// version 7 style parents. Version 8 stores paths relative to their roots,
+/// which version 7 files, with absolute paths, read the same as.
+pub const FORMAT_VERSION: u32 = 8;
+const OLDEST_READABLE_VERSION: u32 = 7;
+
+/// The index file was written in a format this build can't read, and needs
+/// to be rebuilt.
+#[derive(Debug)]
+pub struct UnsupportedVersion(pub Option<u32>);
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(version) => write!(
+                f,
+                "index format version {} is not supported (expected {} to {})",
+                version, OLDEST_READABLE_VERSION, FORMAT_VERSION
+            ),
+            None => write!(f, "index file is in an unrecognized format"),
+        }
+    }
+}
+
+impl Error for UnsupportedVersion {}
+
+const DEFINED: u8 = 1;
+const USED: u8 = 2;
+const KEPT: u8 = 4;
+
+fn write_u32(out: &mut Vec<u8>, value: usize) -> Result<()> {
+    let value = u32::try_from(value).map_err(|_| anyhow!("Index is too large"))?;
+    out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+/// Writes a string table as offsets followed (later) by the concatenated
+/// strings.
+fn write_offsets<'a>(
+    out: &mut Vec<u8>,
+    strings: impl Iterator<Item = &'a str>,
+    blob: &mut Vec<u8>,
+) -> Result<()> {
+    write_u32(out, blob.len())?;
+    for string in strings {
+        blob.extend_from_slice(string.as_bytes());
+        write_u32(out, blob.len())?;
+    }
+    Ok(())
+}
+
+/// Writes `index` in the mapped format.
+pub fn write(index: &ResourceIndex, out: impl Write) -> Result<()> {
+    let mut out = out;
+    let symbol_count = index.symbol_count();
+    let symbols = || (0..symbol_count as u32).map(Symbol::from_id);
+
+    let files_for_definition = index.files_for_definition();
+    let mut path_ids: HashMap<&str, usize> = HashMap::new();
+    let mut paths = Vec::new();
+    let mut location_offsets = vec![0];
+    let mut locations = Vec::new();
+    for symbol in symbols() {
+        for path in files_for_definition.get_vec(&symbol).into_iter().flatten() {
+            let id = *path_ids.entry(path).or_insert_with(|| {
+                paths.push(*path);
+                paths.len() - 1
+            });
+            locations.push(id);
+        }
+        location_offsets.push(locations.len());
+    }
+
+    let defined = index.defined_strings();
+    let used = index.used_strings();
+    let kept = index.kept_strings();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    write_u32(&mut header, FORMAT_VERSION as usize)?;
+    write_u32(&mut header, symbol_count)?;
+    write_u32(&mut header, paths.len())?;
+    write_u32(&mut header, locations.len())?;
+
+    let mut names = Vec::new();
+    write_offsets(&mut header, symbols().map(|s| index.name(s)), &mut names)?;
+    for symbol in symbols() {
+        let mut flags = 0;
+        if defined.contains(&symbol) {
+            flags |= DEFINED;
+        }
+        if used.contains(&symbol) {
+            flags |= USED;
+        }
+        if kept.contains(&symbol) {
+            flags |= KEPT;
+        }
+        header.push(flags);
+    }
+    for offset in location_offsets {
+        write_u32(&mut header, offset)?;
+    }
+    for location in locations {
+        write_u32(&mut header, location)?;
+    }
+    let mut path_blob = Vec::new();
+    write_offsets(&mut header, paths.into_iter(), &mut path_blob)?;
+
+    out.write_all(&header)?;
+    out.write_all(&names)?;
+    out.write_all(&path_blob)?;
+    bincode::serialize_into(&mut out, index)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// A read-only view of an index file on disk.
+pub struct MappedIndex {
+    map: Mmap,
+    version: u32,
+    symbol_count: usize,
+    path_count: usize,
+    name_offsets: usize,
+    flags: usize,
+    location_offsets: usize,
+    locations: usize,
+    path_offsets: usize,
+    names: usize,
+    paths: usize,
+    index: usize,
+}
+
+impl MappedIndex {
+    pub fn open(path: &Path) -> Result<MappedIndex> {
+        let file = File::open(path)?;
+        // Safety: the cache file is only ever replaced, by renaming a new
+        // file over it (see cache::replace), never modified in place while
+        // we're reading it.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let (version, counts) = if map.starts_with(MAGIC) && map.len() >= 12 {
+            (read_u32(&map, 8), 12)
+        } else if map.starts_with(MAGIC_V1) {
+            (1, 8)
+        } else {
+            return Err(UnsupportedVersion(None).into());
+        };
+        if !(OLDEST_READABLE_VERSION..=FORMAT_VERSION).contains(&version) {
+            return Err(UnsupportedVersion(Some(version)).into());
+        }
+        if map.len() < counts + 12 {
+            return Err(anyhow!("{:?} is truncated", path));
+        }
+
+        let mut mapped = MappedIndex {
+            map,
+            version,
+            symbol_count: 0,
+            path_count: 0,
+            name_offsets: counts + 12,
+            flags: 0,
+            location_offsets: 0,
+            locations: 0,
+            path_offsets: 0,
+            names: 0,
+            paths: 0,
+            index: 0,
+        };
+        mapped.symbol_count = mapped.u32_at(counts) as usize;
+        mapped.path_count = mapped.u32_at(counts + 4) as usize;
+        let location_count = mapped.u32_at(counts + 8) as usize;
+
+        mapped.flags = mapped.name_offsets + 4 * (mapped.symbol_count + 1);
+        mapped.location_offsets = mapped.flags + mapped.symbol_count;
+        mapped.locations = mapped.location_offsets + 4 * (mapped.symbol_count + 1);
+        mapped.path_offsets = mapped.locations + 4 * location_count;
+        mapped.names = mapped.path_offsets + 4 * (mapped.path_count + 1);
+        if mapped.names > mapped.map.len() {
+            return Err(anyhow!("{:?} is truncated", path));
+        }
+        mapped.paths = mapped.names + mapped.table_u32(mapped.name_offsets, mapped.symbol_count);
+        mapped.index = mapped.paths + mapped.table_u32(mapped.path_offsets, mapped.path_count);
+        if mapped.index > mapped.map.len() {
+            return Err(anyhow!("{:?} is truncated", path));
+        }
+
+        Ok(mapped)
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        read_u32(&self.map, offset)
+    }
+
+    fn table_u32(&self, table: usize, i: usize) -> usize {
+        self.u32_at(table + 4 * i) as usize
+    }
+
+    fn string(&self, offsets: usize, blob: usize, i: usize) -> &str {
+        let start = blob + self.table_u32(offsets, i);
+        let end = blob + self.table_u32(offsets, i + 1);
+        str::from_utf8(&self.map[start..end]).unwrap_or("")
+    }
+
+    fn flags(&self, symbol: Symbol) -> u8 {
+        self.map[self.flags + symbol.id() as usize]
+    }
+
+    fn symbols(&self) -> impl Iterator<Item = Symbol> {
+        (0..self.symbol_count as u32).map(Symbol::from_id)
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        self.string(self.name_offsets, self.names, symbol.id() as usize)
+    }
+
+    pub fn defined_strings(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.symbols()
+            .filter(move |s| self.flags(*s) & DEFINED != 0)
+    }
+
+    pub fn used_strings(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.symbols().filter(move |s| self.flags(*s) & USED != 0)
+    }
+
+    pub fn unused_strings(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.symbols()
+            .filter(move |s| self.flags(*s) & (DEFINED | USED | KEPT) == DEFINED)
+    }
+
+    /// Returns the paths of the files that define the given string.
+    pub fn files_for_definition(&self, symbol: Symbol) -> impl Iterator<Item = &str> {
+        let id = symbol.id() as usize;
+        let start = self.table_u32(self.location_offsets, id);
+        let end = self.table_u32(self.location_offsets, id + 1);
+        (start..end).map(move |i| {
+            let path = self.table_u32(self.locations, i);
+            self.string(self.path_offsets, self.paths, path)
+        })
+    }
+
+    /// Decodes the full index.
+    pub fn load(&self) -> Result<ResourceIndex> {
+        match self.version {
+            // When the encoding of the full index changes, decode older
+            // versions into their old types here and convert them.
+            7 | 8 => Ok(bincode::deserialize(&self.map[self.index..])?),
+            version => Err(UnsupportedVersion(Some(version)).into()),
+        }
+    }
+}
+
+impl IndexView for MappedIndex {
+    fn defined_count(&self) -> Result<usize> {
+        Ok(self.defined_strings().count())
+    }
+
+    fn used_count(&self) -> Result<usize> {
+        Ok(self.used_strings().count())
+    }
+
+    fn unused_strings(&self) -> Result<Vec<(String, Vec<String>)>> {
+        Ok(MappedIndex::unused_strings(self)
+            .map(|symbol| {
+                let files = self
+                    .files_for_definition(symbol)
+                    .map(str::to_string)
+                    .collect();
+                (self.name(symbol).to_string(), files)
+            })
+            .collect())
+    }
+
+    fn load(&self) -> Result<ResourceIndex> {
+        MappedIndex::load(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_mapped_queries_match_index() -> Result<()> {
+        let tmp_dir = TempDir::new("mapped")?;
+        let res_dir = tmp_dir.path().join("res/values");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(&res_dir)?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("strings.xml"),
+            r#"<resources><string name="used">A</string><string name="unused">B</string></resources>"#,
+        )?;
+        fs::write(src_dir.join("A.java"), "class A { int a = R.string.used; }")?;
+
+        let indexer = Indexer::new(src_dir, tmp_dir.path().join("res"), None, None)?;
+        let index = indexer.index()?;
+        let file = tmp_dir.path().join("index.bin");
+        write(&index, File::create(&file)?)?;
+
+        let mapped = MappedIndex::open(&file)?;
+        assert_eq!(mapped.defined_strings().count(), 2);
+        assert_eq!(mapped.used_strings().count(), 1);
+        let unused: Vec<&str> = mapped.unused_strings().map(|s| mapped.name(s)).collect();
+        assert_eq!(unused, vec!["unused"]);
+        let unused = mapped.unused_strings().next().unwrap();
+        let files: Vec<&str> = mapped.files_for_definition(unused).collect();
+        assert_eq!(files, vec![res_dir.join("strings.xml").to_str().unwrap()]);
+        assert_eq!(mapped.load()?.unused_strings().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_old_and_unknown_versions() -> Result<()> {
+        let tmp_dir = TempDir::new("mapped")?;
+        let index = ResourceIndex::new(Vec::new());
+        let mut current = Vec::new();
+        write(&index, &mut current)?;
+
+        let file = tmp_dir.path().join("index.bin");
+        fs::write(&file, &current)?;
+        assert_eq!(MappedIndex::open(&file)?.version, FORMAT_VERSION);
+        assert_eq!(MappedIndex::open(&file)?.load()?.symbol_count(), 0);
+
+        // Version 1 files have the old magic and no version field.
+        let mut v1 = MAGIC_V1.to_vec();
+        v1.extend_from_slice(&current[12..]);
+        fs::write(&file, v1)?;
+        let error = MappedIndex::open(&file).err().unwrap();
+        assert!(error.downcast_ref::<UnsupportedVersion>().is_some());
+
+        let mut v2 = current.clone();
+        v2[8..12].copy_from_slice(&2_u32.to_le_bytes());
+        fs::write(&file, v2)?;
+        let error = MappedIndex::open(&file).err().unwrap();
+        assert!(error.downcast_ref::<UnsupportedVersion>().is_some());
+
+        let mut future = current.clone();
+        future[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&file, future)?;
+        let error = MappedIndex::open(&file).err().unwrap();
+        assert!(error.downcast_ref::<UnsupportedVersion>().is_some());
+
+        fs::write(&file, b"not an index")?;
+        let error = MappedIndex::open(&file).err().unwrap();
+        assert!(error.downcast_ref::<UnsupportedVersion>().is_some());
+
+        Ok(())
+    }
+}
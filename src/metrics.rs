@@ -0,0 +1,135 @@
+use crate::index::ResourceIndex;
+use crate::summary;
+use crate::symbols::Symbol;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Escapes a label value for the Prometheus text format.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes gauges of the strings defined and unused in each module, in the
+/// Prometheus text exposition format. A string defined in several modules
+/// counts in each of them.
+pub fn write(index: &ResourceIndex, unused: &HashSet<Symbol>, out: &mut dyn Write) -> Result<()> {
+    let mut modules: BTreeMap<String, (HashSet<Symbol>, HashSet<Symbol>)> = BTreeMap::new();
+    for file in index.files() {
+        let definitions = file.string_definitions();
+        if definitions.is_empty() {
+            continue;
+        }
+        let (defined, module_unused) = modules.entry(summary::module_of(file.path())).or_default();
+        for definition in definitions {
+            defined.insert(definition.name);
+            if unused.contains(&definition.name) {
+                module_unused.insert(definition.name);
+            }
+        }
+    }
+
+    for (metric, help, unused_only) in [
+        (
+            "xdel_defined_strings",
+            "Strings defined in the module.",
+            false,
+        ),
+        (
+            "xdel_unused_strings",
+            "Strings defined in the module and reported unused.",
+            true,
+        ),
+    ] {
+        writeln!(out, "# HELP {} {}", metric, help)?;
+        writeln!(out, "# TYPE {} gauge", metric)?;
+        for (module, (defined, module_unused)) in &modules {
+            let count = if unused_only {
+                module_unused.len()
+            } else {
+                defined.len()
+            };
+            writeln!(
+                out,
+                "{}{{module=\"{}\"}} {}",
+                metric,
+                escape_label(module),
+                count
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the metrics to `path` for a textfile collector, replacing it
+/// atomically so the collector never reads a partial file.
+pub fn write_file(index: &ResourceIndex, unused: &HashSet<Symbol>, path: &Path) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    write(index, unused, &mut file)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_write() -> Result<()> {
+        let tmp_dir = TempDir::new("metrics")?;
+        let create = |path: &str, content: &str| -> Result<()> {
+            let path = tmp_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            Ok(fs::write(path, content)?)
+        };
+        create(
+            "app/src/main/res/values/strings.xml",
+            r#"<resources><string name="title">T</string><string name="old">O</string></resources>"#,
+        )?;
+        create(
+            "lib/res/values/strings.xml",
+            r#"<resources><string name="lib_title">L</string></resources>"#,
+        )?;
+        create(
+            "app/src/main/java/Main.java",
+            "R.string.title; R.string.lib_title;",
+        )?;
+        let index = Indexer::new(
+            tmp_dir.path().to_path_buf(),
+            tmp_dir.path().to_path_buf(),
+            None,
+            None,
+        )?
+        .index()?;
+
+        let metrics = tmp_dir.path().join("xdel.prom");
+        write_file(&index, &index.unused_strings(), &metrics)?;
+        assert_eq!(
+            fs::read_to_string(&metrics)?,
+            r#"# HELP xdel_defined_strings Strings defined in the module.
+# TYPE xdel_defined_strings gauge
+xdel_defined_strings{module="app"} 2
+xdel_defined_strings{module="lib"} 1
+# HELP xdel_unused_strings Strings defined in the module and reported unused.
+# TYPE xdel_unused_strings gauge
+xdel_unused_strings{module="app"} 1
+xdel_unused_strings{module="lib"} 0
+"#
+        );
+        assert_eq!(escape_label("a\"b\\"), "a\\\"b\\\\");
+
+        Ok(())
+    }
+}
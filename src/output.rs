@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Error, Result};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// How a command should print its results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow!("Unrecognized format {}", value)),
+        }
+    }
+}
+
+/// How a command listing many results should print them. With ndjson, each
+/// result is printed as a JSON object on its own line as soon as it's ready.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListFormat {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl FromStr for ListFormat {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(ListFormat::Text),
+            "json" => Ok(ListFormat::Json),
+            "ndjson" => Ok(ListFormat::Ndjson),
+            _ => Err(anyhow!("Unrecognized format {}", value)),
+        }
+    }
+}
+
+/// The file given with --output, once it's been created.
+static FILE: OnceLock<Mutex<LineWriter<File>>> = OnceLock::new();
+
+/// Sends a command's results to `path` rather than stdout. Progress and log
+/// messages stay on stderr either way.
+pub fn to_file(path: &Path) -> Result<()> {
+    let file =
+        File::create(path).map_err(|e| anyhow!("Unable to create {}: {}", path.display(), e))?;
+    FILE.set(Mutex::new(LineWriter::new(file)))
+        .map_err(|_| anyhow!("Output already redirected"))
+}
+
+thread_local! {
+    /// Where this thread's results go instead, while a `Redirect` lives.
+    static REDIRECT: RefCell<Option<Box<dyn Write>>> = const { RefCell::new(None) };
+}
+
+/// Sends the results printed on this thread to another sink until it's
+/// dropped, leaving other threads' results where they were going.
+#[cfg(test)]
+pub struct Redirect {
+    previous: Option<Box<dyn Write>>,
+}
+
+#[cfg(test)]
+pub fn redirect(sink: Box<dyn Write>) -> Redirect {
+    Redirect {
+        previous: REDIRECT.with(|redirect| redirect.replace(Some(sink))),
+    }
+}
+
+#[cfg(test)]
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        let sink = REDIRECT.with(|redirect| redirect.replace(self.previous.take()));
+        if let Some(mut sink) = sink {
+            let _ = sink.flush();
+        }
+    }
+}
+
+/// Collects the results `f` prints on this thread.
+#[cfg(test)]
+pub fn capture(f: impl FnOnce() -> Result<()>) -> Result<String> {
+    use std::rc::Rc;
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    let redirect = redirect(Box::new(Shared(buffer.clone())));
+    f()?;
+    drop(redirect);
+    let captured = buffer.borrow().clone();
+    Ok(String::from_utf8(captured)?)
+}
+
+/// Runs `f` on this thread's redirected sink, if it has one.
+fn redirected<T>(f: impl FnOnce(&mut dyn Write) -> io::Result<T>) -> Option<io::Result<T>> {
+    REDIRECT.with(|redirect| {
+        let mut redirect = redirect.borrow_mut();
+        let sink = redirect.as_mut()?;
+        Some(f(sink.as_mut()))
+    })
+}
+
+/// Where a command's results go: stdout, or the file given with --output.
+pub struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(written) = redirected(|sink| sink.write(buf)) {
+            return written;
+        }
+        match FILE.get() {
+            Some(file) => file.lock().unwrap().write(buf),
+            None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(flushed) = redirected(|sink| sink.flush()) {
+            return flushed;
+        }
+        match FILE.get() {
+            Some(file) => file.lock().unwrap().flush(),
+            None => io::stdout().flush(),
+        }
+    }
+}
+
+/// Exits quietly once whatever the results are piped to stops reading, like
+/// head does, and panics like println! otherwise.
+pub fn write_failed(error: io::Error) {
+    if error.kind() == io::ErrorKind::BrokenPipe {
+        std::process::exit(0);
+    }
+    panic!("failed writing output: {}", error);
+}
+
+/// Like println!, but to wherever the command's results go. Each line is
+/// written at once, so lines from different threads don't interleave.
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let line = format!("{}\n", format_args!($($arg)*));
+        $crate::output::Stdout
+            .write_all(line.as_bytes())
+            .unwrap_or_else($crate::output::write_failed)
+    }};
+}
+pub(crate) use outln;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_redirect() -> Result<()> {
+        let tmp_dir = TempDir::new("output")?;
+        let path = tmp_dir.path().join("out.txt");
+        let redirect = redirect(Box::new(LineWriter::new(File::create(&path)?)));
+        outln!("{} unused strings in {}", 2, "a file");
+        let nested = capture(|| {
+            outln!("captured");
+            Ok(())
+        })?;
+        assert_eq!(nested, "captured\n");
+        outln!("after");
+        drop(redirect);
+
+        // Only this thread's results, and nothing once the redirect's gone.
+        outln!("to stdout");
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "2 unused strings in a file\nafter\n"
+        );
+        assert!(FILE.get().is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where CODEOWNERS files may live, relative to the repository root.
+const CODEOWNERS_LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Chromium style OWNERS files, which apply to their directory and below.
+const OWNERS_FILE: &str = "OWNERS";
+
+struct Rule {
+    pattern: GlobMatcher,
+    owners: String,
+}
+
+/// Works out who owns files, from a CODEOWNERS file if there is one, or
+/// else from the nearest OWNERS file.
+pub struct Owners {
+    root: PathBuf,
+    rules: Vec<Rule>,
+    owners_files: RefCell<HashMap<PathBuf, Option<String>>>,
+}
+
+/// Turns a CODEOWNERS (gitignore style) pattern into a glob matching paths
+/// relative to the repository root.
+fn pattern_glob(pattern: &str) -> Result<GlobMatcher> {
+    let mut glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.trim_end_matches('/').contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    if glob.ends_with('/') {
+        glob.push_str("**");
+    } else if !glob.ends_with("**") {
+        // Patterns match directories as well as files.
+        glob = format!("{{{},{}/**}}", glob, glob);
+    }
+
+    Ok(GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+impl Owners {
+    /// Looks for a CODEOWNERS file in `dir` or any of its parents.
+    pub fn find(dir: &Path) -> Result<Owners> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for ancestor in dir.ancestors() {
+            for location in CODEOWNERS_LOCATIONS {
+                let path = ancestor.join(location);
+                if path.is_file() {
+                    let content = fs::read_to_string(&path)?;
+                    return Owners::parse(ancestor, &content)
+                        .with_context(|| format!("Failed to read {}", path.display()));
+                }
+            }
+        }
+
+        Ok(Owners {
+            root: dir,
+            rules: Vec::new(),
+            owners_files: RefCell::default(),
+        })
+    }
+
+    fn parse(root: &Path, content: &str) -> Result<Owners> {
+        let mut rules = Vec::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next().unwrap_or_default();
+            rules.push(Rule {
+                pattern: pattern_glob(pattern)?,
+                owners: fields.collect::<Vec<_>>().join(" "),
+            });
+        }
+
+        Ok(Owners {
+            root: root.to_path_buf(),
+            rules,
+            owners_files: RefCell::default(),
+        })
+    }
+
+    /// Returns the owners of `path`, space separated, if it has any.
+    pub fn owner_of(&self, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        if !self.rules.is_empty() {
+            let relative = path.strip_prefix(&self.root).ok()?;
+            // Later rules take precedence, and a rule without owners unsets
+            // them.
+            return self
+                .rules
+                .iter()
+                .rev()
+                .find(|rule| rule.pattern.is_match(relative))
+                .map(|rule| rule.owners.clone())
+                .filter(|owners| !owners.is_empty());
+        }
+
+        path.ancestors()
+            .skip(1)
+            .find_map(|dir| self.owners_file(dir))
+    }
+
+    fn owners_file(&self, dir: &Path) -> Option<String> {
+        self.owners_files
+            .borrow_mut()
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let content = fs::read_to_string(dir.join(OWNERS_FILE)).ok()?;
+                let owners: Vec<&str> = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| {
+                        !line.is_empty()
+                            && !line.starts_with('#')
+                            && !line.contains(char::is_whitespace)
+                            && !line.contains('=')
+                    })
+                    .collect();
+                Some(owners.join(" ")).filter(|owners| !owners.is_empty())
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_codeowners() -> Result<()> {
+        let tmp_dir = TempDir::new("owners")?;
+        let root = tmp_dir.path().canonicalize()?;
+        fs::create_dir_all(root.join(".github"))?;
+        fs::write(
+            root.join(".github/CODEOWNERS"),
+            "* @org/everyone\n/feature_x/ @org/team-x\nstrings.xml @org/l10n\n/feature_x/legacy\n",
+        )?;
+
+        let owners = Owners::find(&root.join("feature_x/res"))?;
+        let owner = |path: &str| owners.owner_of(root.join(path).to_str().unwrap());
+        assert_eq!(owner("app/res/values/colors.xml").unwrap(), "@org/everyone");
+        assert_eq!(
+            owner("feature_x/res/values/colors.xml").unwrap(),
+            "@org/team-x"
+        );
+        assert_eq!(
+            owner("feature_x/res/values/strings.xml").unwrap(),
+            "@org/l10n"
+        );
+        assert_eq!(owner("feature_x/legacy/res/values/colors.xml"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_owners_files() -> Result<()> {
+        let tmp_dir = TempDir::new("owners")?;
+        let root = tmp_dir.path().canonicalize()?;
+        fs::create_dir_all(root.join("feature_x/res/values"))?;
+        fs::write(root.join("OWNERS"), "# Everyone\nall@example.com\n")?;
+        fs::write(
+            root.join("feature_x/OWNERS"),
+            "set noparent\nx@example.com\ny@example.com\n",
+        )?;
+
+        let owners = Owners::find(&root)?;
+        let owner = |path: &str| owners.owner_of(root.join(path).to_str().unwrap());
+        assert_eq!(
+            owner("feature_x/res/values/strings.xml").unwrap(),
+            "x@example.com y@example.com"
+        );
+        assert_eq!(
+            owner("app/res/values/strings.xml").unwrap(),
+            "all@example.com"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Paths as the index stores and prints them. The index keeps paths as
+//! strings, which nearly all are; the bytes of one that isn't valid UTF-8 are
+//! kept as characters at the very end of the unicode range, so that it still
+//! leads back to its file. Characters of a path really in that range are
+//! kept as their bytes too, so that every such character is a byte.
+
+use std::path::{Path, PathBuf};
+
+/// Where the characters standing for the bytes 0x80 to 0xff start. Only
+/// invalid bytes and the bytes of characters in that range are escaped, and
+/// those are never below 0x80.
+const ESCAPED: u32 = 0x10ff00;
+
+fn unescape(c: char) -> Option<u8> {
+    let byte = (c as u32).checked_sub(ESCAPED)?;
+    (0x80..=0xff).contains(&byte).then_some(byte as u8)
+}
+
+/// `path` as the index stores it.
+pub fn to_stored(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) if !path.chars().any(|c| unescape(c).is_some()) => path.to_string(),
+        _ => escape(path),
+    }
+}
+
+#[cfg(unix)]
+fn escape(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let escaped = |byte: &u8| char::from_u32(ESCAPED + u32::from(*byte));
+    let mut stored = String::new();
+    for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match unescape(c) {
+                Some(_) => stored.extend(
+                    c.encode_utf8(&mut [0; 4])
+                        .as_bytes()
+                        .iter()
+                        .filter_map(escaped),
+                ),
+                None => stored.push(c),
+            }
+        }
+        stored.extend(chunk.invalid().iter().filter_map(escaped));
+    }
+    stored
+}
+
+/// Windows paths are only invalid with unpaired surrogates, which can't name
+/// files a build uses.
+#[cfg(not(unix))]
+fn escape(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The file a stored path leads to.
+pub fn from_stored(stored: &str) -> PathBuf {
+    if !stored.chars().any(|c| unescape(c).is_some()) {
+        return PathBuf::from(stored);
+    }
+    unescaped(stored)
+}
+
+#[cfg(unix)]
+fn unescaped(stored: &str) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    let mut bytes = Vec::new();
+    for c in stored.chars() {
+        match unescape(c) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    PathBuf::from(OsStr::from_bytes(&bytes))
+}
+
+#[cfg(not(unix))]
+fn unescaped(stored: &str) -> PathBuf {
+    PathBuf::from(stored)
+}
+
+fn without_verbatim(path: &str) -> String {
+    if let Some(share) = path.strip_prefix(r"\\?\UNC\") {
+        return format!(r"\\{}", share);
+    }
+    match path.strip_prefix(r"\\?\") {
+        Some(drive) if drive.as_bytes().get(1) == Some(&b':') => drive.to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// A stored path as it's shown to people: without a `\\?\` prefix, with the
+/// platform's separators throughout, and with the bytes of a path that
+/// isn't UTF-8 shown as `\xNN`.
+pub fn display(stored: &str) -> String {
+    displayed(stored, cfg!(windows))
+}
+
+fn displayed(stored: &str, windows: bool) -> String {
+    let mut shown = String::with_capacity(stored.len());
+    for c in without_verbatim(stored).chars() {
+        match unescape(c) {
+            Some(byte) => shown.push_str(&format!("\\x{:02x}", byte)),
+            None if windows && c == '/' => shown.push('\\'),
+            None => shown.push(c),
+        }
+    }
+    shown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_paths() {
+        assert_eq!(
+            to_stored(Path::new("res/values/strings.xml")),
+            "res/values/strings.xml"
+        );
+        assert_eq!(
+            from_stored("res/values/strings.xml"),
+            Path::new("res/values/strings.xml")
+        );
+
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+            let latin1 = Path::new(OsStr::from_bytes(b"res/drawable/caf\xe9.png"));
+            let stored = to_stored(latin1);
+            assert_eq!(from_stored(&stored), latin1);
+            assert_eq!(displayed(&stored, false), r"res/drawable/caf\xe9.png");
+
+            // A name really using the characters bytes are stored as is stored
+            // by its bytes, so that it can't be read as other bytes.
+            let private = Path::new("res/drawable/\u{10ff80}\u{10ffe9}.png");
+            let stored = to_stored(private);
+            assert_ne!(stored, private.to_str().unwrap_or_default());
+            assert_eq!(from_stored(&stored), private);
+            assert_eq!(from_stored(&to_stored(&from_stored(&stored))), private);
+        }
+
+        assert_eq!(
+            displayed(r"\\?\C:\repo\res/values/strings.xml", true),
+            r"C:\repo\res\values\strings.xml"
+        );
+        assert_eq!(
+            displayed(r"\\?\UNC\server\share\res", true),
+            r"\\server\share\res"
+        );
+        assert_eq!(
+            displayed("res/values/strings.xml", false),
+            "res/values/strings.xml"
+        );
+        // Verbatim paths that aren't drive or UNC paths need their prefix.
+        assert_eq!(
+            without_verbatim(r"\\?\Volume{1}\repo"),
+            r"\\?\Volume{1}\repo"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often progress is redrawn (or emitted, for json).
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 30;
+
+/// How indexing progress is reported on stderr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressMode {
+    /// A bar if stderr is a terminal, otherwise nothing.
+    Auto,
+    Bar,
+    /// One json object per line, for tools that wrap us.
+    Json,
+    None,
+}
+
+impl FromStr for ProgressMode {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "auto" => Ok(ProgressMode::Auto),
+            "bar" => Ok(ProgressMode::Bar),
+            "json" => Ok(ProgressMode::Json),
+            "none" => Ok(ProgressMode::None),
+            _ => Err(anyhow!("Unrecognized progress mode {}", value)),
+        }
+    }
+}
+
+impl ProgressMode {
+    fn resolve(self) -> ProgressMode {
+        match self {
+            ProgressMode::Auto if io::stderr().is_terminal() => ProgressMode::Bar,
+            ProgressMode::Auto => ProgressMode::None,
+            mode => mode,
+        }
+    }
+
+    /// Starts reporting progress for one phase of indexing.
+    pub fn start(self, phase: &'static str) -> Progress {
+        let progress = Progress {
+            mode: self.resolve(),
+            phase,
+            walked: AtomicUsize::new(0),
+            indexed: AtomicUsize::new(0),
+            started: Instant::now(),
+            last_update: Mutex::new(Instant::now()),
+        };
+        progress.emit("start");
+        progress
+    }
+}
+
+#[derive(Serialize)]
+struct ProgressEvent<'a> {
+    event: &'a str,
+    phase: &'a str,
+    walked: usize,
+    indexed: usize,
+    elapsed_ms: u128,
+}
+
+/// Counts files walked and indexed during one phase. Shared between walker
+/// threads.
+pub struct Progress {
+    mode: ProgressMode,
+    phase: &'static str,
+    walked: AtomicUsize,
+    indexed: AtomicUsize,
+    started: Instant,
+    last_update: Mutex<Instant>,
+}
+
+impl Progress {
+    pub fn walked(&self) {
+        self.walked.fetch_add(1, Ordering::Relaxed);
+        self.update();
+    }
+
+    pub fn indexed(&self) {
+        self.indexed.fetch_add(1, Ordering::Relaxed);
+        self.update();
+    }
+
+    pub fn finish(&self) {
+        self.emit("finish");
+        if self.mode == ProgressMode::Bar {
+            eprintln!();
+        }
+    }
+
+    fn update(&self) {
+        if self.mode == ProgressMode::None {
+            return;
+        }
+
+        // Only one thread needs to draw; everyone else carries on walking.
+        if let Ok(mut last_update) = self.last_update.try_lock() {
+            if last_update.elapsed() >= UPDATE_INTERVAL {
+                *last_update = Instant::now();
+                self.emit("progress");
+            }
+        }
+    }
+
+    fn emit(&self, event: &str) {
+        if let Some(report) = self.report(event) {
+            let mut stderr = io::stderr();
+            let _ = write!(stderr, "{}", report);
+            let _ = stderr.flush();
+        }
+    }
+
+    /// What `emit` writes for `event`, if anything.
+    fn report(&self, event: &str) -> Option<String> {
+        let walked = self.walked.load(Ordering::Relaxed);
+        let indexed = self.indexed.load(Ordering::Relaxed);
+        match self.mode {
+            ProgressMode::Bar => {
+                let filled = (indexed * BAR_WIDTH).checked_div(walked).unwrap_or(0);
+                Some(format!(
+                    "\r{:>10} [{}{}] {}/{} files",
+                    self.phase,
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    indexed,
+                    walked
+                ))
+            }
+            ProgressMode::Json => {
+                let event = ProgressEvent {
+                    event,
+                    phase: self.phase,
+                    walked,
+                    indexed,
+                    elapsed_ms: self.started.elapsed().as_millis(),
+                };
+                serde_json::to_string(&event).ok().map(|json| json + "\n")
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() -> Result<()> {
+        assert_eq!("bar".parse::<ProgressMode>()?, ProgressMode::Bar);
+        assert!("loud".parse::<ProgressMode>().is_err());
+
+        let progress = ProgressMode::Json.start("xml");
+        for _ in 0..4 {
+            progress.walked();
+        }
+        progress.indexed();
+        let report = progress.report("progress").unwrap_or_default();
+        assert!(report.ends_with('\n'));
+        let event: serde_json::Value = serde_json::from_str(&report)?;
+        assert_eq!(event["event"], "progress");
+        assert_eq!(event["phase"], "xml");
+        assert_eq!(
+            (event["walked"].as_u64(), event["indexed"].as_u64()),
+            (Some(4), Some(1))
+        );
+
+        let bar = Progress {
+            mode: ProgressMode::Bar,
+            ..ProgressMode::None.start("source")
+        };
+        bar.walked();
+        bar.walked();
+        bar.indexed();
+        assert_eq!(
+            bar.report("progress").as_deref(),
+            Some("\r    source [###############---------------] 1/2 files")
+        );
+
+        let none = ProgressMode::None.start("source");
+        none.walked();
+        assert_eq!(none.report("progress"), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use crate::index::{self, ResourceIndex};
+use crate::json_localization;
+use crate::paths;
+use crate::xliff::{self, Imported};
+use anyhow::Result;
+use regex::Regex;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use xml::reader::{ParserConfig, XmlEvent};
+
+/// Accented letters standing in for a to z and A to Z.
+const ACCENTED: [char; 52] = [
+    'å', 'ƀ', 'ç', 'ð', 'é', 'ƒ', 'ĝ', 'ĥ', 'î', 'ĵ', 'ķ', 'ļ', 'ɱ', 'ñ', 'ö', 'þ', 'ǫ', 'ŕ', 'š',
+    'ţ', 'û', 'ṽ', 'ŵ', 'ẋ', 'ý', 'ž', 'Å', 'Ɓ', 'Ç', 'Ð', 'É', 'Ƒ', 'Ĝ', 'Ĥ', 'Î', 'Ĵ', 'Ķ', 'Ļ',
+    'Ṁ', 'Ñ', 'Ö', 'Þ', 'Ǫ', 'Ŕ', 'Š', 'Ţ', 'Û', 'Ṽ', 'Ŵ', 'Ẋ', 'Ý', 'Ž',
+];
+
+/// Words padding a value out, as translations tend to be longer.
+const PADDING: [&str; 10] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+];
+
+fn accent(c: char) -> char {
+    match c {
+        'a'..='z' => ACCENTED[c as usize - 'a' as usize],
+        'A'..='Z' => ACCENTED[26 + c as usize - 'A' as usize],
+        _ => c,
+    }
+}
+
+/// `value`, a string resource's value with Android escapes and all, with
+/// its letters accented, padded by about a third with words and wrapped in
+/// brackets, so that text that isn't translated, is cut off or is pieced
+/// together stands out. Escapes, format specifiers like `%1$s` and ICU
+/// arguments like `{count}` are left as they are.
+fn pseudolocalize(value: &str, protected: &Regex) -> String {
+    let mut pseudo = String::from("[");
+    let mut last = 0;
+    for token in protected.find_iter(value) {
+        pseudo.extend(value[last..token.start()].chars().map(accent));
+        pseudo.push_str(token.as_str());
+        last = token.end();
+    }
+    pseudo.extend(value[last..].chars().map(accent));
+
+    let extra = value.chars().count().div_ceil(3);
+    let mut padding = 0;
+    for word in PADDING.iter().cycle() {
+        if padding >= extra {
+            break;
+        }
+        pseudo.push(' ');
+        pseudo.push_str(word);
+        padding += word.len() + 1;
+    }
+    pseudo.push(']');
+    pseudo
+}
+
+/// The parts of a value that `pseudolocalize` keeps as they are.
+fn protected_pattern() -> Result<Regex> {
+    Ok(Regex::new(
+        r"\\u[0-9a-fA-F]{4}|\\.|%(\d+\$)?[-#+ 0,(<]*\d*(\.\d+)?[a-zA-Z%]|\{[^{}]*\}",
+    )?)
+}
+
+/// The strings in the values file at `path` that can't be pseudolocalized
+/// from their indexed values: those marked translatable="false", and those
+/// holding markup, which the index doesn't keep.
+fn skipped_names(path: &Path) -> Result<HashSet<String>> {
+    let content = fs::read_to_string(path)?;
+    let mut parser = ParserConfig::new().create_reader(content.as_bytes());
+    let mut skipped = HashSet::new();
+    let mut current = None;
+    let mut depth = 0;
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+                let attribute = |local_name: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == local_name && a.name.prefix.is_none())
+                        .map(|a| a.value.clone())
+                };
+                if depth == 2 && name.local_name == "string" {
+                    current = attribute("name");
+                    if attribute("translatable").as_deref() == Some("false") {
+                        skipped.extend(current.clone());
+                    }
+                } else if depth > 2 {
+                    skipped.extend(current.clone());
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                depth -= 1;
+                if depth < 2 {
+                    current = None;
+                }
+            }
+            XmlEvent::EndDocument => return Ok(skipped),
+            _ => {}
+        }
+    }
+}
+
+/// Writes a pseudolocalized copy of every default values file's strings
+/// into the values directory for `locale` beside it, e.g. values-en-rXA for
+/// en-XA, updating strings already there in place. Strings that aren't
+/// translatable, that hold markup, or that are references are left out.
+pub fn generate(index: &ResourceIndex, locale: &str) -> Result<Vec<Imported>> {
+    let protected = protected_pattern()?;
+    let mut by_path: BTreeMap<PathBuf, Vec<(String, String)>> = BTreeMap::new();
+    for file in index.files() {
+        if index::locale_of(file.path()) != "default" || file.string_definitions().is_empty() {
+            continue;
+        }
+        let path = paths::from_stored(file.path());
+        if !path.is_file() {
+            continue;
+        }
+        let skipped = skipped_names(&path)?;
+        let units = by_path
+            .entry(json_localization::translation_path(
+                &path,
+                &xliff::to_android_locale(locale),
+            ))
+            .or_default();
+        for definition in file.string_definitions() {
+            let name = index.name(definition.name);
+            let value = definition.value.trim();
+            if skipped.contains(name)
+                || value.is_empty()
+                || value.starts_with('@')
+                || value.starts_with('?')
+            {
+                continue;
+            }
+            units.push((
+                name.to_string(),
+                pseudolocalize(&definition.value, &protected),
+            ));
+        }
+    }
+
+    by_path
+        .into_iter()
+        .filter(|(_, units)| !units.is_empty())
+        .map(|(path, units)| xliff::write_translations(path, &units))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_generate() -> Result<()> {
+        let protected = protected_pattern()?;
+        assert_eq!(pseudolocalize("Hello", &protected), "[Ĥéļļö one]");
+        assert_eq!(
+            pseudolocalize(r"%1$d songs by %2$s\'s {count}", &protected),
+            r"[%1$d šöñĝš ƀý %2$s\'š {count} one two three]"
+        );
+
+        let tmp_dir = TempDir::new("pseudo")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-en-rXA"))?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources>
+    <string name="hello">Hello</string>
+    <string name="bye">Bye</string>
+    <string name="app_id" translatable="false">com.example</string>
+    <string name="bold">Be <b>bold</b></string>
+    <string name="alias">@string/hello</string>
+</resources>
+"#,
+        )?;
+        fs::write(
+            res_dir.join("values-en-rXA/strings.xml"),
+            r#"<resources>
+    <string name="hello">Stale</string>
+</resources>
+"#,
+        )?;
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+
+        let generated = generate(&index, "en-XA")?;
+        assert_eq!(generated.len(), 1);
+        assert_eq!((generated[0].updated, generated[0].added), (1, 1));
+        assert_eq!(
+            fs::read_to_string(res_dir.join("values-en-rXA/strings.xml"))?,
+            r#"<resources>
+    <string name="hello">[Ĥéļļö one]</string>
+    <string name="bye">[Ɓýé one]</string>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+}
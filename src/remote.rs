@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// A shared location that index files can be fetched from and pushed to, so
+/// they only need to be built once per commit. Supports http(s) and file://
+/// urls (via curl) and s3:// urls (via the aws cli, which honors
+/// AWS_ENDPOINT_URL for other S3 compatible stores).
+#[derive(Clone, Debug)]
+pub struct RemoteCache {
+    url: String,
+}
+
+/// The url schemes a remote cache can have.
+const SCHEMES: [&str; 4] = ["http://", "https://", "s3://", "file://"];
+
+impl RemoteCache {
+    pub fn new(url: &str) -> Result<RemoteCache> {
+        if !SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+            || url.chars().any(|c| c.is_whitespace() || c.is_control())
+        {
+            return Err(anyhow!(
+                "Unsupported remote cache {:?}, expected an http(s)://, s3:// or file:// url",
+                url
+            ));
+        }
+        Ok(RemoteCache {
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// The url of the index file `file_name` for `commit`, which must be a
+    /// full hex commit hash so that it can't reach outside the cache.
+    fn object_url(&self, commit: &str, file_name: &str) -> Result<String> {
+        if !matches!(commit.len(), 40 | 64) || !commit.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!("{:?} isn't a commit hash", commit));
+        }
+        if file_name.is_empty()
+            || !file_name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'_' || b == b'-')
+            || file_name.starts_with('.')
+        {
+            return Err(anyhow!("{:?} isn't an index file name", file_name));
+        }
+        Ok(format!("{}/{}/{}", self.url, commit, file_name))
+    }
+
+    fn run(command: &mut Command) -> Result<()> {
+        let output = command
+            .output()
+            .map_err(|e| anyhow!("Unable to run {:?}: {}", command.get_program(), e))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{:?} failed: {}",
+                command.get_program(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Downloads the index file `name` built for `commit` to `local`.
+    pub fn fetch(&self, commit: &str, name: &str, local: &Path) -> Result<()> {
+        let url = self.object_url(commit, name)?;
+
+        // Download next to the destination and rename, so a failed fetch
+        // never leaves a partial index behind.
+        let partial = local.with_extension("part");
+        let result = if self.url.starts_with("s3://") {
+            Self::run(
+                Command::new("aws")
+                    .args(["s3", "cp", "--quiet", &url])
+                    .arg(&partial),
+            )
+        } else {
+            Self::run(
+                Command::new("curl")
+                    .args([
+                        "--fail",
+                        "--silent",
+                        "--show-error",
+                        "--location",
+                        "--output",
+                    ])
+                    .arg(&partial)
+                    .arg(&url),
+            )
+        };
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        std::fs::rename(&partial, local)?;
+        Ok(())
+    }
+
+    /// Uploads `local` as the index file `name` for `commit`.
+    pub fn push(&self, commit: &str, name: &str, local: &Path) -> Result<()> {
+        let url = self.object_url(commit, name)?;
+
+        if self.url.starts_with("s3://") {
+            Self::run(
+                Command::new("aws")
+                    .args(["s3", "cp", "--quiet"])
+                    .arg(local)
+                    .arg(&url),
+            )
+        } else {
+            Self::run(
+                Command::new("curl")
+                    .args(["--fail", "--silent", "--show-error", "--upload-file"])
+                    .arg(local)
+                    .arg(&url),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn test_rejects_unsafe_values() -> Result<()> {
+        for url in [
+            "--output=/etc/passwd",
+            "ftp://host/cache",
+            "cache",
+            "http://a b",
+        ] {
+            assert!(RemoteCache::new(url).is_err(), "{}", url);
+        }
+
+        let tmp_dir = TempDir::new("remote")?;
+        let remote = RemoteCache::new("https://example.com/cache/")?;
+        assert_eq!(
+            remote.object_url(COMMIT, "index.bin")?,
+            format!("https://example.com/cache/{}/index.bin", COMMIT)
+        );
+        for commit in ["HEAD", "0123abc", "../../etc", &COMMIT.replace('0', "g")] {
+            assert!(
+                remote.object_url(commit, "index.bin").is_err(),
+                "{}",
+                commit
+            );
+            assert!(remote
+                .fetch(commit, "index.bin", &tmp_dir.path().join("index.bin"))
+                .is_err());
+        }
+        for name in ["../index.bin", "a/b", ".", "..", ""] {
+            assert!(remote.object_url(COMMIT, name).is_err(), "{}", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_cache() -> Result<()> {
+        let tmp_dir = TempDir::new("remote")?;
+        let store = tmp_dir.path().join("store");
+        fs::create_dir_all(store.join(COMMIT))?;
+        let remote = RemoteCache::new(&format!("file://{}", store.display()))?;
+
+        let built = tmp_dir.path().join("built.bin");
+        fs::write(&built, "index")?;
+        remote.push(COMMIT, "index.bin", &built)?;
+        assert_eq!(
+            fs::read_to_string(store.join(COMMIT).join("index.bin"))?,
+            "index"
+        );
+
+        let fetched = tmp_dir.path().join("fetched.bin");
+        remote.fetch(COMMIT, "index.bin", &fetched)?;
+        assert_eq!(fs::read_to_string(&fetched)?, "index");
+
+        // A missing index leaves nothing behind.
+        let missing = tmp_dir.path().join("missing.bin");
+        assert!(remote.fetch(COMMIT, "other.bin", &missing).is_err());
+        assert!(!missing.exists());
+        assert!(!missing.with_extension("part").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use crate::mapped::FORMAT_VERSION;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+
+/// The commands with json output, as they're named on the command line.
+pub const COMMANDS: [&str; 11] = [
+    "ls-unused",
+    "search",
+    "diff",
+    "verify",
+    "show",
+    "lint hardcoded-text",
+    "lint names",
+    "lint escaping",
+    "lint conflicts",
+    "diagnostics",
+    "index export",
+];
+
+/// An object with exactly these properties, all required but `optional`.
+fn object(properties: Value, optional: &[&str]) -> Value {
+    let required: Vec<&String> = properties
+        .as_object()
+        .map(|properties| {
+            properties
+                .keys()
+                .filter(|key| !optional.contains(&key.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn line() -> Value {
+    json!({ "type": "integer", "minimum": 1 })
+}
+
+fn names() -> Value {
+    array(string())
+}
+
+/// The schema of the json `command` prints, without the `$schema` and `$id`
+/// every schema has.
+fn body(command: &str) -> Option<Value> {
+    let schema = match command {
+        "ls-unused" => array(object(
+            json!({
+                "name": string(),
+                "locations": array(string()),
+                "group": string(),
+                "blame": object(
+                    json!({
+                        "commit": string(),
+                        "author": string(),
+                        "time": { "type": "integer", "description": "Seconds since the unix epoch" },
+                    }),
+                    &[],
+                ),
+                "test_only": { "type": "boolean", "const": true },
+            }),
+            &["group", "blame", "test_only"],
+        )),
+        "search" => array(object(
+            json!({
+                "name": string(),
+                "locale": string(),
+                "value": string(),
+                "path": string(),
+                "line": line(),
+            }),
+            &[],
+        )),
+        "diff" => object(
+            json!({
+                "added": names(),
+                "removed": names(),
+                "newly_unused": names(),
+                "newly_used": names(),
+            }),
+            &[],
+        ),
+        "verify" => object(json!({ "retained": names(), "shrunk": names() }), &[]),
+        "show" => object(
+            json!({
+                "name": string(),
+                "definitions": array(object(
+                    json!({
+                        "locale": string(),
+                        "value": string(),
+                        "path": string(),
+                        "line": line(),
+                    }),
+                    &[],
+                )),
+                "usages": array(string()),
+                "kept": { "type": "boolean" },
+            }),
+            &[],
+        ),
+        "lint hardcoded-text" => array(object(
+            json!({
+                "path": string(),
+                "line": line(),
+                "column": { "type": "integer", "minimum": 1 },
+                "attribute": string(),
+                "value": string(),
+            }),
+            &[],
+        )),
+        "lint names" => array(object(
+            json!({
+                "path": string(),
+                "line": line(),
+                "name": string(),
+                "problem": string(),
+                "rejected_by_aapt": { "type": "boolean" },
+            }),
+            &[],
+        )),
+        "lint escaping" => array(object(
+            json!({
+                "locale": string(),
+                "path": string(),
+                "line": line(),
+                "name": string(),
+                "problem": string(),
+            }),
+            &[],
+        )),
+        "lint conflicts" => array(object(
+            json!({
+                "name": string(),
+                "configuration": string(),
+                "definitions": array(object(
+                    json!({ "path": string(), "line": line(), "value": string() }),
+                    &[],
+                )),
+            }),
+            &[],
+        )),
+        "diagnostics" => object(
+            json!({
+                "event": { "const": "diagnostics" },
+                "diagnostics": array(object(
+                    json!({
+                        "kind": {
+                            "enum": ["xml-parse", "unreadable", "walk", "latin1", "binary", "too-large", "duplicate"],
+                        },
+                        "path": string(),
+                        "message": string(),
+                    }),
+                    &["path", "message"],
+                )),
+            }),
+            &[],
+        ),
+        "index export" => {
+            let kind = json!({
+                "enum": ["String", "Drawable", "Color", "Dimen", "Style", "Layout", "Destination"],
+            });
+            object(
+                json!({
+                    "version": { "type": "integer" },
+                    "files": array(object(
+                        json!({
+                            "path": string(),
+                            "source_set": { "enum": ["Main", "Test", "AndroidTest"] },
+                            "string_definitions": array(object(
+                                json!({
+                                    "name": string(),
+                                    "value": string(),
+                                    "line": line(),
+                                    "keep": { "type": "boolean" },
+                                }),
+                                &[],
+                            )),
+                            "string_usages": names(),
+                            "public_strings": names(),
+                            "resource_definitions": array(object(
+                                json!({
+                                    "kind": kind,
+                                    "name": string(),
+                                    "line": {
+                                        "type": "integer",
+                                        "minimum": 0,
+                                        "description": "0 for a file that's a resource as a whole",
+                                    },
+                                    "parent": { "type": ["string", "null"] },
+                                }),
+                                &[],
+                            )),
+                            "resource_usages": array(object(
+                                json!({ "kind": kind, "name": string() }),
+                                &[],
+                            )),
+                        }),
+                        &[],
+                    )),
+                }),
+                &[],
+            )
+        }
+        _ => return None,
+    };
+    Some(schema)
+}
+
+/// The JSON Schema of the json `command` prints. Its `$id` carries the index
+/// format version, so consumers can tell when the output may have changed.
+pub fn schema(command: &str) -> Result<Value> {
+    let mut schema = match body(command) {
+        Some(Value::Object(schema)) => schema,
+        _ => {
+            return Err(anyhow!(
+                "No json output for {}, expected one of {}",
+                command,
+                COMMANDS.join(", ")
+            ))
+        }
+    };
+    let mut header = Map::new();
+    header.insert(
+        "$schema".to_string(),
+        json!("https://json-schema.org/draft/2020-12/schema"),
+    );
+    header.insert(
+        "$id".to_string(),
+        json!(format!(
+            "urn:xdel:v{}:{}",
+            FORMAT_VERSION,
+            command.replace(' ', "-")
+        )),
+    );
+    let title = if command == "diagnostics" {
+        "The diagnostics event art --progress json prints".to_string()
+    } else if command == "index export" {
+        "The index art index export --json writes".to_string()
+    } else {
+        format!("art {} --format json", command)
+    };
+    header.insert("title".to_string(), json!(title));
+    header.append(&mut schema);
+    Ok(Value::Object(header))
+}
+
+/// The schemas of every command, keyed by command.
+pub fn all() -> Result<Value> {
+    let mut schemas = Map::new();
+    for command in COMMANDS {
+        schemas.insert(command.to_string(), schema(command)?);
+    }
+    Ok(Value::Object(schemas))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{Diagnostic, Kind};
+    use crate::diff::Diff;
+
+    /// Checks `value` against the subset of JSON Schema the schemas use.
+    fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+        if let Some(constant) = schema.get("const") {
+            return if constant == value {
+                Ok(())
+            } else {
+                Err(format!("{} isn't {}", value, constant))
+            };
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            return if allowed.contains(value) {
+                Ok(())
+            } else {
+                Err(format!("{} isn't one of {:?}", value, allowed))
+            };
+        }
+        match (schema["type"].as_str(), value) {
+            (Some("object"), Value::Object(object)) => {
+                for required in schema["required"].as_array().unwrap() {
+                    if !object.contains_key(required.as_str().unwrap()) {
+                        return Err(format!("missing {}", required));
+                    }
+                }
+                for (key, value) in object {
+                    let property = schema["properties"]
+                        .get(key)
+                        .ok_or_else(|| format!("unexpected {}", key))?;
+                    validate(property, value)?;
+                }
+                Ok(())
+            }
+            (Some("array"), Value::Array(items)) => items
+                .iter()
+                .try_for_each(|item| validate(&schema["items"], item)),
+            (Some("string"), Value::String(_)) | (Some("boolean"), Value::Bool(_)) => Ok(()),
+            (Some("integer"), Value::Number(number)) if number.is_u64() || number.is_i64() => {
+                Ok(())
+            }
+            (expected, value) => Err(format!("{} isn't {:?}", value, expected)),
+        }
+    }
+
+    #[test]
+    fn test_schema() -> Result<()> {
+        let schemas = all()?;
+        assert_eq!(schemas.as_object().unwrap().len(), COMMANDS.len());
+        let diff = schema("diff")?;
+        assert_eq!(diff["$id"], format!("urn:xdel:v{}:diff", FORMAT_VERSION));
+        assert_eq!(
+            schema("lint names")?["$id"],
+            format!("urn:xdel:v{}:lint-names", FORMAT_VERSION)
+        );
+        assert!(schema("counts").is_err());
+
+        let output = serde_json::to_value(Diff {
+            added: vec!["title".to_string()],
+            ..Default::default()
+        })?;
+        assert_eq!(validate(&diff, &output), Ok(()));
+        assert!(validate(&diff, &json!({ "added": [] })).is_err());
+
+        let event = json!({
+            "event": "diagnostics",
+            "diagnostics": [
+                serde_json::to_value(Diagnostic {
+                    kind: Kind::TooLarge,
+                    path: Some("res/values/huge.xml".to_string()),
+                    message: String::new(),
+                })?,
+            ],
+        });
+        assert_eq!(validate(&schema("diagnostics")?, &event), Ok(()));
+
+        let listing =
+            json!([{ "name": "old", "locations": ["res/values/strings.xml"], "test_only": true }]);
+        assert_eq!(validate(&schema("ls-unused")?, &listing), Ok(()));
+        assert!(validate(&schema("ls-unused")?, &json!([{ "name": "old" }])).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use crate::index::{self, ResourceIndex};
+use crate::output::{outln, Format};
+use crate::paths;
+use anyhow::Result;
+use regex::Regex;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Match<'a> {
+    name: &'a str,
+    locale: &'a str,
+    value: &'a str,
+    path: &'a str,
+    line: u64,
+}
+
+/// Finds string definitions, in every locale, whose values match `pattern`.
+fn find<'a>(index: &'a ResourceIndex, pattern: &Regex) -> Vec<Match<'a>> {
+    let mut matches: Vec<Match> = index
+        .files()
+        .iter()
+        .flat_map(|file| {
+            file.string_definitions()
+                .iter()
+                .filter(|definition| pattern.is_match(&definition.value))
+                .map(move |definition| Match {
+                    name: index.name(definition.name),
+                    locale: index::locale_of(file.path()),
+                    value: &definition.value,
+                    path: file.path(),
+                    line: definition.line,
+                })
+        })
+        .collect();
+    matches.sort_by(|a, b| (a.name, a.locale, a.path).cmp(&(b.name, b.locale, b.path)));
+
+    matches
+}
+
+/// Prints string definitions whose values match `pattern`. Returns the number
+/// of matches.
+pub fn search(index: &ResourceIndex, pattern: &Regex, format: Format) -> Result<usize> {
+    let matches = find(index, pattern);
+
+    match format {
+        Format::Text => {
+            for m in &matches {
+                outln!(
+                    "{} [{}] \"{}\" {}:{}",
+                    m.name,
+                    m.locale,
+                    m.value,
+                    paths::display(m.path),
+                    m.line
+                );
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&matches)?),
+    }
+
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_find() -> Result<()> {
+        let tmp_dir = TempDir::new("search")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-fr"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="sign_in">Sign in</string><string name="help">Help</string></resources>"#,
+        )?;
+        fs::write(
+            res_dir.join("values-fr/strings.xml"),
+            r#"<resources><string name="sign_in">Se connecter</string><string name="help">Aide: sign in first</string></resources>"#,
+        )?;
+
+        let index = Indexer::new(src_dir, res_dir.clone(), None, None)?.index()?;
+        let found: Vec<(&str, &str)> = find(&index, &Regex::new("(?i)sign in")?)
+            .iter()
+            .map(|m| (m.name, m.locale))
+            .collect();
+        assert_eq!(found, vec![("help", "fr"), ("sign_in", "default")]);
+
+        let found = find(&index, &Regex::new("^Se ")?);
+        assert_eq!(
+            found,
+            vec![Match {
+                name: "sign_in",
+                locale: "fr",
+                value: "Se connecter",
+                path: res_dir.join("values-fr/strings.xml").to_str().unwrap(),
+                line: 1,
+            }]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use crate::index::{self, ResourceIndex};
+use crate::output::{outln, Format};
+use crate::paths;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Definition<'a> {
+    locale: &'a str,
+    value: &'a str,
+    path: &'a str,
+    line: u64,
+}
+
+/// Everything the index knows about one string.
+#[derive(Serialize, Debug, PartialEq)]
+struct Details<'a> {
+    name: &'a str,
+    definitions: Vec<Definition<'a>>,
+    usages: Vec<&'a str>,
+    kept: bool,
+}
+
+fn details<'a>(index: &'a ResourceIndex, name: &str) -> Result<Details<'a>> {
+    let symbol = index
+        .symbol(name)
+        .ok_or_else(|| anyhow!("No string named {}", name))?;
+
+    let mut definitions: Vec<Definition> = index
+        .definitions_of(symbol)
+        .into_iter()
+        .map(|(path, definition)| Definition {
+            locale: index::locale_of(path),
+            value: &definition.value,
+            path,
+            line: definition.line,
+        })
+        .collect();
+    // The default value first, then the rest by locale.
+    definitions.sort_by_key(|d| (d.locale != "default", d.locale, d.path));
+    let mut usages = index
+        .files_for_usage()
+        .get_vec(&symbol)
+        .cloned()
+        .unwrap_or_default();
+    usages.sort_unstable();
+    usages.dedup();
+
+    Ok(Details {
+        name: index.name(symbol),
+        definitions,
+        usages,
+        kept: index.kept_strings().contains(&symbol),
+    })
+}
+
+/// Prints the value of the string `name` in every locale, where it's defined
+/// and where it's used.
+pub fn show(index: &ResourceIndex, name: &str, format: Format) -> Result<()> {
+    let details = details(index, name)?;
+
+    match format {
+        Format::Text => {
+            outln!("{}", details.name);
+            for definition in &details.definitions {
+                outln!(
+                    "  [{}] \"{}\" {}:{}",
+                    definition.locale,
+                    definition.value,
+                    paths::display(definition.path),
+                    definition.line
+                );
+            }
+            if details.usages.is_empty() {
+                let kept = if details.kept { " (kept)" } else { "" };
+                outln!("Unused{}", kept);
+            } else {
+                outln!("Used in");
+                for usage in &details.usages {
+                    outln!("  {}", paths::display(usage));
+                }
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(&details)?),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_details() -> Result<()> {
+        let tmp_dir = TempDir::new("show")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-de"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values-de/strings.xml"),
+            r#"<resources><string name="title">Titel</string></resources>"#,
+        )?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources>
+<string name="title">Title</string>
+</resources>"#,
+        )?;
+        fs::write(
+            src_dir.join("A.java"),
+            "class A { int a = R.string.title; }",
+        )?;
+
+        let index = Indexer::new(src_dir.clone(), res_dir.clone(), None, None)?.index()?;
+        let details = details(&index, "title")?;
+        let locales: Vec<(&str, &str, u64)> = details
+            .definitions
+            .iter()
+            .map(|d| (d.locale, d.value, d.line))
+            .collect();
+        assert_eq!(locales, vec![("default", "Title", 2), ("de", "Titel", 1)]);
+        assert_eq!(
+            details.usages,
+            vec![src_dir.join("A.java").to_str().unwrap()]
+        );
+        assert!(!details.kept);
+
+        assert!(super::details(&index, "missing").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use crate::index::ResourceIndex;
+use crate::output::outln;
+use crate::paths;
+use crate::xeditor;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Works out where definitions from `source` should go. `into` names the file
+/// for the default configuration (e.g. res/values/strings_feature.xml); other
+/// configurations get a file with the same name in their own values directory.
+fn target_for(source: &Path, into: &Path) -> Result<PathBuf> {
+    let res_dir = into
+        .parent()
+        .and_then(Path::parent)
+        .ok_or_else(|| anyhow!("Expected {:?} to be inside a values directory", into))?;
+    let file_name = into
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a file", into))?;
+    let values_dir = source
+        .parent()
+        .and_then(Path::file_name)
+        .ok_or_else(|| anyhow!("Expected {:?} to be inside a values directory", source))?;
+
+    Ok(res_dir.join(values_dir).join(file_name))
+}
+
+/// Moves every string whose name starts with `prefix` out of the file that
+/// defines it and into the matching split file. Returns the number of
+/// definitions that were moved.
+pub fn split_by_prefix(index: &ResourceIndex, prefix: &str, into: &Path) -> Result<usize> {
+    let files_for_definition = index.files_for_definition();
+
+    let mut names_for_file: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (symbol, files) in files_for_definition.iter_all() {
+        let name = index.name(*symbol);
+        if name.starts_with(prefix) {
+            for file in files {
+                names_for_file.entry(*file).or_default().push(name);
+            }
+        }
+    }
+
+    let mut moved = 0;
+    for (source, mut names) in names_for_file {
+        let source = paths::from_stored(source);
+        let source = source.as_path();
+        let target = target_for(source, into)?;
+        if target == source {
+            continue;
+        }
+
+        names.sort();
+        let mut elements = Vec::with_capacity(names.len());
+        for name in names {
+            let mut matcher = xeditor::ElementMatcher::for_local_name("string");
+            matcher.attr("name", name);
+
+            if let Some(element) = xeditor::take_element(source, &matcher)? {
+                elements.push(element);
+            }
+        }
+
+        if !elements.is_empty() {
+            xeditor::append_elements(&target, &elements)?;
+            outln!(
+                "Moved {} strings from {} to {}",
+                elements.len(),
+                source.display(),
+                target.display()
+            );
+            moved += elements.len();
+        }
+    }
+
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_split_moves_all_locales() -> Result<()> {
+        let tmp_dir = TempDir::new("split")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-fr"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            "<resources>\n    <string name=\"app\">App</string>\n    <string name=\"feature_title\">Title</string>\n</resources>\n",
+        )?;
+        fs::write(
+            res_dir.join("values-fr/strings.xml"),
+            "<resources>\n    <string name=\"feature_title\">Titre</string>\n</resources>\n",
+        )?;
+
+        let indexer = Indexer::new(src_dir, res_dir.clone(), None, Some(tmp_dir.path().into()))?;
+        let index = indexer.index()?;
+
+        let mut moved = 0;
+        let output = crate::output::capture(|| {
+            moved = split_by_prefix(
+                &index,
+                "feature_",
+                &res_dir.join("values/strings_feature.xml"),
+            )?;
+            Ok(())
+        })?;
+
+        assert_eq!(moved, 2);
+        // Paths are shown as they are, without quotes or escapes.
+        assert!(output.contains(&format!(
+            "Moved 1 strings from {} to {}",
+            res_dir.join("values/strings.xml").display(),
+            res_dir.join("values/strings_feature.xml").display()
+        )));
+        assert_eq!(
+            fs::read_to_string(res_dir.join("values/strings.xml"))?,
+            "<resources>\n    <string name=\"app\">App</string>\n</resources>\n"
+        );
+        assert!(
+            fs::read_to_string(res_dir.join("values/strings_feature.xml"))?
+                .contains("\n    <string name=\"feature_title\">Title</string>\n</resources>")
+        );
+        assert!(
+            fs::read_to_string(res_dir.join("values-fr/strings_feature.xml"))?
+                .contains("<string name=\"feature_title\">Titre</string>")
+        );
+
+        Ok(())
+    }
+}
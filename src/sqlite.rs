@@ -0,0 +1,354 @@
+use crate::index::{
+    IndexView, ResourceDefinition, ResourceFile, ResourceIndex, ResourceUsage, SourceSet,
+    StringDefinition,
+};
+use crate::mapped::UnsupportedVersion;
+use anyhow::Result;
+use rusqlite::{params, Connection, OpenFlags};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Stored in the database's user_version. Bump it when the schema changes.
+const SCHEMA_VERSION: u32 = 7;
+
+const SCHEMA: &str = "
+    CREATE TABLE files (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        source_set TEXT NOT NULL
+    );
+    CREATE TABLE symbols (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+    CREATE TABLE definitions (
+        symbol_id INTEGER NOT NULL REFERENCES symbols(id),
+        file_id INTEGER NOT NULL REFERENCES files(id),
+        value TEXT NOT NULL,
+        line INTEGER NOT NULL,
+        keep INTEGER NOT NULL
+    );
+    CREATE TABLE usages (
+        symbol_id INTEGER NOT NULL REFERENCES symbols(id),
+        file_id INTEGER NOT NULL REFERENCES files(id)
+    );
+    CREATE TABLE public_strings (
+        symbol_id INTEGER NOT NULL REFERENCES symbols(id),
+        file_id INTEGER NOT NULL REFERENCES files(id)
+    );
+    CREATE TABLE resources (
+        kind TEXT NOT NULL,
+        symbol_id INTEGER NOT NULL REFERENCES symbols(id),
+        file_id INTEGER NOT NULL REFERENCES files(id),
+        line INTEGER NOT NULL,
+        parent_id INTEGER REFERENCES symbols(id)
+    );
+    CREATE TABLE resource_usages (
+        kind TEXT NOT NULL,
+        symbol_id INTEGER NOT NULL REFERENCES symbols(id),
+        file_id INTEGER NOT NULL REFERENCES files(id)
+    );
+    CREATE INDEX definitions_by_symbol ON definitions(symbol_id);
+    CREATE INDEX definitions_by_file ON definitions(file_id);
+    CREATE INDEX usages_by_symbol ON usages(symbol_id);
+    CREATE INDEX usages_by_file ON usages(file_id);
+    CREATE VIEW unused_strings AS
+        SELECT s.id, s.name FROM symbols s
+        WHERE EXISTS (SELECT 1 FROM definitions d WHERE d.symbol_id = s.id)
+        AND NOT EXISTS (SELECT 1 FROM definitions d WHERE d.symbol_id = s.id AND d.keep)
+        AND NOT EXISTS (SELECT 1 FROM usages u WHERE u.symbol_id = s.id)
+        AND NOT EXISTS (SELECT 1 FROM public_strings p WHERE p.symbol_id = s.id);
+";
+
+/// Writes `index` to a fresh sqlite database at `path`.
+pub fn write(index: &ResourceIndex, path: &Path) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+    let tx = conn.transaction()?;
+    {
+        let mut insert_symbol = tx.prepare("INSERT INTO symbols (id, name) VALUES (?1, ?2)")?;
+        for id in 0..index.symbol_count() as u32 {
+            let symbol = crate::symbols::Symbol::from_id(id);
+            insert_symbol.execute(params![id, index.name(symbol)])?;
+        }
+
+        let mut insert_file =
+            tx.prepare("INSERT INTO files (id, path, source_set) VALUES (?1, ?2, ?3)")?;
+        let mut insert_definition = tx.prepare(
+            "INSERT INTO definitions (symbol_id, file_id, value, line, keep)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_usage =
+            tx.prepare("INSERT INTO usages (symbol_id, file_id) VALUES (?1, ?2)")?;
+        let mut insert_public =
+            tx.prepare("INSERT INTO public_strings (symbol_id, file_id) VALUES (?1, ?2)")?;
+        let mut insert_resource = tx.prepare(
+            "INSERT INTO resources (kind, symbol_id, file_id, line, parent_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_resource_usage = tx.prepare(
+            "INSERT INTO resource_usages (kind, symbol_id, file_id) VALUES (?1, ?2, ?3)",
+        )?;
+        for (file_id, file) in index.files().iter().enumerate() {
+            insert_file.execute(params![
+                file_id as i64,
+                file.path(),
+                file.source_set().as_str()
+            ])?;
+            for definition in file.string_definitions() {
+                insert_definition.execute(params![
+                    definition.name.id(),
+                    file_id as i64,
+                    definition.value,
+                    definition.line as i64,
+                    definition.keep
+                ])?;
+            }
+            for usage in file.string_usages() {
+                insert_usage.execute(params![usage.id(), file_id as i64])?;
+            }
+            for public in file.public_strings() {
+                insert_public.execute(params![public.id(), file_id as i64])?;
+            }
+            for definition in file.resource_definitions() {
+                insert_resource.execute(params![
+                    definition.kind.as_str(),
+                    definition.name.id(),
+                    file_id as i64,
+                    definition.line as i64,
+                    definition.parent.map(|parent| parent.id())
+                ])?;
+            }
+            for usage in file.resource_usages() {
+                insert_resource_usage.execute(params![
+                    usage.kind.as_str(),
+                    usage.name.id(),
+                    file_id as i64
+                ])?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// An index stored in sqlite. Queries run against the database rather than
+/// loading everything into memory.
+pub struct SqliteIndex {
+    conn: Connection,
+}
+
+impl SqliteIndex {
+    pub fn open(path: &Path) -> Result<SqliteIndex> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            return Err(UnsupportedVersion(Some(version)).into());
+        }
+
+        Ok(SqliteIndex { conn })
+    }
+
+    fn count(&self, sql: &str) -> Result<usize> {
+        let count: i64 = self.conn.query_row(sql, [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+impl IndexView for SqliteIndex {
+    fn defined_count(&self) -> Result<usize> {
+        self.count("SELECT COUNT(DISTINCT symbol_id) FROM definitions")
+    }
+
+    fn used_count(&self) -> Result<usize> {
+        self.count("SELECT COUNT(DISTINCT symbol_id) FROM usages")
+    }
+
+    fn unused_strings(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let mut statement = self.conn.prepare(
+            "SELECT u.name, f.path FROM unused_strings u
+             JOIN definitions d ON d.symbol_id = u.id
+             JOIN files f ON f.id = d.file_id
+             ORDER BY u.name, f.id",
+        )?;
+        let mut rows = statement.query([])?;
+
+        let mut unused: Vec<(String, Vec<String>)> = Vec::new();
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let path: String = row.get(1)?;
+            match unused.last_mut() {
+                Some((last, files)) if *last == name => files.push(path),
+                _ => unused.push((name, vec![path])),
+            }
+        }
+
+        Ok(unused)
+    }
+
+    fn load(&self) -> Result<ResourceIndex> {
+        let mut names = Vec::new();
+        let mut statement = self.conn.prepare("SELECT name FROM symbols ORDER BY id")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            names.push(row.get::<_, String>(0)?);
+        }
+
+        let mut paths: BTreeMap<i64, (String, SourceSet)> = BTreeMap::new();
+        let mut statement = self
+            .conn
+            .prepare("SELECT id, path, source_set FROM files")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let source_set = row.get::<_, String>(2)?.parse()?;
+            paths.insert(row.get(0)?, (row.get(1)?, source_set));
+        }
+
+        let mut definitions: HashMap<i64, Vec<StringDefinition<String>>> = HashMap::new();
+        let mut statement = self.conn.prepare(
+            "SELECT symbol_id, file_id, value, line, keep FROM definitions ORDER BY rowid",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let symbol = row.get::<_, i64>(0)? as usize;
+            definitions
+                .entry(row.get(1)?)
+                .or_default()
+                .push(StringDefinition {
+                    name: names[symbol].clone(),
+                    value: row.get(2)?,
+                    line: row.get::<_, i64>(3)? as u64,
+                    keep: row.get(4)?,
+                });
+        }
+
+        let mut usages: HashMap<i64, Vec<String>> = HashMap::new();
+        let mut statement = self
+            .conn
+            .prepare("SELECT symbol_id, file_id FROM usages ORDER BY rowid")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let symbol = row.get::<_, i64>(0)? as usize;
+            usages
+                .entry(row.get(1)?)
+                .or_default()
+                .push(names[symbol].clone());
+        }
+
+        let mut public_strings: HashMap<i64, Vec<String>> = HashMap::new();
+        let mut statement = self
+            .conn
+            .prepare("SELECT symbol_id, file_id FROM public_strings ORDER BY rowid")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let symbol = row.get::<_, i64>(0)? as usize;
+            public_strings
+                .entry(row.get(1)?)
+                .or_default()
+                .push(names[symbol].clone());
+        }
+
+        let mut resources: HashMap<i64, Vec<ResourceDefinition<String>>> = HashMap::new();
+        let mut statement = self.conn.prepare(
+            "SELECT kind, symbol_id, file_id, line, parent_id FROM resources ORDER BY rowid",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let symbol = row.get::<_, i64>(1)? as usize;
+            resources
+                .entry(row.get(2)?)
+                .or_default()
+                .push(ResourceDefinition {
+                    kind: row.get::<_, String>(0)?.parse()?,
+                    name: names[symbol].clone(),
+                    line: row.get::<_, i64>(3)? as u64,
+                    parent: row
+                        .get::<_, Option<i64>>(4)?
+                        .map(|parent| names[parent as usize].clone()),
+                });
+        }
+
+        let mut resource_usages: HashMap<i64, Vec<ResourceUsage<String>>> = HashMap::new();
+        let mut statement = self
+            .conn
+            .prepare("SELECT kind, symbol_id, file_id FROM resource_usages ORDER BY rowid")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let symbol = row.get::<_, i64>(1)? as usize;
+            resource_usages
+                .entry(row.get(2)?)
+                .or_default()
+                .push(ResourceUsage {
+                    kind: row.get::<_, String>(0)?.parse()?,
+                    name: names[symbol].clone(),
+                });
+        }
+
+        Ok(ResourceIndex::new(
+            paths
+                .into_iter()
+                .map(|(id, (path, source_set))| {
+                    ResourceFile::new(
+                        path,
+                        definitions.remove(&id).unwrap_or_default(),
+                        usages.remove(&id).unwrap_or_default(),
+                    )
+                    .with_source_set(source_set)
+                    .with_public_strings(public_strings.remove(&id).unwrap_or_default())
+                    .with_resources(
+                        resources.remove(&id).unwrap_or_default(),
+                        resource_usages.remove(&id).unwrap_or_default(),
+                    )
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_sqlite_round_trip() -> Result<()> {
+        let tmp_dir = TempDir::new("sqlite")?;
+        let res_dir = tmp_dir.path().join("res/values");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(&res_dir)?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("strings.xml"),
+            r#"<resources><string name="used">A</string><string name="unused">B</string></resources>"#,
+        )?;
+        fs::write(src_dir.join("A.java"), "class A { int a = R.string.used; }")?;
+
+        let indexer = Indexer::new(src_dir, tmp_dir.path().join("res"), None, None)?;
+        let file = tmp_dir.path().join("index.sqlite");
+        write(&indexer.index()?, &file)?;
+
+        let stored = SqliteIndex::open(&file)?;
+        assert_eq!(stored.defined_count()?, 2);
+        assert_eq!(stored.used_count()?, 1);
+        let unused = stored.unused_strings()?;
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].0, "unused");
+        assert_eq!(
+            unused[0].1,
+            vec![res_dir.join("strings.xml").to_str().unwrap().to_string()]
+        );
+
+        let index = stored.load()?;
+        assert_eq!(index.unused_strings().len(), 1);
+        let unused = index.symbol("unused").unwrap();
+        assert_eq!(index.definitions_of(unused)[0].1.value, "B");
+
+        Ok(())
+    }
+}
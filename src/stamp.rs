@@ -0,0 +1,324 @@
+use crate::cache;
+use crate::git;
+use crate::index::canonical_path;
+use crate::paths;
+use anyhow::{anyhow, Error, Result};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What to do when a query finds the cached index stale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StalePolicy {
+    Error,
+    Warn,
+    /// Indexes again first, only the changed directories with
+    /// --cache-per-directory.
+    Refresh,
+}
+
+impl FromStr for StalePolicy {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "error" => Ok(StalePolicy::Error),
+            "warn" => Ok(StalePolicy::Warn),
+            "refresh" => Ok(StalePolicy::Refresh),
+            _ => Err(anyhow!("Unrecognized stale policy {}", value)),
+        }
+    }
+}
+
+/// The state of the working tree an index was built from, stored beside it
+/// to tell when it's gone stale.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct Stamp {
+    /// When the index was built, in seconds since the unix epoch.
+    pub built: u64,
+    /// The commit checked out, if the roots are in a git repository.
+    pub commit: Option<String>,
+    /// The files with uncommitted changes, with fingerprints of their sizes
+    /// and modification times, so that changing them again counts too.
+    pub dirty: BTreeMap<String, u64>,
+    /// Outside a git repository, the latest modification time of any file or
+    /// directory under the roots, in nanoseconds since the unix epoch.
+    #[serde(default)]
+    pub modified: Option<u64>,
+}
+
+/// The latest modification time of anything under `roots` but `skip`, in
+/// nanoseconds since the unix epoch.
+fn last_modified(roots: &[&Path], skip: &Path) -> u64 {
+    let mut latest = 0;
+    for root in roots.iter().collect::<BTreeSet<_>>() {
+        let skip = skip.to_path_buf();
+        let walk = WalkBuilder::new(root)
+            .filter_entry(move |entry| entry.path() != skip)
+            .build();
+        for entry in walk.filter_map(|entry| entry.ok()) {
+            let modified = entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+            if let Some(modified) = modified {
+                latest = latest.max(modified.as_nanos() as u64);
+            }
+        }
+    }
+    latest
+}
+
+fn fingerprint(path: &Path) -> u64 {
+    let mut hash = cache::Fnv::default();
+    if let Ok(metadata) = path.metadata() {
+        hash.write(&metadata.len().to_le_bytes());
+        if let Ok(since_epoch) = metadata.modified().map(|m| m.duration_since(UNIX_EPOCH)) {
+            hash.write(&since_epoch.map_or(0, |d| d.as_nanos()).to_le_bytes());
+        }
+    }
+    hash.finish()
+}
+
+impl Stamp {
+    /// Stamps the working tree as it is now. The commit is that of the
+    /// repository containing the first root, and the dirty files are those
+    /// under any of `roots`. Outside git, it's the latest modification time
+    /// under `roots`, leaving out `cache_dir`.
+    pub fn take(roots: &[&Path], cache_dir: &Path) -> Stamp {
+        let built = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let commit = roots.first().and_then(|root| git::head_commit(root).ok());
+        let mut dirty = BTreeMap::new();
+        if commit.is_some() {
+            let mut seen = BTreeSet::new();
+            for root in roots {
+                if !seen.insert(root) {
+                    continue;
+                }
+                for file in git::dirty_files(root).unwrap_or_default() {
+                    let path = root.join(file);
+                    dirty.insert(paths::to_stored(&path), fingerprint(&path));
+                }
+            }
+        }
+
+        let modified = match commit {
+            Some(_) => None,
+            None => Some(last_modified(roots, cache_dir)),
+        };
+
+        Stamp {
+            built,
+            commit,
+            dirty,
+            modified,
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Option<Stamp>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_reader(BufReader::new(File::open(
+            path,
+        )?))?))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Takes the state of `files` from `now`, for an index that's had just
+    /// them indexed again. Outside git, where changes aren't told apart, that
+    /// trusts `files` to be all that changed.
+    pub fn refresh(&mut self, now: &Stamp, files: &[PathBuf]) {
+        if self.modified.is_some() {
+            self.modified = now.modified;
+        }
+        let files: HashSet<PathBuf> = files.iter().map(|file| canonical_path(file)).collect();
+        let refreshed = |path: &String| files.contains(&canonical_path(&paths::from_stored(path)));
+        self.dirty.retain(|path, _| !refreshed(path));
+        for (path, fingerprint) in &now.dirty {
+            if refreshed(path) {
+                self.dirty.insert(path.clone(), *fingerprint);
+            }
+        }
+    }
+
+    /// Why an index stamped with this is stale in a working tree stamped
+    /// `now`, if it is.
+    pub fn staleness(&self, now: &Stamp) -> Option<String> {
+        match (&self.commit, &now.commit) {
+            (Some(built), Some(now)) if built != now => {
+                return Some(format!(
+                    "HEAD moved from {} to {}",
+                    &built[..built.len().min(8)],
+                    &now[..now.len().min(8)]
+                ))
+            }
+            _ => {}
+        }
+        if let (Some(built), Some(now)) = (self.modified, now.modified) {
+            if now > built {
+                return Some("files changed since it was built".to_string());
+            }
+        }
+        let changed = self
+            .dirty
+            .iter()
+            .filter(|(path, fingerprint)| now.dirty.get(*path) != Some(fingerprint))
+            .count()
+            + now
+                .dirty
+                .keys()
+                .filter(|path| !self.dirty.contains_key(*path))
+                .count();
+        if changed > 0 {
+            return Some(format!(
+                "{} uncommitted file{} changed since it was built",
+                changed,
+                if changed == 1 { "" } else { "s" }
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use std::process::Command;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_stale_index() -> Result<()> {
+        let tmp_dir = TempDir::new("stamp")?;
+        let root = tmp_dir.path();
+        let git = |args: &[&str]| -> Result<()> {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(root)
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .status()?;
+            assert!(status.success());
+            Ok(())
+        };
+        fs::create_dir_all(root.join("res/values"))?;
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(
+            root.join("res/values/strings.xml"),
+            r#"<resources><string name="title">T</string></resources>"#,
+        )?;
+        fs::write(root.join("src/Main.java"), "R.string.title;")?;
+        fs::write(root.join(".gitignore"), "cache/\n")?;
+        git(&["init", "-q"])?;
+        git(&["add", "."])?;
+        git(&["commit", "-q", "-m", "Initial"])?;
+
+        // Each run stamps the working tree once, so each gets an indexer.
+        let indexer = |stale| -> Result<Indexer> {
+            let mut indexer = Indexer::new(
+                root.join("src"),
+                root.join("res"),
+                None,
+                Some(root.join("cache")),
+            )?;
+            indexer.stale(stale);
+            Ok(indexer)
+        };
+        let built = indexer(StalePolicy::Error)?;
+        built.serialize(&built.index()?)?;
+        assert!(indexer(StalePolicy::Error)?.deserialize().is_ok());
+
+        fs::write(root.join("src/Main.java"), "// Gone")?;
+        let error = indexer(StalePolicy::Error)?
+            .deserialize()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("1 uncommitted file changed since it was built"));
+
+        // Refreshing brings it up to date, dirty files and all.
+        let refreshed = indexer(StalePolicy::Refresh)?.deserialize()?;
+        assert_eq!(refreshed.unused_strings().len(), 1);
+        assert!(indexer(StalePolicy::Error)?.deserialize().is_ok());
+
+        git(&["commit", "-q", "-am", "Remove usage"])?;
+        let error = indexer(StalePolicy::Error)?
+            .deserialize()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("HEAD moved from"), "{}", error);
+
+        assert_eq!("refresh".parse::<StalePolicy>()?, StalePolicy::Refresh);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_index_outside_git() -> Result<()> {
+        let tmp_dir = TempDir::new("stamp")?;
+        let root = tmp_dir.path();
+        let strings = root.join("res/values/strings.xml");
+        fs::create_dir_all(strings.parent().unwrap())?;
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(
+            &strings,
+            r#"<resources><string name="title">T</string></resources>"#,
+        )?;
+        fs::write(root.join("src/Main.java"), "R.string.title;")?;
+        let indexer = || -> Result<Indexer> {
+            let mut indexer = Indexer::new(
+                root.join("src"),
+                root.join("res"),
+                None,
+                Some(root.join("cache")),
+            )?;
+            indexer.stale(StalePolicy::Error);
+            Ok(indexer)
+        };
+
+        let built = indexer()?;
+        built.serialize(&built.index()?)?;
+        let stamp = built
+            .cache_status()?
+            .and_then(|status| status.stamp)
+            .unwrap();
+        assert_eq!(stamp.commit, None);
+        assert!(stamp.modified.is_some());
+        // Writing the cache doesn't make the index stale.
+        assert!(indexer()?.deserialize().is_ok());
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(root.join("src/Main.java"))?
+            .set_modified(later)?;
+        let error = indexer()?.deserialize().err().unwrap().to_string();
+        assert!(
+            error.contains("files changed since it was built"),
+            "{}",
+            error
+        );
+
+        // Telling it what changed brings it up to date.
+        let updated = indexer()?;
+        updated.update_files(&[root.join("src/Main.java")])?;
+        assert!(indexer()?.deserialize().is_ok());
+
+        Ok(())
+    }
+}
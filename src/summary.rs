@@ -0,0 +1,167 @@
+use crate::filter::Filter;
+use crate::index::ResourceIndex;
+use crate::symbols::Symbol;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Write;
+use std::path::{Component, Path};
+
+/// The module a resource file belongs to: the directory holding its res
+/// directory, skipping over a src/<source set> in between.
+pub fn module_of(path: &str) -> String {
+    let components: Vec<&str> = Path::new(path)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect();
+
+    let res = match components.iter().rposition(|c| *c == "res") {
+        Some(res) => res,
+        None => return ".".to_string(),
+    };
+    let module = if res >= 3 && components[res - 2] == "src" {
+        res - 3
+    } else if res >= 1 {
+        res - 1
+    } else {
+        return ".".to_string();
+    };
+
+    components[module].to_string()
+}
+
+fn escape_cell(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// Writes a Markdown description of a removal, for the commit or pull request
+/// that makes it.
+pub fn write_summary(
+    out: &mut impl Write,
+    index: &ResourceIndex,
+    removed: &[Symbol],
+    filter: &Filter,
+) -> Result<()> {
+    let removed: HashSet<&Symbol> = removed.iter().collect();
+
+    let mut modules: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    // Each string's value in the default configuration, if it has one.
+    let mut values: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+    for file in index.files() {
+        let is_default = Path::new(file.path())
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == "values");
+        for definition in file.string_definitions() {
+            if !removed.contains(&definition.name)
+                || !filter.matches_definition(file.path(), &definition.value)
+            {
+                continue;
+            }
+            let name = index.name(definition.name);
+            modules
+                .entry(module_of(file.path()))
+                .or_default()
+                .insert(name);
+            let value = values.entry(name).or_default();
+            if value.is_none() || is_default {
+                *value = Some(&definition.value);
+            }
+        }
+    }
+
+    writeln!(out, "## Remove {} unused strings", values.len())?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "These strings are defined in resources but never referenced from code, \
+         layouts or manifests."
+    )?;
+    writeln!(out)?;
+    writeln!(out, "| Module | Strings |")?;
+    writeln!(out, "| --- | ---: |")?;
+    for (module, names) in &modules {
+        writeln!(out, "| {} | {} |", escape_cell(module), names.len())?;
+    }
+    writeln!(out)?;
+    writeln!(out, "<details><summary>Removed strings</summary>")?;
+    writeln!(out)?;
+    writeln!(out, "| Name | Value |")?;
+    writeln!(out, "| --- | --- |")?;
+    for (name, value) in &values {
+        writeln!(out, "| {} | {} |", name, escape_cell(value.unwrap_or("")))?;
+    }
+    writeln!(out)?;
+    writeln!(out, "</details>")?;
+    writeln!(out)?;
+    writeln!(out, "### Caveats")?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "- Strings looked up by name at runtime (`Resources.getIdentifier`, \
+         server-driven keys, reflection) look unused. Check that none of these \
+         are."
+    )?;
+    writeln!(
+        out,
+        "- To keep a string, list it in `.xdelignore` or mark its definition \
+         with `<!-- xdel:keep -->`."
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{ResourceFile, StringDefinition};
+
+    #[test]
+    fn test_module_of() {
+        assert_eq!(
+            module_of("/repo/feature_x/src/main/res/values/strings.xml"),
+            "feature_x"
+        );
+        assert_eq!(module_of("/repo/app/res/values-fr/strings.xml"), "app");
+        assert_eq!(module_of("res/values/strings.xml"), ".");
+    }
+
+    #[test]
+    fn test_write_summary() -> Result<()> {
+        let definition = |name: &str, value: &str| StringDefinition {
+            name: name.to_string(),
+            value: value.to_string(),
+            line: 1,
+            keep: false,
+        };
+        let index = ResourceIndex::new(vec![
+            ResourceFile::new(
+                "/repo/app/src/main/res/values-fr/strings.xml".to_string(),
+                vec![definition("old", "Vieux")],
+                Vec::new(),
+            ),
+            ResourceFile::new(
+                "/repo/app/src/main/res/values/strings.xml".to_string(),
+                vec![definition("old", "Old | gone"), definition("kept", "Kept")],
+                Vec::new(),
+            ),
+        ]);
+
+        let mut out = Vec::new();
+        let old = index.symbol("old").unwrap();
+        write_summary(&mut out, &index, &[old], &Filter::default())?;
+        let summary = String::from_utf8(out)?;
+        assert!(summary.starts_with("## Remove 1 unused strings"));
+        assert!(summary.contains("| app | 1 |"));
+        assert!(summary.contains("| old | Old \\| gone |"));
+        assert!(!summary.contains("kept"));
+
+        Ok(())
+    }
+}
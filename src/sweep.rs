@@ -0,0 +1,103 @@
+use crate::index::{self, ResourceIndex};
+use crate::symbols::Symbol;
+use std::collections::{BTreeMap, HashSet};
+
+/// Strings defined for some locale but in no values directory without a
+/// locale qualifier, like the translations left behind when a string was
+/// removed before removals swept every locale. Each comes with the files
+/// defining it, in name order.
+pub fn orphaned_translations(index: &ResourceIndex) -> Vec<(Symbol, Vec<String>)> {
+    let defaults: HashSet<Symbol> = index
+        .files()
+        .iter()
+        .filter(|file| index::language_of(file.path()).is_none())
+        .flat_map(|file| file.string_definitions())
+        .map(|definition| definition.name)
+        .collect();
+
+    let mut orphaned: BTreeMap<&str, (Symbol, Vec<String>)> = BTreeMap::new();
+    for file in index.files() {
+        if index::language_of(file.path()).is_none() {
+            continue;
+        }
+        for definition in file.string_definitions() {
+            if !defaults.contains(&definition.name) {
+                orphaned
+                    .entry(index.name(definition.name))
+                    .or_insert_with(|| (definition.name, Vec::new()))
+                    .1
+                    .push(file.path().to_string());
+            }
+        }
+    }
+
+    orphaned
+        .into_values()
+        .map(|(name, mut files)| {
+            files.sort();
+            files.dedup();
+            (name, files)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use anyhow::Result;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_orphaned_translations() -> Result<()> {
+        assert_eq!(index::language_of("res/values/strings.xml"), None);
+        assert_eq!(index::language_of("res/values-night/strings.xml"), None);
+        assert_eq!(index::language_of("res/values-car/strings.xml"), None);
+        assert_eq!(index::language_of("res/values-fr/strings.xml"), Some("fr"));
+        assert_eq!(
+            index::language_of("res/values-mcc310-pt-rBR-night/strings.xml"),
+            Some("pt-rBR")
+        );
+        assert_eq!(
+            index::language_of("res/values-b+sr+Latn/strings.xml"),
+            Some("b+sr+Latn")
+        );
+        assert_eq!(
+            index::language_of("res/values-es-r419/strings.xml"),
+            Some("es-r419")
+        );
+
+        let tmp_dir = TempDir::new("sweep")?;
+        let res_dir = tmp_dir.path().join("res");
+        for (dir, content) in [
+            ("values", r#"<string name="kept">Kept</string>"#),
+            ("values-night", r#"<string name="dark">Dark</string>"#),
+            (
+                "values-fr",
+                r#"<string name="kept">Gardé</string><string name="gone">Parti</string><string name="dark">Sombre</string>"#,
+            ),
+            ("values-de", r#"<string name="gone">Weg</string>"#),
+        ] {
+            fs::create_dir_all(res_dir.join(dir))?;
+            fs::write(
+                res_dir.join(dir).join("strings.xml"),
+                format!("<resources>{}</resources>", content),
+            )?;
+        }
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+
+        let orphaned: Vec<(&str, Vec<String>)> = orphaned_translations(&index)
+            .into_iter()
+            .map(|(name, files)| (index.name(name), files))
+            .collect();
+        let path = |dir: &str| res_dir.join(dir).join("strings.xml").display().to_string();
+        assert_eq!(
+            orphaned,
+            vec![("gone", vec![path("values-de"), path("values-fr")])]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// A compact id standing in for a resource name in a `SymbolTable`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn from_id(id: u32) -> Symbol {
+        Symbol(id)
+    }
+
+    pub fn id(self) -> u32 {
+        self.0
+    }
+}
+
+/// Stores each distinct resource name once, handing out `Symbol`s for them.
+/// Only the names are serialized; the reverse lookup is rebuilt on load.
+#[derive(Default, Clone)]
+pub struct SymbolTable {
+    names: Vec<String>,
+    ids: HashMap<String, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn intern(&mut self, name: String) -> Symbol {
+        if let Some(symbol) = self.ids.get(&name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.clone());
+        self.ids.insert(name, symbol);
+        symbol
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+impl From<Vec<String>> for SymbolTable {
+    fn from(names: Vec<String>) -> SymbolTable {
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), Symbol(i as u32)))
+            .collect();
+
+        SymbolTable { names, ids }
+    }
+}
+
+impl Serialize for SymbolTable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.names.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SymbolTable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SymbolTable, D::Error> {
+        Vec::<String>::deserialize(deserializer).map(SymbolTable::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_round_trip() {
+        let mut table = SymbolTable::default();
+        let foo = table.intern("foo".to_string());
+        let bar = table.intern("bar".to_string());
+
+        assert_eq!(table.intern("foo".to_string()), foo);
+        assert_ne!(foo, bar);
+
+        let table = SymbolTable::from(
+            bincode::deserialize::<Vec<String>>(&bincode::serialize(&table).unwrap()).unwrap(),
+        );
+        assert_eq!(table.resolve(bar), "bar");
+        assert_eq!(table.lookup("foo"), Some(foo));
+        assert_eq!(table.len(), 2);
+    }
+}
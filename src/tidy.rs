@@ -0,0 +1,49 @@
+use crate::index::ResourceIndex;
+use crate::output::outln;
+use crate::paths;
+use crate::xeditor::{self, TidyOptions};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// The values files in the index, in path order.
+fn values_files(index: &ResourceIndex) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = index
+        .files()
+        .iter()
+        .map(|file| paths::from_stored(file.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "xml")
+                && path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .and_then(|dir| dir.to_str())
+                    .is_some_and(|dir| dir == "values" || dir.starts_with("values-"))
+                && path.is_file()
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Removes orphaned comments and long runs of blank lines from every values
+/// file, listing each as it goes. With `dry_run` only lists them. Returns the
+/// number of comments and runs of blank lines removed.
+pub fn tidy(index: &ResourceIndex, options: &TidyOptions) -> Result<usize> {
+    let mut removed = 0;
+    for path in values_files(index) {
+        let report = xeditor::tidy(&path, options)?;
+        for edit in &report.edits {
+            let what = if edit.old_text.contains("<!--") {
+                let comment = edit.old_text.trim().lines().next().unwrap_or_default();
+                format!("comment {}", comment)
+            } else {
+                let lines = edit.end_line - edit.start_line + 1;
+                format!("{} blank line{}", lines, if lines == 1 { "" } else { "s" })
+            };
+            outln!("{}:{} {}", path.display(), edit.start_line, what);
+        }
+        removed += report.edits.len();
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use tracing_chrome::{ChromeLayer, ChromeLayerBuilder, FlushGuard};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Registry;
+
+/// Records the spans of indexing and editing to `path` in the Chrome trace
+/// format (for chrome://tracing or Perfetto), with a track per thread and
+/// the file each span worked on. The trace is finished when the returned
+/// guard is dropped.
+pub fn start(path: &Path) -> Result<FlushGuard> {
+    let (layer, guard) = chrome_layer(path)?;
+    tracing_subscriber::registry().with(layer).init();
+
+    Ok(guard)
+}
+
+fn chrome_layer(path: &Path) -> Result<(ChromeLayer<Registry>, FlushGuard)> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+    Ok(ChromeLayerBuilder::new()
+        .writer(BufWriter::new(file))
+        .include_args(true)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use crate::xeditor::{self, ElementMatcher};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_trace() -> Result<()> {
+        let tmp_dir = TempDir::new("trace")?;
+        let res_dir = tmp_dir.path().join("res");
+        let strings = res_dir.join("values/strings.xml");
+        fs::create_dir_all(strings.parent().unwrap())?;
+        fs::write(
+            &strings,
+            r#"<resources><string name="a">A</string></resources>"#,
+        )?;
+
+        // Spans on this thread are traced, with the file they worked on.
+        let trace = tmp_dir.path().join("trace.json");
+        let (layer, guard) = chrome_layer(&trace)?;
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || -> Result<()> {
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+            let mut matcher = ElementMatcher::for_local_name("string");
+            matcher.attr("name", "a");
+            xeditor::take_element(&strings, &matcher)?;
+            Ok(())
+        })?;
+        drop(guard);
+
+        let events: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(&trace)?)?;
+        let named = |name: &str| events.iter().find(|event| event["name"] == name);
+        assert!(named("index").is_some());
+        let taken = named("take_element").expect("take_element span");
+        assert_eq!(taken["args"]["path"], strings.display().to_string());
+
+        Ok(())
+    }
+}
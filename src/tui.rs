@@ -0,0 +1,271 @@
+use crate::index::ResourceIndex;
+use crate::symbols::Symbol;
+use anyhow::Result;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Lines reserved at the bottom of the screen for the selected string.
+const DETAIL_HEIGHT: u16 = 8;
+
+struct Item<'a> {
+    symbol: Symbol,
+    name: &'a str,
+    value: String,
+    locations: Vec<String>,
+    delete: bool,
+}
+
+struct Review<'a> {
+    items: Vec<Item<'a>>,
+    selected: usize,
+    scroll: usize,
+}
+
+/// Puts the terminal back the way we found it, even if we bail out early.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn truncate(line: &str, width: u16) -> String {
+    line.chars().take(width as usize).collect()
+}
+
+/// Picks the value from the default configuration (res/values) if there is
+/// one, since that's the one people recognize.
+fn default_value(index: &ResourceIndex, symbol: Symbol) -> String {
+    let definitions = index.definitions_of(symbol);
+    let default = definitions.iter().find(|(path, _)| {
+        Path::new(path)
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == "values")
+    });
+
+    default
+        .or_else(|| definitions.first())
+        .map(|(_, definition)| definition.value.trim().to_string())
+        .unwrap_or_default()
+}
+
+impl<'a> Review<'a> {
+    fn new(index: &'a ResourceIndex, symbols: Vec<Symbol>) -> Review<'a> {
+        let items = symbols
+            .into_iter()
+            .map(|symbol| Item {
+                symbol,
+                name: index.name(symbol),
+                value: default_value(index, symbol),
+                locations: index
+                    .definitions_of(symbol)
+                    .iter()
+                    .map(|(path, definition)| format!("{}:{}", path, definition.line))
+                    .collect(),
+                delete: false,
+            })
+            .collect();
+
+        Review {
+            items,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let last = self.items.len() - 1;
+        self.selected = (self.selected as isize + delta).clamp(0, last as isize) as usize;
+    }
+
+    fn mark(&mut self, delete: Option<bool>) {
+        if let Some(item) = self.items.get_mut(self.selected) {
+            item.delete = delete.unwrap_or(!item.delete);
+        }
+        self.move_by(1);
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let list_height = height.saturating_sub(DETAIL_HEIGHT + 1).max(1) as usize;
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+
+        queue!(out, Clear(ClearType::All))?;
+        for row in 0..list_height {
+            let index = self.scroll + row;
+            let item = match self.items.get(index) {
+                Some(item) => item,
+                None => break,
+            };
+            let mark = if item.delete { "[D]" } else { "[ ]" };
+            let line = truncate(&format!("{} {}", mark, item.name), width);
+            queue!(out, MoveTo(0, row as u16))?;
+            if index == self.selected {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(line))?;
+                queue!(out, SetAttribute(Attribute::Reset))?;
+            } else {
+                queue!(out, Print(line))?;
+            }
+        }
+
+        let mut row = list_height as u16;
+        queue!(out, MoveTo(0, row), Print("-".repeat(width as usize)))?;
+        if let Some(item) = self.items.get(self.selected) {
+            let mut details = vec![format!("Value: {}", item.value)];
+            details.extend(item.locations.iter().map(|loc| format!("  {}", loc)));
+            for detail in details.iter().take(DETAIL_HEIGHT as usize - 2) {
+                row += 1;
+                queue!(out, MoveTo(0, row), Print(truncate(detail, width)))?;
+            }
+        }
+
+        let marked = self.items.iter().filter(|item| item.delete).count();
+        let status = format!(
+            "{}/{}  {} marked  [d]elete [k]eep [space] toggle [a]pply [q]uit",
+            self.selected + 1,
+            self.items.len(),
+            marked
+        );
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1)),
+            SetAttribute(Attribute::Reverse),
+            Print(truncate(&status, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Shows the given unused strings and lets the user decide which to delete.
+/// Returns the names marked for deletion, or nothing if the user quit without
+/// applying.
+pub fn review(index: &ResourceIndex, symbols: Vec<Symbol>) -> Result<Vec<Symbol>> {
+    let mut review = Review::new(index, symbols);
+    let mut out = io::stdout();
+    let _guard = TerminalGuard::enter()?;
+
+    loop {
+        review.draw(&mut out)?;
+
+        let page = terminal::size()?.1.saturating_sub(DETAIL_HEIGHT + 1).max(1) as isize;
+        if let Event::Key(KeyEvent { code, kind, .. }) = event::read()? {
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            match code {
+                KeyCode::Up => review.move_by(-1),
+                KeyCode::Down => review.move_by(1),
+                KeyCode::PageUp => review.move_by(-page),
+                KeyCode::PageDown => review.move_by(page),
+                KeyCode::Home => review.selected = 0,
+                KeyCode::End => review.move_by(isize::MAX / 2),
+                KeyCode::Char('d') => review.mark(Some(true)),
+                KeyCode::Char('k') => review.mark(Some(false)),
+                KeyCode::Char(' ') | KeyCode::Enter => review.mark(None),
+                KeyCode::Char('a') => {
+                    return Ok(review
+                        .items
+                        .into_iter()
+                        .filter(|item| item.delete)
+                        .map(|item| item.symbol)
+                        .collect());
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Vec::new()),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_move_and_mark() -> Result<()> {
+        let tmp_dir = TempDir::new("tui")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="a">A</string><string name="b">B</string><string name="c">C</string></resources>"#,
+        )?;
+        let index = Indexer::new(tmp_dir.path().join("src"), res_dir, None, None)?.index()?;
+        let symbols = ["a", "b", "c"]
+            .iter()
+            .filter_map(|name| index.symbol(name))
+            .collect();
+        let mut review = Review::new(&index, symbols);
+        let marked = |review: &Review| -> Vec<bool> {
+            review.items.iter().map(|item| item.delete).collect()
+        };
+
+        review.move_by(-1);
+        assert_eq!(review.selected, 0);
+        review.move_by(isize::MAX / 2);
+        assert_eq!(review.selected, 2);
+        review.move_by(-10);
+        assert_eq!(review.selected, 0);
+
+        // Marking moves on, but not past the last string.
+        review.mark(None);
+        assert_eq!(
+            (marked(&review), review.selected),
+            (vec![true, false, false], 1)
+        );
+        review.mark(Some(false));
+        review.mark(Some(true));
+        assert_eq!(
+            (marked(&review), review.selected),
+            (vec![true, false, true], 2)
+        );
+        review.mark(None);
+        assert_eq!(
+            (marked(&review), review.selected),
+            (vec![true, false, false], 2)
+        );
+        review.mark(Some(true));
+        review.mark(Some(true));
+        assert_eq!(marked(&review), vec![true, false, true]);
+        review.move_by(-2);
+        review.mark(None);
+        assert_eq!(marked(&review), vec![false, false, true]);
+
+        let mut empty = Review::new(&index, Vec::new());
+        empty.move_by(1);
+        empty.mark(None);
+        assert_eq!(empty.selected, 0);
+
+        Ok(())
+    }
+}
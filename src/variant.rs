@@ -0,0 +1,174 @@
+use crate::index::{self, ResourceFile, ResourceIndex, SourceSet};
+use crate::symbols::Symbol;
+use anyhow::{anyhow, Error, Result};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// A build variant, as the source sets (directories under src/) it's built
+/// from besides main, e.g. paidRelease=paid,release,paidRelease.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub source_sets: Vec<String>,
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, source_sets) = match value.split_once('=') {
+            Some((name, source_sets)) => (name, source_sets.split(',').collect()),
+            None => (value, vec![value]),
+        };
+        if name.is_empty() || source_sets.iter().any(|set| set.is_empty()) {
+            return Err(anyhow!(
+                "Expected a variant like paidRelease=paid,release,paidRelease, not {}",
+                value
+            ));
+        }
+        Ok(Variant {
+            name: name.to_string(),
+            source_sets: source_sets.into_iter().map(str::to_string).collect(),
+        })
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl Variant {
+    /// Whether `file` is built into this variant: it's shared, in main or
+    /// one of the variant's source sets, or in their tests.
+    pub fn includes<N>(&self, file: &ResourceFile<N>) -> bool {
+        let dir = match index::source_set_dir(file.path()) {
+            Some(dir) => dir,
+            None => return true,
+        };
+        match SourceSet::split(dir) {
+            (SourceSet::Main, "main") | (_, "") => true,
+            (SourceSet::Main, set) => self.source_sets.iter().any(|name| name == set),
+            (_, set) => self.source_sets.iter().any(|name| capitalize(name) == set),
+        }
+    }
+}
+
+/// Strings defined in `files` but not used from any of them, leaving out
+/// kept strings.
+fn unused_in<'a>(
+    index: &ResourceIndex,
+    files: impl Iterator<Item = &'a ResourceFile> + Clone,
+) -> HashSet<Symbol> {
+    let used: HashSet<Symbol> = files
+        .clone()
+        .flat_map(|file| file.string_usages().iter().copied())
+        .collect();
+    let kept = index.kept_strings();
+
+    files
+        .flat_map(|file| file.string_definitions())
+        .map(|definition| definition.name)
+        .filter(|name| !used.contains(name) && !kept.contains(name))
+        .collect()
+}
+
+/// Strings defined in `variant`'s files but not used by any of them.
+pub fn unused_in_variant(index: &ResourceIndex, variant: &Variant) -> HashSet<Symbol> {
+    unused_in(
+        index,
+        index.files().iter().filter(|file| variant.includes(*file)),
+    )
+}
+
+/// Strings unused by every one of `variants`: usages from source sets that
+/// aren't in any of them, such as a dropped flavor's, don't count. With no
+/// variants, every file counts.
+pub fn unused_strings(index: &ResourceIndex, variants: &[Variant]) -> HashSet<Symbol> {
+    if variants.is_empty() {
+        return index.unused_strings();
+    }
+
+    let used: HashSet<Symbol> = index
+        .files()
+        .iter()
+        .filter(|file| variants.iter().any(|variant| variant.includes(*file)))
+        .flat_map(|file| file.string_usages().iter().copied())
+        .collect();
+    index
+        .defined_strings()
+        .difference(&index.kept_strings())
+        .filter(|name| !used.contains(name))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_unused_strings() -> Result<()> {
+        let tmp_dir = TempDir::new("variant")?;
+        let app = tmp_dir.path().join("app");
+        let write = |path: &str, content: &str| -> Result<()> {
+            let path = app.join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            Ok(fs::write(path, content)?)
+        };
+        write(
+            "src/main/res/values/strings.xml",
+            r#"<resources><string name="common">C</string><string name="paid_only">P</string><string name="free_only">F</string><string name="debug_only">D</string></resources>"#,
+        )?;
+        write(
+            "src/main/java/Main.java",
+            "class Main { int a = R.string.common; }",
+        )?;
+        write(
+            "src/paid/java/Paid.java",
+            "class Paid { int a = R.string.paid_only; }",
+        )?;
+        write(
+            "src/free/java/Free.java",
+            "class Free { int a = R.string.free_only; }",
+        )?;
+        write(
+            "src/debug/java/Debug.java",
+            "class Debug { int a = R.string.debug_only; }",
+        )?;
+        write(
+            "src/testPaid/java/PaidTest.java",
+            "class PaidTest { int a = R.string.debug_only; }",
+        )?;
+        write(
+            "src/androidTestFree/java/FreeTest.java",
+            "class FreeTest { int a = R.string.free_only; }",
+        )?;
+        let index = Indexer::new(app.join("src"), app.join("src"), None, None)?.index()?;
+        let names = |symbols: HashSet<Symbol>| {
+            let mut names: Vec<&str> = symbols.into_iter().map(|s| index.name(s)).collect();
+            names.sort();
+            names
+        };
+
+        assert!(unused_strings(&index, &[]).is_empty());
+        let paid_release: Variant = "paidRelease=paid,release,paidRelease".parse()?;
+        assert_eq!(
+            names(unused_in_variant(&index, &paid_release)),
+            vec!["free_only"]
+        );
+        let paid_debug: Variant = "paidDebug=paid,debug".parse()?;
+        assert_eq!(
+            names(unused_strings(&index, &[paid_release, paid_debug])),
+            vec!["free_only"]
+        );
+        assert!("=paid".parse::<Variant>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use crate::index::ResourceIndex;
+use crate::output::{outln, Format};
+use crate::symbols::Symbol;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+
+/// How the strings in a built app compare with the ones found unused. Each
+/// list is sorted by name.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Verification {
+    /// Unused strings that the build still contains. The build's resource
+    /// shrinker couldn't rule them out, so they may be looked up by name.
+    pub retained: Vec<String>,
+    /// Strings defined in source and not found unused, but which the build
+    /// left out, so they may be unused after all.
+    pub shrunk: Vec<String>,
+}
+
+/// The names of `symbols`, sorted.
+fn names(index: &ResourceIndex, symbols: impl IntoIterator<Item = Symbol>) -> BTreeSet<String> {
+    symbols
+        .into_iter()
+        .map(|symbol| index.name(symbol).to_string())
+        .collect()
+}
+
+/// Compares `unused`, the strings that would be removed, with `built`, the
+/// names of the strings in the built app.
+pub fn verify(index: &ResourceIndex, unused: &[Symbol], built: &HashSet<String>) -> Verification {
+    let unused: HashSet<Symbol> = unused.iter().copied().collect();
+    let used = index
+        .defined_strings()
+        .into_iter()
+        .filter(|s| !unused.contains(s));
+
+    Verification {
+        retained: names(index, unused.iter().copied())
+            .into_iter()
+            .filter(|name| built.contains(name))
+            .collect(),
+        shrunk: names(index, used)
+            .into_iter()
+            .filter(|name| !built.contains(name))
+            .collect(),
+    }
+}
+
+pub fn print(verification: &Verification, format: Format) -> Result<()> {
+    match format {
+        Format::Text => {
+            for (heading, names) in [
+                ("Unused but retained by the build", &verification.retained),
+                ("Used but removed by the build", &verification.shrunk),
+            ] {
+                outln!("{} ({}):", heading, names.len());
+                for name in names {
+                    outln!("  {}", name);
+                }
+            }
+        }
+        Format::Json => outln!("{}", serde_json::to_string_pretty(verification)?),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apk;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_verify() -> Result<()> {
+        let tmp_dir = TempDir::new("verify")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="used">U</string><string name="dynamic">D</string><string name="unused">N</string><string name="debug_only">B</string></resources>"#,
+        )?;
+        fs::write(
+            src_dir.join("Main.java"),
+            "R.string.used; R.string.debug_only;",
+        )?;
+        let index = Indexer::new(src_dir, res_dir, None, None)?.index()?;
+
+        let path = tmp_dir.path().join("app.apk");
+        apk::fixtures::write_zip(
+            &path,
+            &[(
+                "resources.arsc",
+                &apk::fixtures::resources_arsc(&["used", "dynamic", "lib_string"]),
+            )],
+        )?;
+        let built = apk::string_names(&path)?;
+
+        let mut unused: Vec<Symbol> = index.unused_strings().into_iter().collect();
+        unused.sort_by_key(|s| index.name(*s));
+        assert_eq!(
+            verify(&index, &unused, &built),
+            Verification {
+                retained: vec!["dynamic".to_string()],
+                shrunk: vec!["debug_only".to_string()],
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -1,123 +1,2234 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
 use std::fs;
-use std::fs::File;
-use std::io::LineWriter;
+use std::ops::Range;
 use std::path::Path;
 use std::str;
 use xml::attribute::OwnedAttribute;
-use xml::common::Position;
+use xml::common::TextPosition;
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
 use xml::name::OwnedName;
-use xml::reader::{EventReader, XmlEvent};
+use xml::reader::{ParserConfig, XmlEvent};
 
-use std::collections::HashMap;
-use std::io::prelude::*;
+/// How elements are removed.
+#[derive(Debug, Default, Clone)]
+pub struct RemoveOptions {
+    /// Also removes a comment on the lines just before a removed element,
+    /// which usually documents it.
+    pub remove_comments: bool,
+    /// Collapses the blank lines on either side of a removed element into
+    /// one.
+    pub collapse_blank_lines: bool,
+    /// Deletes files left with an empty `<resources>` element, give or take
+    /// comments.
+    pub prune_empty_files: bool,
+}
+
+/// Returns the byte offset just past the `>` ending the tag that starts at
+/// `start`, skipping over quoted attribute values.
+fn tag_end(content: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in content[start..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return Some(start + i + 1),
+            _ => {}
+        }
+    }
+
+    None
+}
 
-#[derive(Debug)]
-struct ElementLocation {
-    start_line: u64,
-    end_line: u64,
+/// Whether `content[start..end]` holds nothing but spaces and tabs.
+fn is_blank(content: &str, range: Range<usize>) -> bool {
+    content[range].bytes().all(|b| b == b' ' || b == b'\t')
 }
 
-fn find_location_to_strip(
+/// The kinds of markup that start with `<`.
+#[derive(Debug, PartialEq)]
+enum Markup {
+    StartTag,
+    EndTag,
+    Comment,
+    /// CDATA sections, processing instructions and declarations.
+    Other,
+}
+
+/// Finds the markup behind each parser event in the raw text, so that edits
+/// can be made at exact byte offsets. The parser's own positions drift in
+/// some documents (after an XML declaration and DOCTYPE, for instance), so
+/// they aren't used. Every start element, end element and (if the parser
+/// reports them) comment event must be passed on, in order.
+struct MarkupCursor<'a> {
+    content: &'a str,
+    cursor: usize,
+    /// The start of a self closing element whose end hasn't been reported.
+    self_closing: Option<usize>,
+}
+
+impl<'a> MarkupCursor<'a> {
+    fn new(content: &'a str) -> MarkupCursor<'a> {
+        MarkupCursor {
+            content,
+            cursor: 0,
+            self_closing: None,
+        }
+    }
+
+    /// Classifies the markup at `start`, returning where it ends.
+    fn markup_at(&self, start: usize) -> Result<(Markup, usize)> {
+        let rest = &self.content[start..];
+        let after = |terminator: &str| {
+            rest.find(terminator)
+                .map(|end| start + end + terminator.len())
+                .ok_or_else(|| anyhow!("Unterminated markup at byte {}", start))
+        };
+
+        Ok(if rest.starts_with("<!--") {
+            (Markup::Comment, after("-->")?)
+        } else if rest.starts_with("<![CDATA[") {
+            (Markup::Other, after("]]>")?)
+        } else if rest.starts_with("<?") {
+            (Markup::Other, after("?>")?)
+        } else if rest.starts_with("<!") {
+            // A DOCTYPE's internal subset may hold '>'s of its own.
+            match (rest.find('['), rest.find('>')) {
+                (Some(open), Some(close)) if open < close => {
+                    let close = rest[open..]
+                        .find(']')
+                        .and_then(|end| rest[open + end..].find('>').map(|i| open + end + i))
+                        .ok_or_else(|| anyhow!("Unterminated markup at byte {}", start))?;
+                    (Markup::Other, start + close + 1)
+                }
+                _ => (Markup::Other, after(">")?),
+            }
+        } else {
+            let end = tag_end(self.content, start)
+                .ok_or_else(|| anyhow!("Unterminated tag at byte {}", start))?;
+            if rest.starts_with("</") {
+                (Markup::EndTag, end)
+            } else {
+                (Markup::StartTag, end)
+            }
+        })
+    }
+
+    /// Moves past the next markup of the given kind, returning its start and
+    /// end. Only CDATA, declarations and (unless wanted) comments may be
+    /// skipped on the way.
+    fn next(&mut self, wanted: Markup) -> Result<Range<usize>> {
+        loop {
+            let start = self.content[self.cursor..]
+                .find('<')
+                .map(|i| self.cursor + i)
+                .ok_or_else(|| anyhow!("Expected {:?} after byte {}", wanted, self.cursor))?;
+            let (markup, end) = self.markup_at(start)?;
+            self.cursor = end;
+            if markup == wanted {
+                return Ok(start..end);
+            }
+            if markup != Markup::Other && markup != Markup::Comment {
+                return Err(anyhow!(
+                    "Expected {:?} but found {:?} at byte {}",
+                    wanted,
+                    markup,
+                    start
+                ));
+            }
+        }
+    }
+
+    /// Returns the offset of the start tag of the element just started.
+    fn start_element(&mut self) -> Result<usize> {
+        let tag = self.next(Markup::StartTag)?;
+        if self.content[..tag.end].ends_with("/>") {
+            self.self_closing = Some(tag.start);
+        }
+        Ok(tag.start)
+    }
+
+    /// Returns the span of the end tag of the element just ended, or of its
+    /// start tag if it's self closing.
+    fn end_element(&mut self) -> Result<Range<usize>> {
+        match self.self_closing.take() {
+            Some(start) => Ok(start..self.cursor),
+            None => self.next(Markup::EndTag),
+        }
+    }
+
+    /// Returns the span of the comment just read.
+    fn comment(&mut self) -> Result<Range<usize>> {
+        self.next(Markup::Comment)
+    }
+}
+
+/// Finds the byte spans of elements matching any of `matchers`, in document
+/// order. Elements nested in a matching element aren't reported separately.
+/// Stops after `limit` elements.
+fn find_locations_to_strip(
     file_content: &str,
-    matcher: &ElementMatcher,
-) -> Result<Option<ElementLocation>> {
-    let mut parser = EventReader::new(file_content.as_bytes());
+    matchers: &[&ElementMatcher],
+    limit: usize,
+    options: &RemoveOptions,
+) -> Result<Vec<Range<usize>>> {
+    let mut parser = ParserConfig::new()
+        .ignore_comments(false)
+        .create_reader(file_content.as_bytes());
+    let mut markup = MarkupCursor::new(file_content);
+
+    let mut locations = Vec::new();
     let mut in_skipped_element = false;
-    let mut start_line: u64 = 0;
-    let mut start_depth = -1;
-    let mut depth = 0;
+    let mut start = 0;
+    let mut start_depth = 0;
+    // The open elements, outermost first.
+    let mut elements = Vec::new();
+    // The span of a comment on lines of its own, if nothing but whitespace
+    // has followed it yet.
+    let mut preceding_comment: Option<Range<usize>> = None;
     loop {
         let e = parser.next();
         match e {
+            Ok(XmlEvent::Comment(_)) => {
+                let comment = markup.comment()?;
+                let line_start = file_content[..comment.start]
+                    .rfind('\n')
+                    .map_or(0, |i| i + 1);
+                preceding_comment = Some(comment).filter(|comment| {
+                    !in_skipped_element && is_blank(file_content, line_start..comment.start)
+                });
+            }
             Ok(XmlEvent::StartElement {
                 name, attributes, ..
             }) => {
-                depth += 1;
-                let pos = parser.position();
-                if matcher.matches(&name, &attributes) {
+                elements.push((name, attributes));
+                let element_start = markup.start_element()?;
+                let comment = preceding_comment.take();
+                if !in_skipped_element && matchers.iter().any(|m| m.matches(&elements)) {
                     in_skipped_element = true;
-                    start_depth = depth;
-                    start_line = pos.row;
+                    start_depth = elements.len();
+                    start = match comment {
+                        // Only when the comment is right above the element.
+                        Some(comment)
+                            if options.remove_comments
+                                && file_content[comment.end..element_start]
+                                    .matches('\n')
+                                    .count()
+                                    <= 1 =>
+                        {
+                            comment.start
+                        }
+                        _ => element_start,
+                    };
                 }
             }
+            Ok(XmlEvent::Characters(_)) | Ok(XmlEvent::CData(_)) => preceding_comment = None,
             Ok(XmlEvent::EndElement { .. }) => {
-                let pos = parser.position();
-                if in_skipped_element && depth == start_depth {
-                    let end_line = pos.row;
-                    return Ok(Some(ElementLocation {
-                        start_line,
-                        end_line,
-                    }));
-                }
-                in_skipped_element = false;
-                depth -= 1;
+                preceding_comment = None;
+                let end_tag = markup.end_element()?;
+                if in_skipped_element && elements.len() == start_depth {
+                    in_skipped_element = false;
+                    locations.push(start..end_tag.end);
+                    if locations.len() >= limit {
+                        return Ok(locations);
+                    }
+                }
+                elements.pop();
             }
-            Ok(XmlEvent::EndDocument) => return Ok(None),
+            Ok(XmlEvent::EndDocument) => return Ok(locations),
             Err(e) => return Err(anyhow::Error::new(e)),
             _ => {}
         }
     }
 }
 
-pub fn remove_element(path: &Path, matcher: &ElementMatcher) -> Result<bool> {
+/// Widens `span` to take in whole lines, including the newline, if nothing
+/// else shares those lines with it.
+fn widen_to_lines(content: &str, span: Range<usize>) -> Range<usize> {
+    let line_start = content[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = content[span.end..]
+        .find('\n')
+        .map_or(content.len(), |i| span.end + i);
+    let trailing = content[span.end..line_end].trim_end_matches('\r').len();
+    if is_blank(content, line_start..span.start) && is_blank(content, span.end..span.end + trailing)
+    {
+        line_start..(line_end + 1).min(content.len())
+    } else {
+        span
+    }
+}
+
+/// Returns the length in bytes of the blank lines at the start of `content`,
+/// and how long the last of them is.
+fn leading_blank_lines(content: &str) -> (usize, usize) {
+    let mut len = 0;
+    let mut last = 0;
+    while let Some(newline) = content[len..].find('\n') {
+        if !content[len..len + newline].trim().is_empty() {
+            break;
+        }
+        last = newline + 1;
+        len += last;
+    }
+
+    (len, last)
+}
+
+/// Whether the last complete line of `content` is blank.
+fn ends_with_blank_line(content: &str) -> bool {
+    content.strip_suffix('\n').is_some_and(|before| {
+        before[before.rfind('\n').map_or(0, |i| i + 1)..]
+            .trim()
+            .is_empty()
+    })
+}
+
+/// Works out what to remove from `file_content` for the element spans in
+/// `locations`, which must be in order. Elements on lines of their own are
+/// removed along with their lines; anything else sharing a line with an
+/// element is kept.
+fn removal_spans(
+    file_content: &str,
+    locations: &[Range<usize>],
+    options: &RemoveOptions,
+) -> Vec<Range<usize>> {
+    // Elements separated only by spaces are widened together, so that a line
+    // holding nothing but removed elements goes entirely.
+    let mut widened = locations.to_vec();
+    let mut first = 0;
+    while first < locations.len() {
+        let mut last = first;
+        while last + 1 < locations.len()
+            && is_blank(file_content, locations[last].end..locations[last + 1].start)
+        {
+            widened[last].end = locations[last + 1].start;
+            last += 1;
+        }
+        let group = widen_to_lines(file_content, locations[first].start..locations[last].end);
+        widened[first].start = group.start;
+        widened[last].end = group.end;
+        first = last + 1;
+    }
+
+    let mut spans: Vec<Range<usize>> = Vec::with_capacity(locations.len());
+    // The end of the content kept before the current span.
+    let mut kept_to = 0;
+    for span in widened {
+        let removed_to = spans.last().map_or(0, |span| span.end);
+        let mut span = span.start.max(removed_to)..span.end.max(removed_to);
+        if span.start > removed_to || spans.is_empty() {
+            kept_to = span.start;
+        }
+
+        if options.collapse_blank_lines && file_content[..span.end].ends_with('\n') {
+            // Keep at most one blank line where the element was.
+            let (len, last) = leading_blank_lines(&file_content[span.end..]);
+            span.end += if ends_with_blank_line(&file_content[..kept_to]) {
+                len
+            } else {
+                len - last
+            };
+        }
+        if !span.is_empty() {
+            spans.push(span);
+        }
+    }
+
+    spans
+}
+
+/// One change made to a file. Positions are in the file as it was before,
+/// not counting any byte order mark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub bytes: Range<usize>,
+    /// The one based lines that `bytes` starts and ends on.
+    pub start_line: u64,
+    pub end_line: u64,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+/// What an editing function changed in a file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EditReport {
+    pub edits: Vec<Edit>,
+    /// Whether the file was deleted after the edits.
+    pub deleted: bool,
+}
+
+impl EditReport {
+    pub fn changed(&self) -> bool {
+        !self.edits.is_empty()
+    }
+}
+
+/// Replaces each range of `file_content` with its text and writes the result
+/// to `path`, if there's anything to change. Ranges must not overlap.
+fn apply_edits(
+    path: &Path,
+    bom: &str,
+    file_content: &str,
+    mut edits: Vec<(Range<usize>, String)>,
+) -> Result<EditReport> {
+    if edits.is_empty() {
+        return Ok(EditReport::default());
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    write_xml(path, bom, &splice(file_content, &edits))?;
+
+    Ok(report_edits(file_content, edits))
+}
+
+/// Describes `edits` to `file_content`, which must be in order, without
+/// making them.
+fn report_edits(file_content: &str, edits: Vec<(Range<usize>, String)>) -> EditReport {
+    let line_of = |offset: usize| file_content[..offset].matches('\n').count() as u64 + 1;
+    EditReport {
+        edits: edits
+            .into_iter()
+            .map(|(bytes, new_text)| Edit {
+                start_line: line_of(bytes.start),
+                end_line: line_of(bytes.end.max(bytes.start + 1) - 1),
+                old_text: file_content[bytes.clone()].to_string(),
+                bytes,
+                new_text,
+            })
+            .collect(),
+        deleted: false,
+    }
+}
+
+const BOM: &str = "\u{feff}";
+
+/// Reads the file at `path`, splitting off any byte order mark so that parser
+/// positions line up with the content. Pass the mark back to `write_xml`.
+fn read_xml(path: &Path) -> Result<(&'static str, String)> {
+    let content = fs::read_to_string(path)?;
+    Ok(match content.strip_prefix(BOM) {
+        Some(content) => (BOM, content.to_string()),
+        None => ("", content),
+    })
+}
+
+/// Writes `content` to `path`, preceded by the byte order mark it was read
+/// with.
+fn write_xml(path: &Path, bom: &str, content: &str) -> Result<()> {
+    write_atomically(path, &[bom, content].concat())
+}
+
+/// Returns the line ending used by `content`.
+fn newline_of(content: &str) -> &'static str {
+    if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// Replaces the file at `path` with `content` by writing a temporary file
+/// alongside it and renaming it over the original, so that a crash never
+/// leaves a half written file. The original's permissions are kept.
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a file", path))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".xdel-{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = fs::write(&tmp_path, content)
+        .and_then(|_| match fs::metadata(path) {
+            Ok(metadata) => fs::set_permissions(&tmp_path, metadata.permissions()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    Ok(result?)
+}
+
+/// Removes the first element matching `matcher` from the file at `path`,
+/// returning its text so that it can be written elsewhere. An element on lines
+/// of its own is taken with its lines.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn take_element(path: &Path, matcher: &ElementMatcher) -> Result<Option<String>> {
     // We're potentially going to have to read the file twice: once for the xml
     // parser, and again for the buffer to write out with an element trimmed out.
     // Start off by reading it all into memory.
-    let file_content = fs::read_to_string(path)?;
+    let (bom, file_content) = read_xml(path)?;
+
+    let options = RemoveOptions::default();
+    let locations = find_locations_to_strip(&file_content, &[matcher], 1, &options)?;
+    let edits = removal_spans(&file_content, &locations, &options)
+        .into_iter()
+        .map(|span| (span, String::new()))
+        .collect();
+    let report = apply_edits(path, bom, &file_content, edits)?;
+
+    Ok(report.edits.into_iter().next().map(|edit| {
+        let mut taken = edit.old_text;
+        if !taken.ends_with('\n') {
+            taken.push_str(newline_of(&file_content));
+        }
+        taken
+    }))
+}
+
+/// Removes every element matching any of `matchers` from the file at `path`
+/// in a single pass, including repeated matches such as duplicate
+/// definitions. The file is only rewritten if something matched. The report
+/// has an edit for each element removed.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn remove_elements(
+    path: &Path,
+    matchers: &[&ElementMatcher],
+    options: &RemoveOptions,
+) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+
+    let locations = find_locations_to_strip(&file_content, matchers, usize::MAX, options)?;
+    let edits = removal_spans(&file_content, &locations, options)
+        .into_iter()
+        .map(|span| (span, String::new()))
+        .collect();
+
+    let mut report = apply_edits(path, bom, &file_content, edits)?;
+    if options.prune_empty_files
+        && report.changed()
+        && is_empty_resources(&fs::read_to_string(path)?)
+    {
+        fs::remove_file(path)?;
+        report.deleted = true;
+    }
+
+    Ok(report)
+}
+
+/// Whether `content` is a `<resources>` element with nothing in it but
+/// whitespace and comments.
+fn is_empty_resources(content: &str) -> bool {
+    let mut parser = ParserConfig::new().create_reader(content.as_bytes());
+    let mut depth = 0;
+    loop {
+        match parser.next() {
+            Ok(XmlEvent::StartElement { name, .. }) => {
+                depth += 1;
+                if depth > 1 || name.local_name != "resources" {
+                    return false;
+                }
+            }
+            Ok(XmlEvent::Characters(_)) | Ok(XmlEvent::CData(_)) => return false,
+            Ok(XmlEvent::EndDocument) => return depth == 1,
+            Err(_) => return false,
+            _ => {}
+        }
+    }
+}
+
+/// Appends the given element text to the `<resources>` element of the file at
+/// `path`, creating the file if it doesn't exist yet.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn append_elements(path: &Path, elements: &[String]) -> Result<()> {
+    let elements = elements.concat().replace("\r\n", "\n");
+    let (bom, content) = if path.exists() {
+        let (bom, file_content) = read_xml(path)?;
+        // Match the line endings of the file being appended to.
+        let elements = elements.replace('\n', newline_of(&file_content));
+        let close = file_content
+            .rfind("</resources>")
+            .ok_or_else(|| anyhow!("No </resources> element in {:?}", path))?;
+        // Insert at the start of the line holding the closing tag, so the
+        // appended lines keep their own indentation.
+        let insert_at = file_content[..close].rfind('\n').map_or(0, |i| i + 1);
+        let mut content = String::with_capacity(file_content.len() + elements.len());
+        content.push_str(&file_content[..insert_at]);
+        content.push_str(&elements);
+        content.push_str(&file_content[insert_at..]);
+        (bom, content)
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n{}</resources>\n",
+            elements
+        );
+        ("", content)
+    };
+
+    write_xml(path, bom, &content)?;
+
+    Ok(())
+}
+
+/// Where `insert_element` puts new elements among the parent's children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsertPosition {
+    First,
+    Last,
+}
+
+/// Returns the whitespace before `offset` on its line, if there's nothing
+/// else before it.
+fn indentation_at(content: &str, offset: usize) -> Option<&str> {
+    let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    Some(&content[line_start..offset]).filter(|_| is_blank(content, line_start..offset))
+}
+
+/// The unit of indentation used in `content`, taken from its first indented
+/// element.
+fn indentation_unit(content: &str) -> &str {
+    content
+        .lines()
+        .find_map(|line| {
+            let element = line.trim_start();
+            let indent = &line[..line.len() - element.len()];
+            Some(indent).filter(|indent| !indent.is_empty() && element.starts_with('<'))
+        })
+        .unwrap_or("    ")
+}
+
+/// Inserts `xml_fragment`, one or more elements, as children of the first
+/// element matching `parent_matcher` in the file at `path`. The fragment is
+/// reindented to match the parent's existing children, or one level deeper
+/// than the parent if it has none, and uses the file's line endings.
+/// Nothing changes if no parent matches.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn insert_element(
+    path: &Path,
+    parent_matcher: &ElementMatcher,
+    xml_fragment: &str,
+    position: InsertPosition,
+) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+    let mut parser = ParserConfig::new().create_reader(file_content.as_bytes());
+    let mut markup = MarkupCursor::new(&file_content);
+
+    // The start of the parent, its first child and its end tag.
+    let mut parent = None;
+    let mut first_child = None;
+    let mut end_tag = None;
+    let mut elements = Vec::new();
+    while end_tag.is_none() {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                elements.push((name, attributes));
+                let start = markup.start_element()?;
+                match parent {
+                    None if parent_matcher.matches(&elements) => {
+                        parent = Some((start, elements.len()));
+                    }
+                    Some((_, depth)) if first_child.is_none() && elements.len() == depth + 1 => {
+                        first_child = Some(start);
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let tag = markup.end_element()?;
+                if parent.is_some_and(|(_, depth)| depth == elements.len()) {
+                    end_tag = Some(tag.start);
+                }
+                elements.pop();
+            }
+            XmlEvent::EndDocument => return Ok(EditReport::default()),
+            _ => {}
+        }
+    }
+    let (parent, end_tag) = match (parent, end_tag) {
+        (Some((parent, _)), Some(end_tag)) => (parent, end_tag),
+        _ => return Ok(EditReport::default()),
+    };
+
+    let newline = newline_of(&file_content);
+    let parent_indent = indentation_at(&file_content, parent).unwrap_or("");
+    let child_indent = match first_child.and_then(|child| indentation_at(&file_content, child)) {
+        Some(indent) => indent.to_string(),
+        None => format!("{}{}", parent_indent, indentation_unit(&file_content)),
+    };
+
+    // Reindent relative to the fragment's first line.
+    let fragment = xml_fragment.trim_end().replace("\r\n", "\n");
+    let fragment_indent = &fragment[..fragment.len() - fragment.trim_start().len()];
+    let lines: Vec<String> = fragment
+        .trim_start()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let line = if i == 0 {
+                line
+            } else {
+                line.strip_prefix(fragment_indent).unwrap_or(line)
+            };
+            format!("{}{}", child_indent, line)
+        })
+        .collect();
+    let lines = lines.join(newline);
+
+    let start_tag_end = tag_end(&file_content, parent)
+        .ok_or_else(|| anyhow!("Unterminated tag at byte {} of {:?}", parent, path))?;
+    let edit = if end_tag == parent {
+        // A self closing parent needs an end tag.
+        let name_end = file_content[parent + 1..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .map_or(start_tag_end, |i| parent + 1 + i);
+        let close = file_content[..start_tag_end - 2].trim_end().len();
+        (
+            close..start_tag_end,
+            format!(
+                ">{}{}{}{}</{}>",
+                newline,
+                lines,
+                newline,
+                parent_indent,
+                &file_content[parent + 1..name_end]
+            ),
+        )
+    } else if position == InsertPosition::First {
+        let rest_of_line = file_content[start_tag_end..]
+            .split('\n')
+            .next()
+            .unwrap_or("");
+        let after = if rest_of_line.trim().is_empty() {
+            String::new()
+        } else {
+            format!("{}{}", newline, child_indent)
+        };
+        (
+            start_tag_end..start_tag_end,
+            format!("{}{}{}", newline, lines, after),
+        )
+    } else if indentation_at(&file_content, end_tag).is_some() {
+        let line_start = file_content[..end_tag].rfind('\n').map_or(0, |i| i + 1);
+        (line_start..line_start, format!("{}{}", lines, newline))
+    } else {
+        (
+            end_tag..end_tag,
+            format!("{}{}{}{}", newline, lines, newline, parent_indent),
+        )
+    };
+
+    apply_edits(path, bom, &file_content, vec![edit])
+}
+
+/// A replacement value for one attribute of the element whose start tag
+/// is at `position`, as reported by the xml parser.
+pub struct AttributeEdit {
+    pub position: TextPosition,
+    pub name: String,
+    pub value: String,
+}
+
+/// Returns the byte offset at which each line of `content` starts.
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Converts a parser position (zero based row, column in chars) into a byte
+/// offset into `content`, whose lines start at `line_starts`.
+fn offset_of(content: &str, line_starts: &[usize], position: TextPosition) -> Option<usize> {
+    let line_start = *line_starts.get(position.row as usize)?;
+    let line = &content[line_start..];
+    let column = line
+        .char_indices()
+        .nth(position.column as usize)
+        .map_or(line.len(), |(i, _)| i);
+
+    Some(line_start + column)
+}
+
+/// Finds attribute `name` (as written, e.g. "android:text") in the start tag
+/// beginning at `start`. Returns the byte range of the whole attribute,
+/// including the whitespace before it, and of its value, excluding the quotes.
+fn attribute_range(
+    content: &str,
+    start: usize,
+    name: &str,
+) -> Option<(Range<usize>, Range<usize>)> {
+    let bytes = content.as_bytes();
+    if bytes.get(start) != Some(&b'<') {
+        return None;
+    }
+
+    // Skip past the element name.
+    let mut i = start + 1;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' && bytes[i] != b'/'
+    {
+        i += 1;
+    }
+
+    loop {
+        let attr_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] == b'>' || bytes[i] == b'/' {
+            return None;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && bytes[i] != b'=' && !bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let attr_name = &content[name_start..i];
+
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'=') {
+            i += 1;
+        }
+        let quote = *bytes.get(i)?;
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+        let value_start = i + 1;
+        let value_end = value_start + content[value_start..].find(quote as char)?;
+        if attr_name == name {
+            return Some((attr_start..value_end + 1, value_start..value_end));
+        }
+        i = value_end + 1;
+    }
+}
+
+/// Rewrites attribute values in the file at `path` in place, leaving the rest
+/// of the file untouched.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn edit_attributes(path: &Path, edits: &[AttributeEdit]) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+
+    let line_starts = line_starts(&file_content);
+    let mut ranges = Vec::with_capacity(edits.len());
+    for edit in edits {
+        // xml-rs puts the root element at the end of its start tag rather than
+        // at its `<`, so look back for it.
+        let start = offset_of(&file_content, &line_starts, edit.position)
+            .and_then(|offset| {
+                if file_content[offset..].starts_with('<') {
+                    Some(offset)
+                } else {
+                    file_content[..offset].rfind('<')
+                }
+            })
+            .ok_or_else(|| anyhow!("Position {} is outside of {:?}", edit.position, path))?;
+        let (_, range) = attribute_range(&file_content, start, &edit.name).ok_or_else(|| {
+            anyhow!(
+                "No attribute {} at {} in {:?}",
+                edit.name,
+                edit.position,
+                path
+            )
+        })?;
+        ranges.push((range, escape_str_attribute(&edit.value).to_string()));
+    }
+
+    apply_edits(path, bom, &file_content, ranges)
+}
+
+/// Returns `content` with each range replaced by its text. Ranges must be in
+/// order and not overlap.
+fn splice(content: &str, edits: &[(Range<usize>, String)]) -> String {
+    let mut spliced = String::with_capacity(content.len());
+    let mut copied_to = 0;
+    for (range, text) in edits {
+        spliced.push_str(&content[copied_to..range.start]);
+        spliced.push_str(text);
+        copied_to = range.end;
+    }
+    spliced.push_str(&content[copied_to..]);
+
+    spliced
+}
+
+/// Finds the byte offsets of the start tags of every element matching
+/// `matcher`, including ones nested in other matches.
+fn find_start_tags(file_content: &str, matcher: &ElementMatcher) -> Result<Vec<usize>> {
+    let mut parser = ParserConfig::new().create_reader(file_content.as_bytes());
+    let mut markup = MarkupCursor::new(file_content);
+
+    let mut starts = Vec::new();
+    let mut elements = Vec::new();
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                elements.push((name, attributes));
+                let start = markup.start_element()?;
+                if matcher.matches(&elements) {
+                    starts.push(start);
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                markup.end_element()?;
+                elements.pop();
+            }
+            XmlEvent::EndDocument => return Ok(starts),
+            _ => {}
+        }
+    }
+}
+
+/// Sets attribute `attr` (as written, e.g. "tools:ignore") to `value` on
+/// every element matching `matcher` in the file at `path`, adding it to the
+/// end of the start tag where it's missing. Nothing else in the file changes.
+/// The report has an edit for each element that changed.
+#[allow(dead_code)]
+pub fn set_attribute(
+    path: &Path,
+    matcher: &ElementMatcher,
+    attr: &str,
+    value: &str,
+) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+
+    let escaped = escape_str_attribute(value).to_string();
+    let mut edits = Vec::new();
+    for start in find_start_tags(&file_content, matcher)? {
+        match attribute_range(&file_content, start, attr) {
+            Some((_, range)) if file_content[range.clone()] == escaped => {}
+            Some((_, range)) => edits.push((range, escaped.clone())),
+            None => {
+                let end = tag_end(&file_content, start)
+                    .ok_or_else(|| anyhow!("Unterminated tag at byte {} of {:?}", start, path))?;
+                let close = file_content[..end - 1]
+                    .trim_end_matches('/')
+                    .trim_end()
+                    .len();
+                edits.push((close..close, format!(" {}=\"{}\"", attr, escaped)));
+            }
+        }
+    }
+
+    apply_edits(path, bom, &file_content, edits)
+}
+
+/// Removes attribute `attr` (as written, e.g. "tools:ignore") from every
+/// element matching `matcher` in the file at `path`, along with the
+/// whitespace before it. Nothing else in the file changes. The report has an
+/// edit for each element that changed.
+#[allow(dead_code)]
+pub fn remove_attribute(path: &Path, matcher: &ElementMatcher, attr: &str) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+
+    let edits: Vec<(Range<usize>, String)> = find_start_tags(&file_content, matcher)?
+        .into_iter()
+        .filter_map(|start| attribute_range(&file_content, start, attr))
+        .map(|(range, _)| (range, String::new()))
+        .collect();
+
+    apply_edits(path, bom, &file_content, edits)
+}
 
-    let location = find_location_to_strip(&file_content, matcher)?;
-    match location {
-        Some(location) => {
-            let file = File::create(path)?;
-            let mut file = LineWriter::new(file);
-            let mut line_number = 0;
-            for line in file_content.lines() {
-                if line_number < location.start_line || line_number > location.end_line {
-                    file.write_all(line.as_bytes())?;
-                    file.write_all(b"\n")?;
+/// `text` as a CDATA section, split where it holds the `]]>` that would end
+/// one.
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// A matching element `set_text` is in.
+struct TextElement<'a> {
+    start: usize,
+    depth: usize,
+    text: &'a str,
+    /// The text it holds now, as parsed.
+    current: String,
+    /// Whether it holds CDATA sections, and text outside of them other than
+    /// whitespace.
+    cdata: bool,
+    plain_text: bool,
+    has_children: bool,
+}
+
+/// Replaces the content of every element in the file at `path` matching one
+/// of the matchers with the text paired with it, escaped as character data,
+/// or as CDATA if the element's text was all CDATA. Elements nested in a
+/// matching element aren't matched themselves, and elements that already
+/// hold the text are left alone, however it's escaped. Nothing else in the
+/// file changes. The report has an edit for each element that changed.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn set_text(path: &Path, texts: &[(&ElementMatcher, &str)]) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+    let mut parser = ParserConfig::new().create_reader(file_content.as_bytes());
+    let mut markup = MarkupCursor::new(&file_content);
+
+    let mut edits = Vec::new();
+    let mut open: Option<TextElement> = None;
+    let mut elements = Vec::new();
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                elements.push((name, attributes));
+                let start = markup.start_element()?;
+                match open.as_mut() {
+                    Some(element) => element.has_children = true,
+                    None => {
+                        open = texts
+                            .iter()
+                            .find(|(matcher, _)| matcher.matches(&elements))
+                            .map(|(_, text)| TextElement {
+                                start,
+                                depth: elements.len(),
+                                text,
+                                current: String::new(),
+                                cdata: false,
+                                plain_text: false,
+                                has_children: false,
+                            });
+                    }
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => {
+                if let Some(element) = open.as_mut() {
+                    element.plain_text |= !text.trim().is_empty();
+                    element.current.push_str(&text);
+                }
+            }
+            XmlEvent::CData(text) => {
+                if let Some(element) = open.as_mut() {
+                    element.cdata = true;
+                    element.current.push_str(&text);
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                let tag = markup.end_element()?;
+                match open.take() {
+                    Some(element) if element.depth == elements.len() => {
+                        let start = element.start;
+                        let text = if element.cdata && !element.plain_text && !element.has_children
+                        {
+                            cdata(element.text)
+                        } else {
+                            escape_str_pcdata(element.text).to_string()
+                        };
+                        if tag.start == start {
+                            // Self closing, so the end tag has to be written.
+                            let name_end = file_content[start + 1..]
+                                .find(|c: char| c.is_whitespace() || c == '/')
+                                .map_or(tag.end, |i| start + 1 + i);
+                            let close = file_content[..tag.end - 2].trim_end().len();
+                            let edit =
+                                format!(">{}</{}>", text, &file_content[start + 1..name_end]);
+                            edits.push((close..tag.end, edit));
+                        } else {
+                            let content_start = tag_end(&file_content, start).ok_or_else(|| {
+                                anyhow!("Unterminated tag at byte {} of {:?}", start, path)
+                            })?;
+                            let unchanged =
+                                !element.has_children && element.current == element.text;
+                            if !unchanged {
+                                edits.push((content_start..tag.start, text));
+                            }
+                        }
+                    }
+                    other => open = other,
                 }
-                line_number -= 1;
+                elements.pop();
             }
-            Ok(true)
+            XmlEvent::EndDocument => break,
+            _ => {}
         }
-        None => Ok(false),
+    }
+
+    apply_edits(path, bom, &file_content, edits)
+}
+
+/// The words in `comment` that look like resource names, with an underscore
+/// somewhere past their first letter, like `settings_title` or the name in
+/// `@string/settings_title`.
+fn mentioned_names(comment: &str) -> impl Iterator<Item = &str> {
+    comment
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|word| {
+            word.starts_with(|c: char| c.is_ascii_alphabetic())
+                && word.trim_end_matches('_').contains('_')
+        })
+}
+
+/// What `tidy` removes.
+#[derive(Debug, Clone)]
+pub struct TidyOptions {
+    /// How many blank lines in a row to keep between elements.
+    pub max_blank_lines: usize,
+    /// Reports what would be removed without changing the file.
+    pub dry_run: bool,
+}
+
+/// Removes the comments in the `<resources>` element of the file at `path`
+/// that look orphaned, since they mention names like `settings_title` but
+/// none that the file defines, along with blank lines between its elements
+/// beyond `max_blank_lines` in a row. Only comments on lines of their own are
+/// removed, and nothing inside an element is touched. The report has an edit
+/// for each comment and each run of blank lines removed.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn tidy(path: &Path, options: &TidyOptions) -> Result<EditReport> {
+    let (bom, file_content) = read_xml(path)?;
+    let mut parser = ParserConfig::new()
+        .ignore_comments(false)
+        .create_reader(file_content.as_bytes());
+    let mut markup = MarkupCursor::new(&file_content);
+
+    // The content of the root element, the spans of its children, the
+    // comments between them and the names defined anywhere.
+    let mut content = 0..0;
+    let mut children = Vec::new();
+    let mut comments = Vec::new();
+    let mut names = HashSet::new();
+    let mut depth = 0;
+    let mut child_start = 0;
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement { attributes, .. } => {
+                depth += 1;
+                let start = markup.start_element()?;
+                match depth {
+                    1 => content.start = tag_end(&file_content, start).unwrap_or(start),
+                    2 => child_start = start,
+                    _ => {}
+                }
+                names.extend(
+                    attributes
+                        .into_iter()
+                        .filter(|a| a.name.local_name == "name" && a.name.prefix.is_none())
+                        .map(|a| a.value),
+                );
+            }
+            XmlEvent::EndElement { .. } => {
+                let tag = markup.end_element()?;
+                match depth {
+                    1 => content.end = tag.start.max(content.start),
+                    2 => children.push(child_start..tag.end),
+                    _ => {}
+                }
+                depth -= 1;
+            }
+            XmlEvent::Comment(text) => {
+                let span = markup.comment()?;
+                if depth == 1 {
+                    comments.push((span, text));
+                }
+            }
+            XmlEvent::EndDocument => break,
+            _ => {}
+        }
+    }
+
+    let mut edits = Vec::new();
+    // Orphaned comments, as whole lines.
+    let mut removed_lines = Vec::new();
+    for (span, text) in comments {
+        let mut mentioned = mentioned_names(&text).peekable();
+        if mentioned.peek().is_none() || mentioned.any(|name| names.contains(name)) {
+            continue;
+        }
+        let lines = widen_to_lines(&file_content, span.clone());
+        if lines != span {
+            removed_lines.push(lines.clone());
+            edits.push((lines, String::new()));
+        }
+    }
+
+    // Runs of blank lines between the children, counting the lines of
+    // removed comments as part of the run they split.
+    let in_child = |offset: usize| children.iter().any(|child| child.contains(&offset));
+    let mut blank_run: Vec<Range<usize>> = Vec::new();
+    let mut line_start = content.start;
+    let flush = |run: &mut Vec<Range<usize>>, edits: &mut Vec<(Range<usize>, String)>| {
+        // The first lines of a run are kept, and the lines after them
+        // removed together where they're next to each other.
+        let extra = run.iter().skip(options.max_blank_lines);
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        for line in extra {
+            match spans.last_mut() {
+                Some(span) if span.end == line.start => span.end = line.end,
+                _ => spans.push(line.clone()),
+            }
+        }
+        edits.extend(spans.into_iter().map(|span| (span, String::new())));
+        run.clear();
+    };
+    while line_start < content.end {
+        let line_end = file_content[line_start..content.end]
+            .find('\n')
+            .map_or(content.end, |i| line_start + i + 1);
+        let line = line_start..line_end;
+        let removed = removed_lines
+            .iter()
+            .find(|lines| lines.start == line.start)
+            .cloned();
+        if let Some(lines) = removed {
+            line_start = lines.end;
+            continue;
+        }
+        let blank = file_content[line.clone()].ends_with('\n')
+            && file_content[line.clone()].trim().is_empty()
+            && !in_child(line.start)
+            && line.start > content.start;
+        if blank {
+            blank_run.push(line);
+        } else {
+            flush(&mut blank_run, &mut edits);
+        }
+        line_start = line_end;
+    }
+    flush(&mut blank_run, &mut edits);
+
+    if options.dry_run {
+        edits.sort_by_key(|(range, _)| range.start);
+        return Ok(report_edits(&file_content, edits));
+    }
+    apply_edits(path, bom, &file_content, edits)
+}
+
+/// How a name's namespace is constrained.
+#[derive(Debug, PartialEq)]
+enum Namespace {
+    /// The prefix as written in the document, e.g. `tools`.
+    Prefix(String),
+    /// The namespace URI the prefix is bound to, which holds whatever prefix
+    /// the document uses.
+    Uri(String),
+}
+
+/// Matches an element or attribute name. Without a namespace constraint, a
+/// name matches by local name alone.
+#[derive(Debug, PartialEq)]
+struct NameTest {
+    namespace: Option<Namespace>,
+    /// None matches any local name.
+    local_name: Option<String>,
+}
+
+impl NameTest {
+    /// Parses `local`, `prefix:local` or `{uri}local`, where the local name
+    /// may be `*`.
+    fn parse(name: &str) -> Result<NameTest> {
+        let (namespace, local_name) = if let Some(rest) = name.strip_prefix('{') {
+            let (uri, local_name) = rest
+                .split_once('}')
+                .ok_or_else(|| anyhow!("Unterminated {{ in {:?}", name))?;
+            (Some(Namespace::Uri(uri.to_string())), local_name)
+        } else {
+            match name.split_once(':') {
+                Some((prefix, local_name)) => {
+                    (Some(Namespace::Prefix(prefix.to_string())), local_name)
+                }
+                None => (None, name),
+            }
+        };
+
+        let local_name = match local_name {
+            "" => return Err(anyhow!("Missing local name in {:?}", name)),
+            "*" => None,
+            local_name => Some(local_name.to_string()),
+        };
+
+        Ok(NameTest {
+            namespace,
+            local_name,
+        })
+    }
+
+    fn matches(&self, name: &OwnedName) -> bool {
+        let namespace_matches = match &self.namespace {
+            None => true,
+            Some(Namespace::Prefix(prefix)) => name.prefix.as_ref() == Some(prefix),
+            Some(Namespace::Uri(uri)) => name.namespace.as_ref() == Some(uri),
+        };
+
+        namespace_matches
+            && self
+                .local_name
+                .as_ref()
+                .is_none_or(|local_name| *local_name == name.local_name)
     }
 }
 
+/// One step of an element path: the element's name and the attribute values
+/// it must have.
+struct Step {
+    name: NameTest,
+    attributes: Vec<(NameTest, String)>,
+    /// Whether this step may be any number of levels below the previous one
+    /// (`//`), rather than a direct child (`/`).
+    descendant: bool,
+}
+
+impl Step {
+    fn matches(&self, name: &OwnedName, attrs: &[OwnedAttribute]) -> bool {
+        self.name.matches(name)
+            && self.attributes.iter().all(|(name, value)| {
+                attrs
+                    .iter()
+                    .any(|attr| name.matches(&attr.name) && attr.value == *value)
+            })
+    }
+}
+
+/// Matches elements by name and attribute values, optionally constrained by
+/// their ancestors.
 pub struct ElementMatcher {
-    local_name: String,
-    local_attribute_values: HashMap<String, String>,
+    steps: Vec<Step>,
+    /// Whether the first step must be the document's root element.
+    anchored: bool,
 }
 
 impl ElementMatcher {
+    /// Matches elements with the given local name, in any namespace, anywhere
+    /// in the document.
     pub fn for_local_name(local_name: &str) -> ElementMatcher {
         ElementMatcher {
-            local_name: local_name.to_string(),
-            local_attribute_values: HashMap::new(),
+            steps: vec![Step {
+                name: NameTest {
+                    namespace: None,
+                    local_name: Some(local_name.to_string()),
+                },
+                attributes: Vec::new(),
+                descendant: true,
+            }],
+            anchored: false,
         }
     }
 
+    /// Matches elements by a simple XPath-like expression, e.g.
+    /// `resources/string[@name='foo']`. Steps are element names or `*`,
+    /// separated by `/` for a child or `//` for a descendant, each with any
+    /// number of `[@attr='value']` predicates. Element and attribute names
+    /// may be qualified by a prefix, e.g. `@tools:ignore`, or by a namespace
+    /// URI, e.g. `@{http://schemas.android.com/tools}ignore`. A leading `/`
+    /// anchors the path at the root element; otherwise it may start anywhere.
+    pub fn for_path(path: &str) -> Result<ElementMatcher> {
+        let invalid = |reason: &str| anyhow!("Invalid path {:?}: {}", path, reason);
+
+        let anchored = path.starts_with('/') && !path.starts_with("//");
+        let mut rest = path;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            let descendant = if let Some(after) = rest.strip_prefix("//") {
+                rest = after;
+                true
+            } else if let Some(after) = rest.strip_prefix('/') {
+                rest = after;
+                steps.is_empty() && !anchored
+            } else if steps.is_empty() {
+                !anchored
+            } else {
+                return Err(invalid("expected /"));
+            };
+
+            // Namespace URIs may hold slashes.
+            let uri_end = if rest.starts_with('{') {
+                rest.find('}').ok_or_else(|| invalid("unterminated {"))?
+            } else {
+                0
+            };
+            let name_end = rest[uri_end..]
+                .find(['/', '['])
+                .map_or(rest.len(), |i| uri_end + i);
+            if name_end == 0 {
+                return Err(invalid("empty step"));
+            }
+            let name = NameTest::parse(&rest[..name_end])?;
+            rest = &rest[name_end..];
+
+            let mut attributes = Vec::new();
+            while let Some(predicate) = rest.strip_prefix('[') {
+                let end = predicate
+                    .find(']')
+                    .ok_or_else(|| invalid("unterminated ["))?;
+                let predicate_attr = predicate[..end]
+                    .strip_prefix('@')
+                    .ok_or_else(|| invalid("expected [@attr='value']"))?;
+                let uri_end = if predicate_attr.starts_with('{') {
+                    predicate_attr.find('}').unwrap_or(0)
+                } else {
+                    0
+                };
+                let (attr, value) = predicate_attr[uri_end..]
+                    .split_once('=')
+                    .map(|(attr, value)| (&predicate_attr[..uri_end + attr.len()], value))
+                    .ok_or_else(|| invalid("expected [@attr='value']"))?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('\'')
+                    .and_then(|value| value.strip_suffix('\''))
+                    .or_else(|| {
+                        value
+                            .strip_prefix('"')
+                            .and_then(|value| value.strip_suffix('"'))
+                    })
+                    .ok_or_else(|| invalid("attribute values must be quoted"))?;
+                attributes.push((NameTest::parse(attr.trim())?, value.to_string()));
+                rest = &predicate[end + 1..];
+            }
+
+            steps.push(Step {
+                name,
+                attributes,
+                descendant,
+            });
+        }
+        if steps.is_empty() {
+            return Err(invalid("no steps"));
+        }
+
+        Ok(ElementMatcher { steps, anchored })
+    }
+
+    /// Requires matching elements to have an attribute with this local name,
+    /// in any namespace, and value.
     pub fn attr<'a>(&'a mut self, local_name: &str, value: &str) -> &'a mut ElementMatcher {
-        self.local_attribute_values
-            .insert(local_name.to_string(), value.to_string());
+        if let Some(step) = self.steps.last_mut() {
+            let name = NameTest {
+                namespace: None,
+                local_name: Some(local_name.to_string()),
+            };
+            step.attributes.push((name, value.to_string()));
+        }
 
         self
     }
 
-    fn matches(&self, name: &OwnedName, attrs: &[OwnedAttribute]) -> bool {
-        if !self.local_name.eq(&name.local_name) {
+    /// Whether the last of `elements`, whose ancestors are the ones before it,
+    /// matches.
+    fn matches(&self, elements: &[(OwnedName, Vec<OwnedAttribute>)]) -> bool {
+        match elements.len() {
+            0 => false,
+            len => self.matches_from(self.steps.len() - 1, elements, len - 1),
+        }
+    }
+
+    /// Whether `steps[..=step]` matches with `steps[step]` at `elements[element]`.
+    fn matches_from(
+        &self,
+        step: usize,
+        elements: &[(OwnedName, Vec<OwnedAttribute>)],
+        element: usize,
+    ) -> bool {
+        let (name, attrs) = &elements[element];
+        if !self.steps[step].matches(name, attrs) {
             return false;
         }
+        if step == 0 {
+            return !self.anchored || element == 0;
+        }
 
-        for attr in attrs {
-            if let Some(required_val) = self.local_attribute_values.get(&attr.name.local_name) {
-                if !required_val.eq(&attr.value) {
-                    return false;
-                }
-            }
+        if self.steps[step].descendant {
+            (0..element).any(|ancestor| self.matches_from(step - 1, elements, ancestor))
+        } else {
+            element > 0 && self.matches_from(step - 1, elements, element - 1)
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_remove_elements() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources>
+    <string name="a">A</string>
+    <string name="b">B</string>
+    <string-array name="c">
+        <item>C</item>
+    </string-array>
+    <string name="d">D</string>
+</resources>
+"#,
+        )?;
+
+        let mut a = ElementMatcher::for_local_name("string");
+        a.attr("name", "a");
+        let mut c = ElementMatcher::for_local_name("string-array");
+        c.attr("name", "c");
+        let mut missing = ElementMatcher::for_local_name("string");
+        missing.attr("name", "missing");
+
+        assert_eq!(
+            remove_elements(&path, &[&a, &c, &missing], &RemoveOptions::default())?
+                .edits
+                .len(),
+            2
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <string name="b">B</string>
+    <string name="d">D</string>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_with_comments() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        let content = r#"<resources>
+    <!-- Shown on the welcome screen,
+         before sign in. -->
+    <string name="a">A</string>
+    <!-- Not about b -->
+
+    <string name="b">B</string>
+    <string name="c">C</string> <!-- About d -->
+    <string name="d">D</string>
+</resources>
+"#;
+        let matchers: Vec<ElementMatcher> = ["a", "b", "d"]
+            .iter()
+            .map(|name| {
+                let mut matcher = ElementMatcher::for_local_name("string");
+                matcher.attr("name", name);
+                matcher
+            })
+            .collect();
+        let matchers: Vec<&ElementMatcher> = matchers.iter().collect();
+
+        fs::write(&path, content)?;
+        let options = RemoveOptions {
+            remove_comments: true,
+            ..Default::default()
+        };
+        assert_eq!(remove_elements(&path, &matchers, &options)?.edits.len(), 3);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <!-- Not about b -->
+
+    <string name="c">C</string> <!-- About d -->
+</resources>
+"#
+        );
+
+        fs::write(&path, content)?;
+        remove_elements(&path, &matchers, &RemoveOptions::default())?;
+        assert!(fs::read_to_string(&path)?.contains("before sign in. -->"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_collapsing_blank_lines() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources>
+    <string name="a">A</string>
+
+    <string name="b">B</string>
+
+    <string name="c">C</string>
+
+
+    <string name="d">D</string>
+</resources>
+"#,
+        )?;
+
+        let mut b = ElementMatcher::for_local_name("string");
+        b.attr("name", "b");
+        let options = RemoveOptions {
+            collapse_blank_lines: true,
+            ..Default::default()
+        };
+        remove_elements(&path, &[&b], &options)?;
+        // Blank lines away from the removal are left alone.
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <string name="a">A</string>
+
+    <string name="c">C</string>
+
+
+    <string name="d">D</string>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_sharing_lines() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources><string name="a">A</string><string name="b" note="a > b"/><string name="c">C</string></resources>"#,
+        )?;
+
+        let mut b = ElementMatcher::for_local_name("string");
+        b.attr("name", "b");
+        let mut c = ElementMatcher::for_local_name("string");
+        c.attr("name", "c");
+        assert_eq!(
+            remove_elements(&path, &[&b, &c], &RemoveOptions::default())?
+                .edits
+                .len(),
+            2
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources><string name="a">A</string></resources>"#
+        );
+
+        fs::write(
+            &path,
+            r#"<resources>
+    <string name="a">A</string> <string name="b">B</string>
+    <string name="c">C</string>
+</resources>
+"#,
+        )?;
+        assert_eq!(
+            take_element(&path, &b)?,
+            Some("<string name=\"b\">B</string>\n".to_string())
+        );
+        assert_eq!(
+            take_element(&path, &c)?,
+            Some("    <string name=\"c\">C</string>\n".to_string())
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <string name="a">A</string> 
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_by_path() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("nav.xml");
+        let content = r#"<navigation xmlns:android="http://schemas.android.com/apk/res/android">
+    <fragment android:id="@+id/home">
+        <action android:id="@+id/to_detail" />
+        <argument android:name="id" />
+    </fragment>
+    <fragment android:id="@+id/detail">
+        <action android:id="@+id/to_home" />
+    </fragment>
+    <action android:id="@+id/to_home" />
+</navigation>
+"#;
+        fs::write(&path, content)?;
+
+        let matcher = ElementMatcher::for_path(
+            "/navigation/fragment[@android:id='@+id/detail']/action[@android:id='@+id/to_home']",
+        )?;
+        assert_eq!(
+            remove_elements(&path, &[&matcher], &RemoveOptions::default())?
+                .edits
+                .len(),
+            1
+        );
+        // Only the nested action is removed, not the top level one.
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            content.replacen("        <action android:id=\"@+id/to_home\" />\n", "", 1)
+        );
+
+        let arguments = ElementMatcher::for_path("navigation//argument")?;
+        let any_action = ElementMatcher::for_path("*/action[@id=\"@+id/to_detail\"]")?;
+        assert_eq!(
+            remove_elements(&path, &[&arguments, &any_action], &RemoveOptions::default())?
+                .edits
+                .len(),
+            2
+        );
+        let anchored = ElementMatcher::for_path("/fragment")?;
+        assert_eq!(
+            remove_elements(&path, &[&anchored], &RemoveOptions::default())?
+                .edits
+                .len(),
+            0
+        );
+
+        assert!(ElementMatcher::for_path("a[@b=c]").is_err());
+        assert!(ElementMatcher::for_path("a//").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_by_namespace() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("AndroidManifest.xml");
+        let content = r#"<manifest xmlns:android="http://schemas.android.com/apk/res/android" xmlns:t="http://schemas.android.com/tools">
+    <uses-permission t:name="CAMERA" />
+    <uses-permission android:name="CAMERA" />
+</manifest>
+"#;
+        fs::write(&path, content)?;
+
+        // The document uses t rather than the usual tools prefix.
+        let by_uri = ElementMatcher::for_path(
+            "manifest/uses-permission[@{http://schemas.android.com/tools}name='CAMERA']",
+        )?;
+        let by_prefix = ElementMatcher::for_path("uses-permission[@tools:name='CAMERA']")?;
+        assert_eq!(
+            remove_elements(&path, &[&by_uri, &by_prefix], &RemoveOptions::default())?
+                .edits
+                .len(),
+            1
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            content.replace("    <uses-permission t:name=\"CAMERA\" />\n", "")
+        );
+
+        let wrong_namespace = ElementMatcher::for_path("{http://example.com}manifest//*")?;
+        assert_eq!(
+            remove_elements(&path, &[&wrong_namespace], &RemoveOptions::default())?
+                .edits
+                .len(),
+            0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_and_remove_attribute() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources xmlns:tools="http://schemas.android.com/tools">
+    <string name="a" translatable='true'>A</string>
+    <string
+        name="b">B</string>
+    <plurals name="c" />
+</resources>
+"#,
+        )?;
+
+        let strings = ElementMatcher::for_path("resources/string")?;
+        assert_eq!(
+            set_attribute(&path, &strings, "translatable", "false")?
+                .edits
+                .len(),
+            2
+        );
+        assert_eq!(
+            set_attribute(&path, &strings, "translatable", "false")?
+                .edits
+                .len(),
+            0
+        );
+        let plurals = ElementMatcher::for_path("plurals")?;
+        set_attribute(&path, &plurals, "tools:ignore", "Missing<Quantity>")?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources xmlns:tools="http://schemas.android.com/tools">
+    <string name="a" translatable='false'>A</string>
+    <string
+        name="b" translatable="false">B</string>
+    <plurals name="c" tools:ignore="Missing&lt;Quantity&gt;" />
+</resources>
+"#
+        );
+
+        let mut b = ElementMatcher::for_local_name("string");
+        b.attr("name", "b");
+        assert_eq!(remove_attribute(&path, &b, "translatable")?.edits.len(), 1);
+        assert_eq!(
+            remove_attribute(&path, &plurals, "tools:ignore")?
+                .edits
+                .len(),
+            1
+        );
+        assert_eq!(
+            remove_attribute(&path, &plurals, "tools:ignore")?
+                .edits
+                .len(),
+            0
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources xmlns:tools="http://schemas.android.com/tools">
+    <string name="a" translatable='false'>A</string>
+    <string
+        name="b">B</string>
+    <plurals name="c" />
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_text() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources>
+    <!-- Kept as is -->
+    <string name="a">A</string>
+    <string name="b"><b>Bold</b> B</string>
+    <string name="c" />
+    <string name="d">D</string>
+</resources>
+"#,
+        )?;
+
+        let a = ElementMatcher::for_path("/resources/string[@name='a']")?;
+        let b = ElementMatcher::for_path("/resources/string[@name='b']")?;
+        let c = ElementMatcher::for_path("/resources/string[@name='c']")?;
+        let d = ElementMatcher::for_path("/resources/string[@name='d']")?;
+        let report = set_text(&path, &[(&a, "A & a"), (&b, "B"), (&c, "C"), (&d, "D")])?;
+        assert_eq!(report.edits.len(), 3);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <!-- Kept as is -->
+    <string name="a">A &amp; a</string>
+    <string name="b">B</string>
+    <string name="c">C</string>
+    <string name="d">D</string>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cdata_round_trips() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources>
+    <string name="same"><![CDATA[<b>Bold</b> & more]]></string>
+    <string name="changed"><![CDATA[<b>Old</b>]]></string>
+    <string name="refs">&#169; 2024</string>
+    <string name="mixed">Hi <![CDATA[<i>there</i>]]></string>
+    <string name="moved"><![CDATA[<u>Moved</u>]]></string>
+</resources>
+"#,
+        )?;
+
+        let matcher =
+            |name: &str| ElementMatcher::for_path(&format!("/resources/string[@name='{}']", name));
+        let (same, changed, refs, mixed) = (
+            matcher("same")?,
+            matcher("changed")?,
+            matcher("refs")?,
+            matcher("mixed")?,
+        );
+        let report = set_text(
+            &path,
+            &[
+                (&same, "<b>Bold</b> & more"),
+                (&changed, "<b>New</b> ]]> end"),
+                (&refs, "© 2024"),
+                (&mixed, "Hi <i>you</i>"),
+            ],
+        )?;
+        // Text that's the same however it's escaped is left alone, and CDATA
+        // stays CDATA.
+        assert_eq!(report.edits.len(), 2);
+        assert_eq!(
+            take_element(&path, &matcher("moved")?)?,
+            Some("    <string name=\"moved\"><![CDATA[<u>Moved</u>]]></string>\n".to_string())
+        );
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <string name="same"><![CDATA[<b>Bold</b> & more]]></string>
+    <string name="changed"><![CDATA[<b>New</b> ]]]]><![CDATA[> end]]></string>
+    <string name="refs">&#169; 2024</string>
+    <string name="mixed">Hi &lt;i&gt;you&lt;/i&gt;</string>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_element() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            "<resources>\n  <string name=\"a\">A</string>\n  <plurals name=\"p\"/>\n</resources>\n",
+        )?;
+
+        let resources = ElementMatcher::for_path("/resources")?;
+        let plurals = ElementMatcher::for_path("resources/plurals")?;
+        insert_element(
+            &path,
+            &resources,
+            "<string name=\"b\">B</string>\n<string name=\"c\">C</string>",
+            InsertPosition::Last,
+        )?;
+        insert_element(
+            &path,
+            &resources,
+            "    <string name=\"first\">F</string>\n",
+            InsertPosition::First,
+        )?;
+        insert_element(
+            &path,
+            &plurals,
+            "<item quantity=\"one\">One</item>",
+            InsertPosition::Last,
+        )?;
+        assert!(!insert_element(
+            &path,
+            &ElementMatcher::for_path("missing")?,
+            "<x/>",
+            InsertPosition::Last
+        )?
+        .changed());
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+  <string name="first">F</string>
+  <string name="a">A</string>
+  <plurals name="p">
+    <item quantity="one">One</item>
+  </plurals>
+  <string name="b">B</string>
+  <string name="c">C</string>
+</resources>
+"#
+        );
+
+        fs::write(&path, "<resources></resources>")?;
+        insert_element(
+            &path,
+            &resources,
+            "<string name=\"a\">A</string>",
+            InsertPosition::Last,
+        )?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "<resources>\n    <string name=\"a\">A</string>\n</resources>"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_nested_multiline_and_adjacent_elements() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("values.xml");
+        let content = r#"<resources>
+    <declare-styleable name="outer">
+        <declare-styleable name="inner"><attr name="x" /></declare-styleable>
+        <attr name="y" />
+    </declare-styleable>
+    <string
+        name="multi"
+        >Spans
+        lines</string><string name="adjacent">A</string>
+    <declare-styleable name="last"><declare-styleable name="outer" /></declare-styleable>
+</resources>
+"#;
+        fs::write(&path, content)?;
+
+        // A match nested in a non-matching element of the same name.
+        let mut inner = ElementMatcher::for_local_name("declare-styleable");
+        inner.attr("name", "inner");
+        let mut multi = ElementMatcher::for_local_name("string");
+        multi.attr("name", "multi");
+        let mut adjacent = ElementMatcher::for_local_name("string");
+        adjacent.attr("name", "adjacent");
+        let report = remove_elements(
+            &path,
+            &[&inner, &multi, &adjacent],
+            &RemoveOptions::default(),
+        )?;
+        assert_eq!(report.edits.len(), 3);
+        assert_eq!(report.edits[1].start_line, 6);
+        assert_eq!(report.edits[1].end_line, 9);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <declare-styleable name="outer">
+        <attr name="y" />
+    </declare-styleable>
+    <declare-styleable name="last"><declare-styleable name="outer" /></declare-styleable>
+</resources>
+"#
+        );
+
+        // Matches nested in matches go with their parent, and the parent ends
+        // at its own end tag rather than its child's.
+        let mut outer = ElementMatcher::for_local_name("declare-styleable");
+        outer.attr("name", "outer");
+        let report = remove_elements(&path, &[&outer], &RemoveOptions::default())?;
+        assert_eq!(report.edits.len(), 2);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<resources>
+    <declare-styleable name="last"></declare-styleable>
+</resources>
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_preserves_escaping() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        let keep = r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE resources [ <!ENTITY app "My App"> ]>
+<resources>
+    <string name="cdata"><![CDATA[<b>Bold</b> & <string name="b">not an element</string>]]></string>
+    <string name="entities">Tom &amp; Jerry &lt;3 &quot;&apos; &app;</string>
+    <string name="refs" note="a &gt; b">&#169; &#x1F600; ©</string>
+"#;
+        fs::write(
+            &path,
+            format!(
+                "{}    <string name=\"b\">B &amp; <![CDATA[<i>]]></string>\n</resources>\n",
+                keep
+            ),
+        )?;
+
+        let mut b = ElementMatcher::for_local_name("string");
+        b.attr("name", "b");
+        let report = remove_elements(&path, &[&b], &RemoveOptions::default())?;
+        assert_eq!(report.edits.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            format!("{}</resources>\n", keep)
+        );
+
+        let mut refs = ElementMatcher::for_local_name("string");
+        refs.attr("name", "refs");
+        set_attribute(&path, &refs, "note", "a < b")?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            format!("{}</resources>\n", keep.replace("a &gt; b", "a &lt; b"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_pruning_empty_files() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        let content = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- Strings for the old flow. -->
+<resources>
+    <string name="a">A</string>
+</resources>
+"#;
+        fs::write(&path, content)?;
+
+        let a = ElementMatcher::for_local_name("string");
+        let options = RemoveOptions {
+            prune_empty_files: true,
+            ..Default::default()
+        };
+        let report = remove_elements(&path, &[&a], &options)?;
+        assert!(report.changed() && report.deleted);
+        assert!(!path.exists());
+
+        fs::write(
+            &path,
+            content.replace("</resources>", "<plurals name=\"p\" />\n</resources>"),
+        )?;
+        let report = remove_elements(&path, &[&a], &options)?;
+        assert!(report.changed() && !report.deleted);
+        assert!(path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_elements_duplicates() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            r#"<resources>
+    <string name="a">A</string>
+    <string name="b">B</string>
+    <string name="a">Again</string>
+    <string name="a">And again</string>
+</resources>
+"#,
+        )?;
+
+        let mut a = ElementMatcher::for_local_name("string");
+        a.attr("name", "a");
+        let report = remove_elements(&path, &[&a], &RemoveOptions::default())?;
+        assert_eq!(report.edits.len(), 3);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "<resources>\n    <string name=\"b\">B</string>\n</resources>\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_report() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            "<resources>\n    <string name=\"a\">A</string>\n    <string name=\"b\">B</string>\n</resources>\n",
+        )?;
+
+        let mut b = ElementMatcher::for_local_name("string");
+        b.attr("name", "b");
+        let report = remove_elements(&path, &[&b], &RemoveOptions::default())?;
+        assert_eq!(
+            report.edits,
+            vec![Edit {
+                bytes: 44..76,
+                start_line: 3,
+                end_line: 3,
+                old_text: "    <string name=\"b\">B</string>\n".to_string(),
+                new_text: String::new(),
+            }]
+        );
+        assert!(!remove_elements(&path, &[&b], &RemoveOptions::default())?.changed());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserves_line_endings_and_bom() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(
+            &path,
+            "\u{feff}<resources>\r\n    <string name=\"a\">A</string>\r\n    <string name=\"b\">B</string>\r\n</resources>\r\n",
+        )?;
+
+        let mut a = ElementMatcher::for_local_name("string");
+        a.attr("name", "a");
+        remove_elements(&path, &[&a], &RemoveOptions::default())?;
+        append_elements(&path, &["    <string name=\"c\">C</string>\n".to_string()])?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "\u{feff}<resources>\r\n    <string name=\"b\">B</string>\r\n    <string name=\"c\">C</string>\r\n</resources>\r\n"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomically_keeps_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        fs::write(&path, "old")?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640))?;
+
+        write_atomically(&path, "new")?;
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o640);
+        // No temporary files are left behind.
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tidy() -> Result<()> {
+        let tmp_dir = TempDir::new("xeditor")?;
+        let path = tmp_dir.path().join("strings.xml");
+        let content = r#"<!-- Copyright notice_text -->
+<resources>
+
+
+    <!-- Shown in settings_title -->
+    <string name="settings_title">Settings</string>
+
+    <!-- Used by old_welcome
+         and old_goodbye -->
+
+
+    <!-- A general note -->
+    <string name="about">About
+
+
+
+us</string> <!-- removed_inline -->
+    <plurals name="songs"><item quantity="one">Song</item></plurals>
+    <!-- @string/removed_thing -->
+</resources>
+"#;
+        fs::write(&path, content)?;
+
+        let dry_run = TidyOptions {
+            max_blank_lines: 1,
+            dry_run: true,
+        };
+        let report = tidy(&path, &dry_run)?;
+        assert_eq!(fs::read_to_string(&path)?, content);
+        let lines: Vec<(u64, u64)> = report
+            .edits
+            .iter()
+            .map(|edit| (edit.start_line, edit.end_line))
+            .collect();
+        assert_eq!(lines, vec![(4, 4), (8, 9), (10, 11), (19, 19)]);
+
+        let options = TidyOptions {
+            dry_run: false,
+            ..dry_run
+        };
+        assert_eq!(tidy(&path, &options)?, report);
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            r#"<!-- Copyright notice_text -->
+<resources>
+
+    <!-- Shown in settings_title -->
+    <string name="settings_title">Settings</string>
+
+    <!-- A general note -->
+    <string name="about">About
+
+
+
+us</string> <!-- removed_inline -->
+    <plurals name="songs"><item quantity="one">Song</item></plurals>
+</resources>
+"#
+        );
+        assert!(!tidy(&path, &options)?.changed());
 
-        true
+        Ok(())
     }
 }
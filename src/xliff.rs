@@ -0,0 +1,676 @@
+use crate::index::{self, ResourceIndex};
+use crate::xeditor::{self, ElementMatcher, InsertPosition};
+use anyhow::{anyhow, Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use xml::attribute::OwnedAttribute;
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
+use xml::reader::{ParserConfig, XmlEvent};
+
+/// Which version of XLIFF to write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XliffVersion {
+    V1_2,
+    V2_0,
+}
+
+impl FromStr for XliffVersion {
+    type Err = Error;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1.2" => Ok(XliffVersion::V1_2),
+            "2.0" | "2" => Ok(XliffVersion::V2_0),
+            _ => Err(anyhow!("Unrecognized XLIFF version {}", value)),
+        }
+    }
+}
+
+/// Converts an Android locale qualifier (e.g. pt-rBR) into a BCP 47 language
+/// tag (pt-BR).
+pub fn to_language_tag(locale: &str) -> String {
+    if let Some(tag) = locale.strip_prefix("b+") {
+        return tag.replace('+', "-");
+    }
+    match locale.split_once("-r") {
+        Some((language, region)) => format!("{}-{}", language, region),
+        None => locale.to_string(),
+    }
+}
+
+/// Converts a BCP 47 language tag (e.g. pt-BR) into the qualifier Android
+/// uses for its values directory (pt-rBR). Tags that don't fit the
+/// language-region form use Android's b+ syntax. Qualifiers are returned as
+/// they are.
+pub fn to_android_locale(tag: &str) -> String {
+    if tag.starts_with("b+") || tag.contains("-r") {
+        return tag.to_string();
+    }
+    let parts: Vec<&str> = tag.split(['-', '_']).collect();
+    match parts.as_slice() {
+        [language] => language.to_string(),
+        [language, region] if region.len() == 2 || region.chars().all(|c| c.is_ascii_digit()) => {
+            format!("{}-r{}", language, region.to_uppercase())
+        }
+        _ => format!("b+{}", parts.join("+")),
+    }
+}
+
+/// One string to translate.
+struct Unit<'a> {
+    name: &'a str,
+    source: &'a str,
+    target: Option<&'a str>,
+}
+
+/// Writes the strings of every default values file, along with any existing
+/// translations into `locale` (an Android qualifier or a language tag), as
+/// XLIFF. Values are written as they appear in the resources, Android escapes
+/// and all. Each file's `original` is its path relative to `res_root`.
+/// Units whose whitespace a tool could normalize away are marked
+/// `xml:space="preserve"`, so that they come back as they went. Returns the
+/// number of strings written.
+pub fn export(
+    index: &ResourceIndex,
+    res_root: &Path,
+    locale: &str,
+    source_language: &str,
+    version: XliffVersion,
+    out: &mut dyn Write,
+) -> Result<usize> {
+    let android_locale = to_android_locale(locale);
+    let target_language = to_language_tag(&android_locale);
+
+    // Existing translations by res directory and name.
+    let mut translations: HashMap<(&Path, &str), &str> = HashMap::new();
+    for file in index.files() {
+        if index::locale_of(file.path()) == android_locale {
+            let res_dir = res_dir_of(Path::new(file.path()));
+            for definition in file.string_definitions() {
+                translations.insert((res_dir, index.name(definition.name)), &definition.value);
+            }
+        }
+    }
+
+    let mut files: Vec<(&str, Vec<Unit>)> = index
+        .files()
+        .iter()
+        .filter(|file| index::locale_of(file.path()) == "default")
+        .map(|file| {
+            let res_dir = res_dir_of(Path::new(file.path()));
+            let units = file
+                .string_definitions()
+                .iter()
+                .map(|definition| {
+                    let name = index.name(definition.name);
+                    Unit {
+                        name,
+                        source: &definition.value,
+                        target: translations.get(&(res_dir, name)).copied(),
+                    }
+                })
+                .collect();
+            (file.path(), units)
+        })
+        .filter(|(_, units): &(&str, Vec<Unit>)| !units.is_empty())
+        .collect();
+    files.sort_by_key(|(path, _)| *path);
+
+    let language = escape_str_attribute(source_language);
+    let target_language = escape_str_attribute(&target_language);
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    match version {
+        XliffVersion::V1_2 => writeln!(
+            out,
+            "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">"
+        )?,
+        XliffVersion::V2_0 => writeln!(
+            out,
+            "<xliff version=\"2.0\" xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" srcLang=\"{}\" trgLang=\"{}\">",
+            language, target_language
+        )?,
+    }
+
+    let mut count = 0;
+    for (i, (path, units)) in files.iter().enumerate() {
+        let original = Path::new(path)
+            .strip_prefix(res_root)
+            .unwrap_or_else(|_| Path::new(path));
+        let original = escape_str_attribute(original.to_str().unwrap_or(path));
+        match version {
+            XliffVersion::V1_2 => {
+                writeln!(
+                    out,
+                    "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\">",
+                    original, language, target_language
+                )?;
+                writeln!(out, "    <body>")?;
+            }
+            XliffVersion::V2_0 => {
+                writeln!(out, "  <file id=\"f{}\" original=\"{}\">", i + 1, original)?
+            }
+        }
+
+        for unit in units {
+            let target = match unit.target {
+                Some(target) => format!("<target>{}</target>", escape_str_pcdata(target)),
+                None => String::new(),
+            };
+            let name = escape_str_attribute(unit.name);
+            let source = escape_str_pcdata(unit.source);
+            let space = if is_normalizable(unit.source) || unit.target.is_some_and(is_normalizable)
+            {
+                " xml:space=\"preserve\""
+            } else {
+                ""
+            };
+            match version {
+                XliffVersion::V1_2 => writeln!(
+                    out,
+                    "      <trans-unit id=\"{}\" resname=\"{}\"{}><source>{}</source>{}</trans-unit>",
+                    name, name, space, source, target
+                )?,
+                XliffVersion::V2_0 => writeln!(
+                    out,
+                    "    <unit id=\"{}\"{}><segment><source>{}</source>{}</segment></unit>",
+                    name, space, source, target
+                )?,
+            }
+            count += 1;
+        }
+
+        if version == XliffVersion::V1_2 {
+            writeln!(out, "    </body>")?;
+        }
+        writeln!(out, "  </file>")?;
+    }
+    writeln!(out, "</xliff>")?;
+
+    Ok(count)
+}
+
+/// Whether normalizing the whitespace in `text`, as XML lets tools do outside
+/// of `xml:space="preserve"`, would change it.
+fn is_normalizable(text: &str) -> bool {
+    text.split_whitespace().collect::<Vec<_>>().join(" ") != text
+}
+
+/// `text` with its whitespace trimmed and each run of it collapsed into a
+/// space, as aapt does to a resource value, except inside the double quoted
+/// parts of the value, where aapt keeps whitespace as it is.
+fn normalize_whitespace(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() && !quoted {
+            space = true;
+            escaped = false;
+            continue;
+        }
+        if space && !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        space = false;
+        if c == '"' && !escaped {
+            quoted = !quoted;
+        }
+        escaped = c == '\\' && !escaped;
+        normalized.push(c);
+    }
+    normalized
+}
+
+/// Whether `attributes` set `xml:space`, and if so whether to preserve.
+fn xml_space(attributes: &[OwnedAttribute]) -> Option<bool> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == "space" && a.name.prefix.as_deref() == Some("xml"))
+        .map(|a| a.value == "preserve")
+}
+
+/// The translations for one file of an XLIFF document.
+#[derive(Debug, Default, PartialEq)]
+struct TranslatedFile {
+    original: String,
+    /// The target language tag.
+    language: Option<String>,
+    /// Names and their translated values, in document order.
+    units: Vec<(String, String)>,
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name && a.name.prefix.is_none())
+        .map(|a| a.value.as_str())
+}
+
+/// Reads the translated units of an XLIFF 1.2 or 2.0 document. Units
+/// without a target, or with an empty one, are left out, as are alternative
+/// translations. Inline markup in a target is dropped, keeping its text.
+/// Targets keep their whitespace as it is where `xml:space="preserve"` is in
+/// effect; elsewhere it's normalized outside the value's quoted parts, which
+/// changes nothing aapt would see.
+fn read_translations(input: impl Read) -> Result<Vec<TranslatedFile>> {
+    let mut parser = ParserConfig::new()
+        .cdata_to_characters(true)
+        .create_reader(input);
+
+    let mut files = Vec::new();
+    // The document's trgLang (2.0), which files inherit.
+    let mut document_language = None;
+    let mut file: Option<TranslatedFile> = None;
+    let mut unit = None;
+    let mut target: Option<String> = None;
+    // The local names of the open elements, and whether each preserves
+    // whitespace.
+    let mut elements: Vec<(String, bool)> = Vec::new();
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let parent = elements.last().map(|(name, _)| name.as_str());
+                let preserve = xml_space(&attributes)
+                    .unwrap_or_else(|| elements.last().is_some_and(|(_, preserve)| *preserve));
+                match (name.local_name.as_str(), parent) {
+                    ("xliff", _) => {
+                        document_language = attribute(&attributes, "trgLang").map(str::to_string);
+                    }
+                    ("file", _) => {
+                        let original = attribute(&attributes, "original")
+                            .ok_or_else(|| anyhow!("<file> without an original attribute"))?;
+                        file = Some(TranslatedFile {
+                            original: original.to_string(),
+                            language: attribute(&attributes, "target-language")
+                                .map(str::to_string)
+                                .or_else(|| document_language.clone()),
+                            units: Vec::new(),
+                        });
+                    }
+                    ("trans-unit", _) | ("unit", _) => {
+                        unit = ["resname", "name", "id"]
+                            .iter()
+                            .find_map(|a| attribute(&attributes, a))
+                            .map(str::to_string);
+                    }
+                    ("target", Some("trans-unit")) | ("target", Some("segment")) => {
+                        target = Some(String::new())
+                    }
+                    _ => {}
+                }
+                elements.push((name.local_name, preserve));
+            }
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) => {
+                if let Some(target) = target.as_mut() {
+                    target.push_str(&text);
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                let preserve = elements.pop().is_some_and(|(_, preserve)| preserve);
+                match name.local_name.as_str() {
+                    "target" => {
+                        if let (Some(file), Some(unit), Some(mut target)) =
+                            (file.as_mut(), unit.as_ref(), target.take())
+                        {
+                            if !preserve {
+                                target = normalize_whitespace(&target);
+                            }
+                            if !target.is_empty() {
+                                file.units.push((unit.clone(), target));
+                            }
+                        }
+                    }
+                    "trans-unit" | "unit" => unit = None,
+                    "file" => files.extend(file.take()),
+                    _ => {}
+                }
+            }
+            XmlEvent::EndDocument => return Ok(files),
+            _ => {}
+        }
+    }
+}
+
+/// What importing did to one values file.
+pub struct Imported {
+    pub path: PathBuf,
+    pub updated: usize,
+    pub added: usize,
+}
+
+/// Writes the translations in the XLIFF file at `xliff` into the values
+/// directories for their target language, next to the default values file
+/// each was exported from (its `original`, relative to `res_root`). Existing
+/// strings are updated in place and new ones are appended, creating files as
+/// needed; nothing else in the files changes. `locale` overrides the target
+/// language given in the document.
+pub fn import(res_root: &Path, xliff: &Path, locale: Option<&str>) -> Result<Vec<Imported>> {
+    let files = read_translations(BufReader::new(File::open(xliff)?))?;
+
+    let mut imported = Vec::new();
+    for file in files {
+        if file.units.is_empty() {
+            continue;
+        }
+        let language = locale
+            .or(file.language.as_deref())
+            .ok_or_else(|| anyhow!("No target language for {}, use --locale", file.original))?;
+        let path = translation_path(res_root, &file.original, &to_android_locale(language))?;
+
+        imported.push(write_translations(path, &file.units)?);
+    }
+
+    Ok(imported)
+}
+
+/// Writes strings into the values file at `path`, updating those it already
+/// defines in place and appending the rest, and creating the file if it
+/// doesn't exist. Nothing else in the file changes.
+pub fn write_translations(path: PathBuf, units: &[(String, String)]) -> Result<Imported> {
+    if !path.exists() {
+        xeditor::append_elements(&path, &[])?;
+    }
+    let existing = string_names(&path)?;
+    let (updates, additions): (Vec<_>, Vec<_>) =
+        units.iter().partition(|(name, _)| existing.contains(name));
+
+    let matchers: Vec<ElementMatcher> = updates
+        .iter()
+        .map(|(name, _)| ElementMatcher::for_path(&format!("/resources/string[@name='{}']", name)))
+        .collect::<Result<_>>()?;
+    let texts: Vec<(&ElementMatcher, &str)> = matchers
+        .iter()
+        .zip(&updates)
+        .map(|(matcher, (_, value))| (matcher, value.as_str()))
+        .collect();
+    let updated = xeditor::set_text(&path, &texts)?.edits.len();
+
+    if !additions.is_empty() {
+        let fragment: Vec<String> = additions
+            .iter()
+            .map(|(name, value)| {
+                // Line breaks as references, so reindenting the new
+                // elements leaves the values alone.
+                let value = escape_str_pcdata(value)
+                    .replace('\r', "&#13;")
+                    .replace('\n', "&#10;");
+                format!(
+                    "<string name=\"{}\">{}</string>",
+                    escape_str_attribute(name),
+                    value
+                )
+            })
+            .collect();
+        let resources = ElementMatcher::for_path("/resources")?;
+        let report = xeditor::insert_element(
+            &path,
+            &resources,
+            &fragment.join("\n"),
+            InsertPosition::Last,
+        )?;
+        if !report.changed() {
+            return Err(anyhow!("No <resources> element in {:?}", path));
+        }
+    }
+
+    Ok(Imported {
+        path,
+        updated,
+        added: additions.len(),
+    })
+}
+
+/// Where the translation of the default values file at `original` into
+/// `locale` belongs, e.g. values-fr/strings.xml for values/strings.xml.
+fn translation_path(res_root: &Path, original: &str, locale: &str) -> Result<PathBuf> {
+    let original = Path::new(original);
+    let in_values = original
+        .parent()
+        .and_then(Path::file_name)
+        .is_some_and(|dir| dir == "values");
+    if !in_values || original.components().any(|c| c == Component::ParentDir) {
+        return Err(anyhow!(
+            "{:?} isn't a default values file under the res directory",
+            original
+        ));
+    }
+
+    let values_dir = original.parent().unwrap();
+    let res_dir = values_dir.parent().unwrap_or_else(|| Path::new(""));
+    Ok(res_root
+        .join(res_dir)
+        .join(format!("values-{}", locale))
+        .join(original.file_name().unwrap()))
+}
+
+/// The names of the strings defined in the values file at `path`.
+fn string_names(path: &Path) -> Result<HashSet<String>> {
+    let content = fs::read_to_string(path)?;
+    let mut parser = ParserConfig::new().create_reader(content.as_bytes());
+    let mut names = HashSet::new();
+    let mut depth = 0;
+    loop {
+        match parser.next()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                depth += 1;
+                if depth == 2 && name.local_name == "string" {
+                    names.extend(attribute(&attributes, "name").map(str::to_string));
+                }
+            }
+            XmlEvent::EndElement { .. } => depth -= 1,
+            XmlEvent::EndDocument => return Ok(names),
+            _ => {}
+        }
+    }
+}
+
+/// The res directory holding the values directory that holds `path`.
+fn res_dir_of(path: &Path) -> &Path {
+    path.parent()
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Indexer;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export() -> Result<()> {
+        assert_eq!(to_android_locale("pt-BR"), "pt-rBR");
+        assert_eq!(to_android_locale("pt-rBR"), "pt-rBR");
+        assert_eq!(to_android_locale("fr"), "fr");
+        assert_eq!(to_android_locale("sr-Latn"), "b+sr+Latn");
+        assert_eq!(to_language_tag("pt-rBR"), "pt-BR");
+        assert_eq!(to_language_tag("b+sr+Latn"), "sr-Latn");
+
+        let tmp_dir = TempDir::new("xliff")?;
+        let res_dir = tmp_dir.path().join("res");
+        let src_dir = tmp_dir.path().join("src");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::create_dir_all(res_dir.join("values-pt-rBR"))?;
+        fs::create_dir_all(&src_dir)?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="hello">Hello &amp; welcome</string><string name="bye">Bye</string></resources>"#,
+        )?;
+        fs::write(
+            res_dir.join("values-pt-rBR/strings.xml"),
+            r#"<resources><string name="hello">Olá</string></resources>"#,
+        )?;
+        let index = Indexer::new(src_dir, res_dir.clone(), None, None)?.index()?;
+
+        let mut out = Vec::new();
+        let count = export(
+            &index,
+            &res_dir,
+            "pt-BR",
+            "en",
+            XliffVersion::V1_2,
+            &mut out,
+        )?;
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(out)?,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file original="values/strings.xml" source-language="en" target-language="pt-BR" datatype="plaintext">
+    <body>
+      <trans-unit id="hello" resname="hello"><source>Hello &amp; welcome</source><target>Olá</target></trans-unit>
+      <trans-unit id="bye" resname="bye"><source>Bye</source></trans-unit>
+    </body>
+  </file>
+</xliff>
+"#
+        );
+
+        let mut out = Vec::new();
+        export(
+            &index,
+            &res_dir,
+            "pt-rBR",
+            "en",
+            XliffVersion::V2_0,
+            &mut out,
+        )?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains(r#"srcLang="en" trgLang="pt-BR""#));
+        assert!(out.contains(r#"<unit id="bye"><segment><source>Bye</source></segment></unit>"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let tmp_dir = TempDir::new("xliff")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values-fr"))?;
+        fs::write(
+            res_dir.join("values-fr/strings.xml"),
+            r#"<resources>
+    <!-- Untouched -->
+    <string name="other">Autre</string>
+    <string name="hello">Bonjour</string>
+</resources>
+"#,
+        )?;
+        let xliff_1_2 = tmp_dir.path().join("fr.xlf");
+        fs::write(
+            &xliff_1_2,
+            r#"<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file original="values/strings.xml" source-language="en" target-language="fr">
+    <body>
+      <trans-unit id="hello" resname="hello"><source>Hello</source><target>Salut &amp; bienvenue</target>
+        <alt-trans><target>Allô</target></alt-trans>
+      </trans-unit>
+      <trans-unit id="bye"><source>Bye</source><target>Au revoir</target></trans-unit>
+      <trans-unit id="untranslated"><source>Untranslated</source></trans-unit>
+    </body>
+  </file>
+</xliff>
+"#,
+        )?;
+        let xliff_2_0 = tmp_dir.path().join("pt.xlf");
+        fs::write(
+            &xliff_2_0,
+            r#"<xliff version="2.0" xmlns="urn:oasis:names:tc:xliff:document:2.0" srcLang="en" trgLang="pt-BR">
+  <file id="f1" original="feature/values/strings.xml">
+    <unit id="hello"><segment><source>Hello</source><target><![CDATA[Olá]]></target></segment></unit>
+  </file>
+</xliff>
+"#,
+        )?;
+
+        let imported = import(&res_dir, &xliff_1_2, None)?;
+        assert_eq!(imported.len(), 1);
+        assert_eq!((imported[0].updated, imported[0].added), (1, 1));
+        assert_eq!(
+            fs::read_to_string(res_dir.join("values-fr/strings.xml"))?,
+            r#"<resources>
+    <!-- Untouched -->
+    <string name="other">Autre</string>
+    <string name="hello">Salut &amp; bienvenue</string>
+    <string name="bye">Au revoir</string>
+</resources>
+"#
+        );
+
+        import(&res_dir, &xliff_2_0, None)?;
+        assert_eq!(
+            fs::read_to_string(res_dir.join("feature/values-pt-rBR/strings.xml"))?,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n    <string name=\"hello\">Olá</string>\n</resources>\n"
+        );
+
+        fs::write(
+            &xliff_2_0,
+            fs::read_to_string(&xliff_2_0)?.replace("feature/values", "../values"),
+        )?;
+        assert!(import(&res_dir, &xliff_2_0, Some("de")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_significant_whitespace() -> Result<()> {
+        let tmp_dir = TempDir::new("xliff")?;
+        let res_dir = tmp_dir.path().join("res");
+        fs::create_dir_all(res_dir.join("values"))?;
+        fs::write(
+            res_dir.join("values/strings.xml"),
+            r#"<resources><string name="plain">Plain</string><string name="unit">"  km"</string><string name="spaced">Two  spaces</string></resources>"#,
+        )?;
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+        let mut out = Vec::new();
+        export(&index, &res_dir, "fr", "en", XliffVersion::V1_2, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains(r#"<trans-unit id="plain" resname="plain"><source>"#));
+        assert!(out.contains(r#"<trans-unit id="unit" resname="unit" xml:space="preserve">"#));
+        assert!(out.contains(r#"<trans-unit id="spaced" resname="spaced" xml:space="preserve">"#));
+
+        let xliff = tmp_dir.path().join("fr.xlf");
+        fs::write(
+            &xliff,
+            "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">
+  <file original=\"values/strings.xml\" target-language=\"fr\">
+    <body>
+      <trans-unit id=\"plain\"><source>Plain</source><target>
+        Tout   simple \"  cité  \"
+      </target></trans-unit>
+      <trans-unit id=\"unit\" xml:space=\"preserve\"><source/><target>\"  km\"</target></trans-unit>
+      <trans-unit id=\"lines\" xml:space=\"preserve\"><source/><target>Deux\n  lignes</target></trans-unit>
+      <trans-unit id=\"reset\" xml:space=\"preserve\"><source/><target xml:space=\"default\"> Sans  espaces </target></trans-unit>
+    </body>
+  </file>
+</xliff>
+",
+        )?;
+        import(&res_dir, &xliff, None)?;
+        let translations = res_dir.join("values-fr/strings.xml");
+        let index =
+            Indexer::new(tmp_dir.path().join("src"), res_dir.clone(), None, None)?.index()?;
+        let values: HashMap<&str, &str> = index
+            .files()
+            .iter()
+            .filter(|file| Path::new(file.path()) == translations)
+            .flat_map(|file| file.string_definitions())
+            .map(|definition| (index.name(definition.name), definition.value.as_str()))
+            .collect();
+        assert_eq!(values["plain"], r#"Tout simple "  cité  ""#);
+        assert_eq!(values["unit"], r#""  km""#);
+        assert_eq!(values["lines"], "Deux\n  lignes");
+        assert_eq!(values["reset"], "Sans espaces");
+
+        Ok(())
+    }
+}